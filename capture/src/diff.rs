@@ -0,0 +1,127 @@
+//! Content-based diffing of captured data.
+
+use tracing_core::Metadata;
+
+use crate::{CapturedEvent, CapturedSpan, Storage};
+
+/// Difference between two [`Storage`]s returned by [`Storage::diff()`].
+///
+/// Spans and events are matched by their content rather than by identity, since identities
+/// are not comparable across storages. Spans are matched by name, level, target and recorded
+/// values; events are matched by level, target and values (including the message). Values
+/// are compared irrespective of their order, which for spans depends on the order of recording
+/// values. Matching takes multiplicity into account: if a span is captured twice
+/// in the baseline storage and once in the other one, one of the baseline spans
+/// will be considered removed.
+#[derive(Debug)]
+#[non_exhaustive]
+pub struct StorageDiff<'a> {
+    /// Spans present in the baseline storage, but not in the other one.
+    pub removed_spans: Vec<CapturedSpan<'a>>,
+    /// Spans present in the other storage, but not in the baseline one.
+    pub added_spans: Vec<CapturedSpan<'a>>,
+    /// Events present in the baseline storage, but not in the other one.
+    pub removed_events: Vec<CapturedEvent<'a>>,
+    /// Events present in the other storage, but not in the baseline one.
+    pub added_events: Vec<CapturedEvent<'a>>,
+}
+
+impl StorageDiff<'_> {
+    /// Checks whether the diff is empty, i.e., the compared storages contain the same spans
+    /// and events (up to the ordering).
+    pub fn is_empty(&self) -> bool {
+        self.removed_spans.is_empty()
+            && self.added_spans.is_empty()
+            && self.removed_events.is_empty()
+            && self.added_events.is_empty()
+    }
+}
+
+fn spans_match(lhs: &CapturedSpan<'_>, rhs: &CapturedSpan<'_>) -> bool {
+    lhs.metadata().name() == rhs.metadata().name()
+        && metadata_match(lhs.metadata(), rhs.metadata())
+        && lhs.field_count() == rhs.field_count()
+        && lhs
+            .values()
+            .all(|(name, value)| rhs.value(name) == Some(value))
+}
+
+fn events_match(lhs: &CapturedEvent<'_>, rhs: &CapturedEvent<'_>) -> bool {
+    metadata_match(lhs.metadata(), rhs.metadata())
+        && lhs.field_count() == rhs.field_count()
+        && lhs
+            .values()
+            .all(|(name, value)| rhs.value(name) == Some(value))
+}
+
+fn metadata_match(lhs: &Metadata<'_>, rhs: &Metadata<'_>) -> bool {
+    lhs.level() == rhs.level() && lhs.target() == rhs.target()
+}
+
+/// Splits items into ones only present in `baseline` and ones only present in `other`.
+fn diff_items<T>(
+    baseline: impl Iterator<Item = T>,
+    other: impl Iterator<Item = T>,
+    matches: impl Fn(&T, &T) -> bool,
+) -> (Vec<T>, Vec<T>) {
+    let mut unmatched_other: Vec<_> = other.map(Some).collect();
+    let removed = baseline
+        .filter(|item| {
+            let matching_item = unmatched_other.iter_mut().find(|other_item| {
+                other_item
+                    .as_ref()
+                    .is_some_and(|other| matches(item, other))
+            });
+            // Each item in `other` can only be matched once.
+            matching_item.map(Option::take).is_none()
+        })
+        .collect();
+    let added = unmatched_other.into_iter().flatten().collect();
+    (removed, added)
+}
+
+impl Storage {
+    /// Computes the content-based difference between this (baseline) storage and the `other`
+    /// one. This can be used in regression tests to compare a "golden" capture to a fresh one.
+    /// See [`StorageDiff`] for details on how spans and events are matched.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use tracing_subscriber::{layer::SubscriberExt, Registry};
+    /// # use tracing_capture::{CaptureLayer, SharedStorage};
+    /// fn capture(extra_event: bool) -> SharedStorage {
+    ///     let storage = SharedStorage::default();
+    ///     let subscriber = Registry::default().with(CaptureLayer::new(&storage));
+    ///     tracing::subscriber::with_default(subscriber, || {
+    ///         tracing::info_span!("compute", count = 3).in_scope(|| {
+    ///             tracing::info!("started");
+    ///             if extra_event {
+    ///                 tracing::warn!("retrying");
+    ///             }
+    ///         });
+    ///     });
+    ///     storage
+    /// }
+    ///
+    /// let (golden, fresh) = (capture(false), capture(true));
+    /// let (golden, fresh) = (golden.lock(), fresh.lock());
+    /// let diff = golden.diff(&fresh);
+    /// assert!(diff.added_spans.is_empty() && diff.removed_spans.is_empty());
+    /// assert!(diff.removed_events.is_empty());
+    /// assert_eq!(diff.added_events.len(), 1);
+    /// assert_eq!(diff.added_events[0].message(), Some("retrying"));
+    /// ```
+    pub fn diff<'a>(&'a self, other: &'a Storage) -> StorageDiff<'a> {
+        let (removed_spans, added_spans) =
+            diff_items(self.all_spans(), other.all_spans(), spans_match);
+        let (removed_events, added_events) =
+            diff_items(self.all_events(), other.all_events(), events_match);
+        StorageDiff {
+            removed_spans,
+            added_spans,
+            removed_events,
+            added_events,
+        }
+    }
+}
@@ -231,3 +231,37 @@ impl<'a> Iterator for DescendantEvents<'a> {
         self.inner.size_hint()
     }
 }
+
+/// Event together with the path of its ancestor spans. Returned by [`Storage::flattened()`].
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct FlatEvent<'a> {
+    /// Ancestor spans of the event, starting from a [root span](Storage::root_spans())
+    /// and ending in the direct parent of the event. Empty for
+    /// [root events](Storage::root_events()).
+    pub span_path: Vec<CapturedSpan<'a>>,
+    /// Event.
+    pub event: CapturedEvent<'a>,
+}
+
+impl<'a> FlatEvent<'a> {
+    pub(crate) fn new(event: CapturedEvent<'a>) -> Self {
+        let mut span_path: Vec<_> = event.ancestors().collect();
+        span_path.reverse();
+        Self { span_path, event }
+    }
+
+    /// Iterates over the names of spans in the [path](Self::span_path).
+    pub fn span_names(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.span_path.iter().map(|span| span.metadata().name())
+    }
+}
+
+/// Item of the combined span / event timeline returned by [`Storage::timeline()`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TimelineItem<'a> {
+    /// Span (positioned at its creation).
+    Span(CapturedSpan<'a>),
+    /// Event.
+    Event(CapturedEvent<'a>),
+}
@@ -0,0 +1,333 @@
+//! Conversion of captured data to JSON values.
+
+use serde_json::{json, Map, Value};
+
+use std::{
+    cmp::Reverse,
+    fmt,
+    io::Write,
+    sync::{Mutex, PoisonError},
+    time::Instant,
+};
+
+use crate::{CapturedEvent, CapturedEventId, CapturedSpan, CapturedSpanId, Storage, TracedValue};
+
+impl CapturedEvent<'_> {
+    /// Converts this event into a JSON value. The value has the following shape:
+    ///
+    /// ```text
+    /// {
+    ///     "level": "INFO",
+    ///     "target": "my_crate::module",
+    ///     "message": "computed Fibonacci number", // `null` if there is no message
+    ///     "fields": { "result": { "int": 5 } }
+    /// }
+    /// ```
+    ///
+    /// Fields exclude the message and use the serialized presentation of [`TracedValue`]s.
+    ///
+    /// [`TracedValue`]: tracing_tunnel::TracedValue
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use serde_json::json;
+    /// # use tracing_subscriber::{layer::SubscriberExt, Registry};
+    /// # use tracing_capture::{CaptureLayer, SharedStorage};
+    /// let storage = SharedStorage::default();
+    /// let subscriber = Registry::default().with(CaptureLayer::new(&storage));
+    /// tracing::subscriber::with_default(subscriber, || {
+    ///     tracing::info!(target: "app", result = 5_u64, "done");
+    /// });
+    ///
+    /// let storage = storage.lock();
+    /// let event = storage.all_events().next().unwrap();
+    /// let json = event.to_json();
+    /// assert_eq!(json["message"], "done");
+    /// assert_eq!(json["fields"], json!({ "result": { "u_int": 5 } }));
+    /// ```
+    #[cfg_attr(docsrs, doc(cfg(feature = "json")))]
+    pub fn to_json(&self) -> Value {
+        let metadata = self.metadata();
+        let fields: Map<_, _> = self
+            .values()
+            .filter(|(name, _)| *name != "message")
+            .map(|(name, value)| (name.to_owned(), json!(value)))
+            .collect();
+        json!({
+            "level": metadata.level().as_str(),
+            "target": metadata.target(),
+            "message": self.message(),
+            "fields": fields,
+        })
+    }
+}
+
+/// Begin / end marker of a span in the Chrome trace format.
+#[derive(Debug)]
+struct ChromeTraceMarker<'a> {
+    span: CapturedSpan<'a>,
+    instant: Instant,
+    is_begin: bool,
+    track: usize,
+}
+
+/// Track (i.e., `tid`) in the Chrome trace containing properly nested spans.
+#[derive(Debug)]
+struct ChromeTraceTrack<'a> {
+    index: usize,
+    /// Spans open on this track together with their end instants.
+    open_spans: Vec<(CapturedSpan<'a>, Instant)>,
+    markers: Vec<ChromeTraceMarker<'a>>,
+}
+
+impl<'a> ChromeTraceTrack<'a> {
+    fn new(index: usize) -> Self {
+        Self {
+            index,
+            open_spans: vec![],
+            markers: vec![],
+        }
+    }
+
+    /// Closes spans ending no later than the specified instant, or all spans if `instant`
+    /// is not specified.
+    fn close_spans(&mut self, instant: Option<Instant>) {
+        while let Some(&(span, end)) = self.open_spans.last() {
+            if instant.is_some_and(|instant| end > instant) {
+                break;
+            }
+            self.open_spans.pop();
+            self.markers.push(ChromeTraceMarker {
+                span,
+                instant: end,
+                is_begin: false,
+                track: self.index,
+            });
+        }
+    }
+
+    /// Attempts to open a span on this track. This is only possible if the span is nested
+    /// within the innermost open span (if any).
+    fn try_open(&mut self, span: CapturedSpan<'a>, begin: Instant, end: Instant) -> bool {
+        self.close_spans(Some(begin));
+        if self
+            .open_spans
+            .last()
+            .is_some_and(|&(_, open_end)| open_end < end)
+        {
+            return false;
+        }
+        self.open_spans.push((span, end));
+        self.markers.push(ChromeTraceMarker {
+            span,
+            instant: begin,
+            is_begin: true,
+            track: self.index,
+        });
+        true
+    }
+}
+
+impl Storage {
+    /// Exports captured spans in the [Chrome trace event format], which can be viewed
+    /// in `chrome://tracing`, [Perfetto UI] and similar tools. The output is a JSON object
+    /// with the `traceEvents` array containing begin (`"ph": "B"`) and end (`"ph": "E"`)
+    /// markers for each span. Timestamps are expressed in microseconds since the creation
+    /// of the earliest exported span.
+    ///
+    /// Spans are exported only if [timing](crate::CaptureLayer::with_timing()) is enabled.
+    /// A span begins when it was created and ends when it was closed (or, if the span
+    /// is not closed, when it was exited for the last time); spans that were never closed
+    /// or exited are skipped. Since thread IDs are not captured, spans are distributed
+    /// among tracks (`tid`s) so that begin / end markers on each track are properly nested.
+    /// Each span is placed on the first track where it fits into the innermost open span
+    /// (or where no spans are open); e.g., a child span outliving its parent is placed
+    /// on a separate track.
+    ///
+    /// [Chrome trace event format]: https://docs.google.com/document/d/1CvAClvFfyA5R-PhYUmn5OOQtYMH4h6I0nSsKchNAySU
+    /// [Perfetto UI]: https://ui.perfetto.dev/
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use tracing_subscriber::{layer::SubscriberExt, Registry};
+    /// # use tracing_capture::{CaptureLayer, SharedStorage};
+    /// let storage = SharedStorage::default();
+    /// let layer = CaptureLayer::new(&storage).with_timing();
+    /// let subscriber = Registry::default().with(layer);
+    /// tracing::subscriber::with_default(subscriber, || {
+    ///     tracing::info_span!("outer").in_scope(|| {
+    ///         tracing::info_span!("inner", arg = 5).in_scope(|| { /* do work */ });
+    ///     });
+    /// });
+    ///
+    /// let trace = storage.lock().to_chrome_trace();
+    /// let trace: serde_json::Value = serde_json::from_str(&trace)?;
+    /// let trace_events = trace["traceEvents"].as_array().unwrap();
+    /// assert_eq!(trace_events.len(), 4);
+    /// assert_eq!(trace_events[0]["ph"], "B");
+    /// assert_eq!(trace_events[0]["name"], "outer");
+    /// # Ok::<_, serde_json::Error>(())
+    /// ```
+    #[cfg_attr(docsrs, doc(cfg(feature = "json")))]
+    pub fn to_chrome_trace(&self) -> String {
+        let mut spans: Vec<_> = self
+            .all_spans()
+            .filter_map(|span| {
+                let timestamps = span.timestamps()?;
+                let end = timestamps.closed.or(timestamps.last_exited)?;
+                Some((span, timestamps.created, end))
+            })
+            .collect();
+        // If spans begin at the same instant, the enclosing span should be opened first.
+        spans.sort_by_key(|&(span, begin, end)| (begin, Reverse(end), span.ancestors().count()));
+
+        let mut tracks: Vec<ChromeTraceTrack<'_>> = vec![];
+        for (span, begin, end) in spans {
+            let is_placed = tracks
+                .iter_mut()
+                .any(|track| track.try_open(span, begin, end));
+            if !is_placed {
+                let mut track = ChromeTraceTrack::new(tracks.len());
+                track.try_open(span, begin, end);
+                tracks.push(track);
+            }
+        }
+        let mut markers: Vec<_> = tracks
+            .into_iter()
+            .flat_map(|mut track| {
+                track.close_spans(None);
+                track.markers
+            })
+            .collect();
+        // The sort is stable, so markers with equal instants retain their order on each track.
+        markers.sort_by_key(|marker| marker.instant);
+
+        let origin = markers.first().map(|marker| marker.instant);
+        let trace_events: Vec<_> = markers
+            .iter()
+            .map(|marker| {
+                let timestamp = marker.instant - origin.unwrap_or(marker.instant);
+                let metadata = marker.span.metadata();
+                let mut trace_event = json!({
+                    "name": metadata.name(),
+                    "cat": metadata.target(),
+                    "ph": if marker.is_begin { "B" } else { "E" },
+                    "ts": timestamp.as_secs_f64() * 1_000_000.0,
+                    "pid": 1,
+                    "tid": marker.track + 1,
+                });
+                if marker.is_begin {
+                    trace_event["args"] = Value::Object(JsonWriter::span_fields(&marker.span));
+                }
+                trace_event
+            })
+            .collect();
+        json!({ "traceEvents": trace_events }).to_string()
+    }
+}
+
+/// Span lifecycle operation exported by [`JsonWriter`].
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum SpanOp {
+    New,
+    Record,
+    Enter,
+    Exit,
+    Close,
+}
+
+impl SpanOp {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::New => "new_span",
+            Self::Record => "record",
+            Self::Enter => "enter",
+            Self::Exit => "exit",
+            Self::Close => "close",
+        }
+    }
+}
+
+/// Writer of JSON lines for [`CaptureLayer::with_json_writer()`](crate::CaptureLayer::with_json_writer()).
+pub(crate) struct JsonWriter {
+    inner: Mutex<Box<dyn Write + Send>>,
+}
+
+impl fmt::Debug for JsonWriter {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter.debug_struct("JsonWriter").finish_non_exhaustive()
+    }
+}
+
+impl JsonWriter {
+    pub(crate) fn new(writer: impl Write + Send + 'static) -> Self {
+        Self {
+            inner: Mutex::new(Box::new(writer)),
+        }
+    }
+
+    /// Writes a line serialized via [`Self::span_op_line()`] or [`Self::event_line()`].
+    /// This may block, so the line should be written after releasing the storage lock.
+    pub(crate) fn write_line(&self, line: &[u8]) {
+        let mut writer = self.inner.lock().unwrap_or_else(PoisonError::into_inner);
+        // There's no way to report an I/O error from a layer, so it's ignored.
+        writer.write_all(line).ok();
+    }
+
+    fn serialize_line(value: &Value) -> Vec<u8> {
+        let mut line = serde_json::to_vec(value).expect("failed serializing JSON value");
+        line.push(b'\n');
+        line
+    }
+
+    fn span_fields(span: &CapturedSpan<'_>) -> Map<String, Value> {
+        span.values()
+            .map(|(name, value)| (name.to_owned(), json!(value)))
+            .collect()
+    }
+
+    pub(crate) fn span_op_line(
+        storage: &Storage,
+        partition: Option<&TracedValue>,
+        op: SpanOp,
+        id: CapturedSpanId,
+    ) -> Vec<u8> {
+        let span = storage.span(id);
+        let mut line = json!({ "op": op.as_str(), "id": id.index() });
+        if let Some(partition) = partition {
+            line["partition"] = json!(partition);
+        }
+        match op {
+            SpanOp::New => {
+                let metadata = span.metadata();
+                line["parent"] = json!(span.parent().map(|parent| parent.inner.id.index()));
+                line["name"] = json!(metadata.name());
+                line["level"] = json!(metadata.level().as_str());
+                line["target"] = json!(metadata.target());
+                line["fields"] = Value::Object(Self::span_fields(&span));
+            }
+            SpanOp::Record => {
+                line["fields"] = Value::Object(Self::span_fields(&span));
+            }
+            SpanOp::Enter | SpanOp::Exit | SpanOp::Close => { /* no additional data */ }
+        }
+        Self::serialize_line(&line)
+    }
+
+    pub(crate) fn event_line(
+        storage: &Storage,
+        partition: Option<&TracedValue>,
+        id: CapturedEventId,
+    ) -> Vec<u8> {
+        let event = storage.event(id);
+        let mut line = event.to_json();
+        line["op"] = json!("event");
+        if let Some(partition) = partition {
+            line["partition"] = json!(partition);
+        }
+        line["parent"] = json!(event.parent().map(|parent| parent.inner.id.index()));
+        Self::serialize_line(&line)
+    }
+}
@@ -1,26 +1,36 @@
 //! `CaptureLayer` and related types.
 
 use id_arena::Arena;
+use predicates::Predicate;
 use tracing_core::{
+    callsite::Identifier,
     span::{Attributes, Id, Record},
-    Event, Metadata, Subscriber,
+    Dispatch, Event, Interest, Level, Metadata, Subscriber,
 };
 use tracing_subscriber::{
+    filter::filter_fn,
     layer::{Context, Filter},
-    registry::LookupSpan,
+    registry::{LookupSpan, SpanRef},
     Layer,
 };
 
 use std::{
-    fmt, ops,
-    sync::{Arc, RwLock},
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    fmt, iter, ops,
+    sync::{Arc, Mutex, RwLock, RwLockReadGuard},
+    thread::{self, ThreadId},
+    time::Instant,
 };
 
+#[cfg(feature = "json")]
+use crate::json::{JsonWriter, SpanOp};
 use crate::{
     CapturedEvent, CapturedEventId, CapturedEventInner, CapturedEvents, CapturedSpan,
-    CapturedSpanId, CapturedSpanInner, CapturedSpans, SpanStats,
+    CapturedSpanId, CapturedSpanInner, CapturedSpans, FlatEvent, SpanStats, SpanTimestamps,
+    TimelineItem,
 };
-use tracing_tunnel::TracedValues;
+use tracing_tunnel::{ConversionOptions, TracedValue, TracedValues};
 
 /// Storage of captured tracing information.
 ///
@@ -32,6 +42,9 @@ pub struct Storage {
     pub(crate) events: Arena<CapturedEventInner>,
     root_span_ids: Vec<CapturedSpanId>,
     root_event_ids: Vec<CapturedEventId>,
+    known_call_sites: Vec<&'static Metadata<'static>>,
+    known_call_site_ids: HashSet<Identifier>,
+    next_seq: u64,
 }
 
 impl Storage {
@@ -41,6 +54,9 @@ impl Storage {
             events: Arena::new(),
             root_span_ids: vec![],
             root_event_ids: vec![],
+            known_call_sites: vec![],
+            known_call_site_ids: HashSet::new(),
+            next_seq: 0,
         }
     }
 
@@ -63,6 +79,13 @@ impl Storage {
         CapturedSpans::from_arena(self)
     }
 
+    /// Iterates over captured spans starting from the most recently captured one.
+    /// This is equivalent to `self.all_spans().rev()`. See [`Self::events_newest_first()`]
+    /// for an example of usage.
+    pub fn spans_newest_first(&self) -> iter::Rev<CapturedSpans<'_>> {
+        self.all_spans().rev()
+    }
+
     /// Iterates over root spans (i.e., spans that do not have a captured parent span)
     /// in the order of capture.
     pub fn root_spans(&self) -> CapturedSpans<'_> {
@@ -74,27 +97,367 @@ impl Storage {
         CapturedEvents::from_arena(self)
     }
 
+    /// Iterates over captured events starting from the most recently captured one.
+    /// This is equivalent to `self.all_events().rev()`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use tracing_subscriber::{layer::SubscriberExt, Registry};
+    /// # use tracing_capture::{CaptureLayer, SharedStorage};
+    /// let storage = SharedStorage::default();
+    /// let subscriber = Registry::default().with(CaptureLayer::new(&storage));
+    /// tracing::subscriber::with_default(subscriber, || {
+    ///     tracing::info_span!("first").in_scope(|| tracing::info!("started"));
+    ///     tracing::info_span!("second").in_scope(|| tracing::info!("finished"));
+    /// });
+    ///
+    /// let storage = storage.lock();
+    /// let last_event = storage.events_newest_first().next().unwrap();
+    /// assert_eq!(last_event.message(), Some("finished"));
+    /// let last_span = storage.spans_newest_first().next().unwrap();
+    /// assert_eq!(last_span.metadata().name(), "second");
+    /// ```
+    pub fn events_newest_first(&self) -> iter::Rev<CapturedEvents<'_>> {
+        self.all_events().rev()
+    }
+
     /// Iterates over root events (i.e., events that do not have a captured parent span)
     /// in the order of capture.
     pub fn root_events(&self) -> CapturedEvents<'_> {
         CapturedEvents::from_slice(self, &self.root_event_ids)
     }
 
+    /// Iterates over all captured events in the order of capture, pairing each event
+    /// with its effective capture context, i.e., the closest captured ancestor span.
+    /// This is the same as calling [`CapturedEvent::parent()`] for each event; the context
+    /// is `None` for [root events](Self::root_events()).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use tracing_subscriber::{layer::SubscriberExt, Registry};
+    /// # use tracing_capture::{CaptureLayer, SharedStorage};
+    /// let storage = SharedStorage::default();
+    /// let subscriber = Registry::default().with(CaptureLayer::new(&storage));
+    /// tracing::subscriber::with_default(subscriber, || {
+    ///     tracing::info!("started");
+    ///     tracing::info_span!("compute").in_scope(|| tracing::info!("done"));
+    /// });
+    ///
+    /// let storage = storage.lock();
+    /// let contexts: Vec<_> = storage
+    ///     .events_with_context()
+    ///     .map(|(_, span)| span.map(|span| span.metadata().name()))
+    ///     .collect();
+    /// assert_eq!(contexts, [None, Some("compute")]);
+    /// ```
+    pub fn events_with_context(
+        &self,
+    ) -> impl Iterator<Item = (CapturedEvent<'_>, Option<CapturedSpan<'_>>)> + '_ {
+        self.all_events().map(|event| {
+            let parent = event.parent();
+            (event, parent)
+        })
+    }
+
+    /// Iterates over all captured events in the order of capture, pairing each event
+    /// with the path of its ancestor spans. This is useful to export captured data
+    /// in a flat format (e.g., CSV or JSON lines).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use tracing_subscriber::{layer::SubscriberExt, Registry};
+    /// # use tracing_capture::{CaptureLayer, SharedStorage};
+    /// let storage = SharedStorage::default();
+    /// let subscriber = Registry::default().with(CaptureLayer::new(&storage));
+    /// tracing::subscriber::with_default(subscriber, || {
+    ///     tracing::info_span!("outer").in_scope(|| {
+    ///         tracing::info_span!("inner").in_scope(|| tracing::info!("done"));
+    ///     });
+    /// });
+    ///
+    /// let storage = storage.lock();
+    /// for flat_event in storage.flattened() {
+    ///     let path = flat_event.span_names().collect::<Vec<_>>().join("/");
+    ///     assert_eq!(path, "outer/inner");
+    ///     assert_eq!(flat_event.event.message(), Some("done"));
+    /// }
+    /// ```
+    pub fn flattened(&self) -> impl Iterator<Item = FlatEvent<'_>> + '_ {
+        self.all_events().map(FlatEvent::new)
+    }
+
+    /// Iterates over all captured spans and events merged into a single stream in the order
+    /// of capture. Spans are positioned at their creation. This can be used to reconstruct
+    /// the full execution trace.
+    ///
+    /// For a storage restored from a snapshot (requires the `serde` crate feature), all spans
+    /// are placed before all events since the relative capture order of spans and events
+    /// is not retained in snapshots.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use tracing_subscriber::{layer::SubscriberExt, Registry};
+    /// # use tracing_capture::{CaptureLayer, SharedStorage, TimelineItem};
+    /// let storage = SharedStorage::default();
+    /// let subscriber = Registry::default().with(CaptureLayer::new(&storage));
+    /// tracing::subscriber::with_default(subscriber, || {
+    ///     tracing::info!("started");
+    ///     tracing::info_span!("compute").in_scope(|| tracing::info!("done"));
+    /// });
+    ///
+    /// let storage = storage.lock();
+    /// let timeline: Vec<_> = storage
+    ///     .timeline()
+    ///     .map(|item| match item {
+    ///         TimelineItem::Span(span) => span.metadata().name(),
+    ///         TimelineItem::Event(event) => event.message().unwrap(),
+    ///     })
+    ///     .collect();
+    /// assert_eq!(timeline, ["started", "compute", "done"]);
+    /// ```
+    pub fn timeline(&self) -> impl Iterator<Item = TimelineItem<'_>> + '_ {
+        let mut spans = self.all_spans().peekable();
+        let mut events = self.all_events().peekable();
+        iter::from_fn(move || {
+            let is_span_next = match (spans.peek(), events.peek()) {
+                (Some(span), Some(event)) => span.inner.seq < event.inner.seq,
+                (Some(_), None) => true,
+                (None, _) => false,
+            };
+            if is_span_next {
+                spans.next().map(TimelineItem::Span)
+            } else {
+                events.next().map(TimelineItem::Event)
+            }
+        })
+    }
+
+    fn next_seq(&mut self) -> u64 {
+        let seq = self.next_seq;
+        self.next_seq += 1;
+        seq
+    }
+
+    /// Iterates over captured spans that are not [balanced](CapturedSpan::is_balanced()),
+    /// i.e., were entered a different number of times than exited. This can be used
+    /// to debug leaked spans or partial executions.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use tracing_subscriber::{layer::SubscriberExt, Registry};
+    /// # use tracing_capture::{CaptureLayer, SharedStorage};
+    /// let storage = SharedStorage::default();
+    /// let subscriber = Registry::default().with(CaptureLayer::new(&storage));
+    /// tracing::subscriber::with_default(subscriber, || {
+    ///     tracing::info_span!("balanced").in_scope(|| tracing::info!("done"));
+    ///     let leaked = tracing::info_span!("leaked");
+    ///     std::mem::forget(leaked.enter());
+    /// });
+    ///
+    /// let storage = storage.lock();
+    /// let unbalanced: Vec<_> = storage.unbalanced_spans().collect();
+    /// assert_eq!(unbalanced.len(), 1);
+    /// assert_eq!(unbalanced[0].metadata().name(), "leaked");
+    /// ```
+    pub fn unbalanced_spans(&self) -> impl Iterator<Item = CapturedSpan<'_>> + '_ {
+        self.all_spans().filter(|span| !span.is_balanced())
+    }
+
+    /// Checks that no captured events have the specified `level` or a more severe one.
+    /// E.g., for `Level::WARN`, this checks that there are no warnings or errors.
+    ///
+    /// # Panics
+    ///
+    /// Panics with the list of offending events if the check fails.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use tracing_core::Level;
+    /// # use tracing_subscriber::{layer::SubscriberExt, Registry};
+    /// # use tracing_capture::{CaptureLayer, SharedStorage};
+    /// let storage = SharedStorage::default();
+    /// let subscriber = Registry::default().with(CaptureLayer::new(&storage));
+    /// tracing::subscriber::with_default(subscriber, || {
+    ///     tracing::info!("all good");
+    /// });
+    /// storage.lock().assert_no_events_at_or_above(Level::WARN);
+    /// ```
+    #[track_caller]
+    pub fn assert_no_events_at_or_above(&self, level: Level) {
+        // More severe levels compare as lesser ones.
+        let offending_events: Vec<_> = self
+            .all_events()
+            .filter(|event| *event.metadata().level() <= level)
+            .collect();
+        assert!(
+            offending_events.is_empty(),
+            "found {} event(s) at or above level {level}: {offending_events:#?}",
+            offending_events.len()
+        );
+    }
+
+    /// Returns all captured spans sorted by the specified key. The sort is stable, i.e.,
+    /// spans with equal keys retain the capture order.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use tracing_subscriber::{layer::SubscriberExt, Registry};
+    /// # use tracing_capture::{CaptureLayer, SharedStorage};
+    /// let storage = SharedStorage::default();
+    /// let subscriber = Registry::default().with(CaptureLayer::new(&storage));
+    /// tracing::subscriber::with_default(subscriber, || {
+    ///     tracing::info_span!("compute").in_scope(|| {
+    ///         tracing::info_span!("aggregate").in_scope(|| { /* ... */ });
+    ///     });
+    /// });
+    ///
+    /// let storage = storage.lock();
+    /// let spans = storage.spans_sorted_by(|span| span.metadata().name());
+    /// let names: Vec<_> = spans.iter().map(|span| span.metadata().name()).collect();
+    /// assert_eq!(names, ["aggregate", "compute"]);
+    /// ```
+    pub fn spans_sorted_by<K: Ord>(
+        &self,
+        mut key: impl FnMut(&CapturedSpan<'_>) -> K,
+    ) -> Vec<CapturedSpan<'_>> {
+        let mut spans: Vec<_> = self.all_spans().collect();
+        spans.sort_by_key(|span| key(span));
+        spans
+    }
+
+    /// Returns all captured spans matching the specified predicate, in the order of capture.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use predicates::str::starts_with;
+    /// # use tracing_subscriber::{layer::SubscriberExt, Registry};
+    /// # use tracing_capture::{predicates::name, CaptureLayer, SharedStorage};
+    /// let storage = SharedStorage::default();
+    /// let subscriber = Registry::default().with(CaptureLayer::new(&storage));
+    /// tracing::subscriber::with_default(subscriber, || {
+    ///     for i in 0..3 {
+    ///         tracing::info_span!("compute", i).in_scope(|| { /* ... */ });
+    ///     }
+    ///     tracing::info_span!("aggregate").in_scope(|| { /* ... */ });
+    /// });
+    ///
+    /// let storage = storage.lock();
+    /// let spans = storage.spans_matching(&name(starts_with("comp")));
+    /// assert_eq!(spans.len(), 3);
+    /// ```
+    pub fn spans_matching<'s>(
+        &'s self,
+        predicate: &impl Predicate<CapturedSpan<'s>>,
+    ) -> Vec<CapturedSpan<'s>> {
+        self.all_spans()
+            .filter(|span| predicate.eval(span))
+            .collect()
+    }
+
+    /// Returns all captured events matching the specified predicate, in the order of capture.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use tracing_core::Level;
+    /// # use tracing_subscriber::{layer::SubscriberExt, Registry};
+    /// # use tracing_capture::{predicates::level, CaptureLayer, SharedStorage};
+    /// let storage = SharedStorage::default();
+    /// let subscriber = Registry::default().with(CaptureLayer::new(&storage));
+    /// tracing::subscriber::with_default(subscriber, || {
+    ///     tracing::info!("started");
+    ///     tracing::warn!(attempt = 1, "retrying");
+    ///     tracing::warn!(attempt = 2, "retrying");
+    /// });
+    ///
+    /// let storage = storage.lock();
+    /// let warnings = storage.events_matching(&level(Level::WARN));
+    /// assert_eq!(warnings.len(), 2);
+    /// assert_eq!(warnings[1]["attempt"], 2_i64);
+    /// ```
+    pub fn events_matching<'s>(
+        &'s self,
+        predicate: &impl Predicate<CapturedEvent<'s>>,
+    ) -> Vec<CapturedEvent<'s>> {
+        self.all_events()
+            .filter(|event| predicate.eval(event))
+            .collect()
+    }
+
+    /// Returns call sites registered while the storage was used by a [`CaptureLayer`]
+    /// with [call site tracking](CaptureLayer::with_known_call_sites()) enabled, in the order
+    /// of registration. Unlike captured spans and events, call sites are tracked regardless
+    /// of [filtering](CaptureLayer::with_filter()), so this allows finding instrumented code paths
+    /// that were inactive (e.g., filtered out by level).
+    ///
+    /// Call sites are registered globally in `tracing`; thus, the returned list may contain
+    /// call sites not related to the traced code (e.g., ones registered before the subscriber
+    /// with the layer was installed).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use tracing_core::LevelFilter;
+    /// # use tracing_subscriber::{layer::SubscriberExt, Registry};
+    /// # use tracing_capture::{CaptureLayer, SharedStorage};
+    /// let storage = SharedStorage::default();
+    /// let layer = CaptureLayer::new(&storage)
+    ///     .with_filter(LevelFilter::INFO)
+    ///     .with_known_call_sites();
+    /// let subscriber = Registry::default().with(layer);
+    /// tracing::subscriber::with_default(subscriber, || {
+    ///     tracing::debug!(target: "filtered", "not captured");
+    /// });
+    ///
+    /// let storage = storage.lock();
+    /// assert_eq!(storage.all_events().len(), 0);
+    /// assert!(storage
+    ///     .known_call_sites()
+    ///     .iter()
+    ///     .any(|metadata| metadata.target() == "filtered"));
+    /// ```
+    pub fn known_call_sites(&self) -> &[&'static Metadata<'static>] {
+        &self.known_call_sites
+    }
+
+    pub(crate) fn push_call_site(&mut self, metadata: &'static Metadata<'static>) {
+        if self.known_call_site_ids.insert(metadata.callsite()) {
+            self.known_call_sites.push(metadata);
+        }
+    }
+
     pub(crate) fn push_span(
         &mut self,
         metadata: &'static Metadata<'static>,
         values: TracedValues<&'static str>,
         parent_id: Option<CapturedSpanId>,
     ) -> CapturedSpanId {
+        let seq = self.next_seq();
         let span_id = self.spans.alloc_with_id(|id| CapturedSpanInner {
             metadata,
             values,
             stats: SpanStats::default(),
+            timestamps: None,
             id,
+            seq,
             parent_id,
+            skipped_ancestors: 0,
+            raw_id: None,
+            raw_parent_id: None,
+            #[cfg(feature = "tunnel-source-ids")]
+            source_span_id: None,
             child_ids: vec![],
             event_ids: vec![],
             follows_from_ids: vec![],
+            busy_durations: vec![],
         });
         if let Some(parent_id) = parent_id {
             let span = self.spans.get_mut(parent_id).unwrap();
@@ -105,24 +468,42 @@ impl Storage {
         span_id
     }
 
-    fn on_span_enter(&mut self, id: CapturedSpanId) {
+    fn on_span_enter(&mut self, id: CapturedSpanId, now: Option<Instant>) {
         let span = self.spans.get_mut(id).unwrap();
         span.stats.entered += 1;
+        if let (Some(timestamps), Some(now)) = (&mut span.timestamps, now) {
+            timestamps.first_entered.get_or_insert(now);
+            timestamps.last_entered = Some(now);
+        }
     }
 
-    fn on_span_exit(&mut self, id: CapturedSpanId) {
+    fn on_span_exit(&mut self, id: CapturedSpanId, now: Option<Instant>) {
         let span = self.spans.get_mut(id).unwrap();
         span.stats.exited += 1;
+        if let (Some(timestamps), Some(now)) = (&mut span.timestamps, now) {
+            timestamps.last_exited = Some(now);
+            if let Some(last_entered) = timestamps.last_entered {
+                span.busy_durations
+                    .push(now.saturating_duration_since(last_entered));
+            }
+        }
     }
 
-    fn on_span_closed(&mut self, id: CapturedSpanId) {
+    fn on_span_closed(&mut self, id: CapturedSpanId, now: Option<Instant>) {
         let span = self.spans.get_mut(id).unwrap();
         span.stats.is_closed = true;
+        if let (Some(timestamps), Some(now)) = (&mut span.timestamps, now) {
+            timestamps.closed = Some(now);
+        }
     }
 
     fn on_record(&mut self, id: CapturedSpanId, values: TracedValues<&'static str>) {
         let span = self.spans.get_mut(id).unwrap();
-        span.values.extend(values);
+        // Values can be recorded for a closed span, e.g., by a layer recording them in
+        // its `on_close()` hook. We consider span values final once the span is closed.
+        if !span.stats.is_closed {
+            span.values.extend(values);
+        }
     }
 
     fn on_follows_from(&mut self, id: CapturedSpanId, follows_id: CapturedSpanId) {
@@ -136,11 +517,14 @@ impl Storage {
         values: TracedValues<&'static str>,
         parent_id: Option<CapturedSpanId>,
     ) -> CapturedEventId {
+        let seq = self.next_seq();
         let event_id = self.events.alloc_with_id(|id| CapturedEventInner {
             metadata,
             values,
             id,
+            seq,
             parent_id,
+            skipped_ancestors: 0,
         });
         if let Some(parent_id) = parent_id {
             let span = self.spans.get_mut(parent_id).unwrap();
@@ -152,6 +536,39 @@ impl Storage {
     }
 }
 
+/// Iterates over all captured events in the order of capture; equivalent
+/// to [`Storage::all_events()`].
+///
+/// # Examples
+///
+/// ```
+/// # use tracing_subscriber::{layer::SubscriberExt, Registry};
+/// # use tracing_capture::{CaptureLayer, SharedStorage};
+/// let storage = SharedStorage::default();
+/// let subscriber = Registry::default().with(CaptureLayer::new(&storage));
+/// tracing::subscriber::with_default(subscriber, || {
+///     tracing::info!(i = 0, "started");
+///     tracing::info!(i = 1, "finished");
+/// });
+///
+/// let storage = storage.lock();
+/// let mut count = 0_i64;
+/// for event in &*storage {
+///     assert_eq!(event["i"], count);
+///     count += 1;
+/// }
+/// assert_eq!(count, 2);
+/// ```
+#[allow(clippy::into_iter_without_iter)] // `Storage::all_events()` plays the role of `iter()`
+impl<'a> IntoIterator for &'a Storage {
+    type Item = CapturedEvent<'a>;
+    type IntoIter = CapturedEvents<'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.all_events()
+    }
+}
+
 /// Shared wrapper for tracing [`Storage`].
 #[derive(Debug, Clone)]
 pub struct SharedStorage {
@@ -170,10 +587,127 @@ impl Default for SharedStorage {
 impl SharedStorage {
     /// Locks the underlying [`Storage`] for exclusive access. While the lock is held,
     /// capturing cannot progress; beware of deadlocks!
+    ///
+    /// Spans and events emitted on the thread holding the lock (e.g., by a [`Predicate`]
+    /// logging during evaluation) are not captured into this storage; otherwise, capturing them
+    /// would deadlock. See [`CaptureLayer`] docs for details.
     pub fn lock(&self) -> impl ops::Deref<Target = Storage> + '_ {
-        self.inner
+        let guard = self
+            .inner
             .read()
-            .expect("failed accessing shared tracing data storage")
+            .expect("failed accessing shared tracing data storage");
+        StorageReadGuard::new(&self.inner, guard)
+    }
+
+    /// Returns the number of captured spans. Unlike with [`Self::lock()`], the lock
+    /// on the storage is only held for the duration of the call, so this method is safe to use
+    /// e.g. for progress reporting while capturing is in progress.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use tracing_subscriber::{layer::SubscriberExt, Registry};
+    /// # use tracing_capture::{CaptureLayer, SharedStorage};
+    /// let storage = SharedStorage::default();
+    /// let subscriber = Registry::default().with(CaptureLayer::new(&storage));
+    /// tracing::subscriber::with_default(subscriber, || {
+    ///     for i in 0..3 {
+    ///         let _entered = tracing::info_span!("iteration", i).entered();
+    ///         assert_eq!(storage.span_count(), i + 1);
+    ///     }
+    /// });
+    /// ```
+    pub fn span_count(&self) -> usize {
+        self.lock().spans.len()
+    }
+
+    /// Returns the number of captured events. Like [`Self::span_count()`], this only holds
+    /// the lock on the storage for the duration of the call.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use tracing_subscriber::{layer::SubscriberExt, Registry};
+    /// # use tracing_capture::{CaptureLayer, SharedStorage};
+    /// let storage = SharedStorage::default();
+    /// let subscriber = Registry::default().with(CaptureLayer::new(&storage));
+    /// tracing::subscriber::with_default(subscriber, || {
+    ///     tracing::info!("hello");
+    ///     assert_eq!(storage.event_count(), 1);
+    ///     tracing::warn!("world");
+    ///     assert_eq!(storage.event_count(), 2);
+    /// });
+    /// ```
+    pub fn event_count(&self) -> usize {
+        self.lock().events.len()
+    }
+}
+
+thread_local! {
+    /// Addresses of storages read-locked by the current thread via [`SharedStorage::lock()`].
+    static READ_LOCKED_STORAGES: RefCell<Vec<usize>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Read guard for a [`Storage`] that tracks read locks held by the current thread, so that
+/// [`CaptureLayer`] doesn't deadlock trying to write to the storage on the same thread.
+struct StorageReadGuard<'a> {
+    guard: RwLockReadGuard<'a, Storage>,
+    address: usize,
+}
+
+impl<'a> StorageReadGuard<'a> {
+    fn new(storage: &Arc<RwLock<Storage>>, guard: RwLockReadGuard<'a, Storage>) -> Self {
+        let address = Arc::as_ptr(storage) as usize;
+        READ_LOCKED_STORAGES.with(|storages| storages.borrow_mut().push(address));
+        Self { guard, address }
+    }
+
+    fn is_locked_by_current_thread(storage: &Arc<RwLock<Storage>>) -> bool {
+        let address = Arc::as_ptr(storage) as usize;
+        READ_LOCKED_STORAGES.with(|storages| storages.borrow().contains(&address))
+    }
+}
+
+impl Drop for StorageReadGuard<'_> {
+    fn drop(&mut self) {
+        READ_LOCKED_STORAGES.with(|storages| {
+            let mut storages = storages.borrow_mut();
+            if let Some(pos) = storages.iter().rposition(|&addr| addr == self.address) {
+                storages.swap_remove(pos);
+            }
+        });
+    }
+}
+
+impl ops::Deref for StorageReadGuard<'_> {
+    type Target = Storage;
+
+    fn deref(&self) -> &Storage {
+        &self.guard
+    }
+}
+
+/// Source of [`Instant`]s used by [`CaptureLayer`] to capture [timestamps](SpanTimestamps)
+/// of span operations. By default, the layer uses [`RealClock`]; a custom clock can be set
+/// via [`CaptureLayer::with_clock()`], e.g., to make timing assertions in tests deterministic.
+pub trait CaptureClock: Send + Sync {
+    /// Returns the current instant.
+    fn now(&self) -> Instant;
+}
+
+impl<C: CaptureClock + ?Sized> CaptureClock for Arc<C> {
+    fn now(&self) -> Instant {
+        (**self).now()
+    }
+}
+
+/// Real [`CaptureClock`] based on [`Instant::now()`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RealClock;
+
+impl CaptureClock for RealClock {
+    fn now(&self) -> Instant {
+        Instant::now()
     }
 }
 
@@ -188,19 +722,120 @@ impl SharedStorage {
 /// in the span hierarchy. If no entered spans are captured when the event is emitted,
 /// the event will be captured in [`Storage::root_events()`].
 ///
+/// Note that the parent of an event is determined by the span context at the time
+/// the event is emitted. In particular, events emitted after the span is exited and closed
+/// (e.g., from `Drop` implementations of values outliving the span guard) are not tied
+/// to the span by default; see [`Self::with_close_time_events()`] for a way to change this.
+///
+/// # Composing with other layers
+///
+/// Captured data does not depend on the position of `CaptureLayer` relative to other layers
+/// in the subscriber (e.g., a `fmt` layer). The layer only relies on the span data managed
+/// by the subscriber (span scopes and parents) and on its own span extensions, and captures
+/// raw field values rather than output of other layers. Hence, `CaptureLayer` can be placed
+/// before or after formatting layers, or can wrap them via [`Self::teeing()`]. Note that filtering
+/// performed by other layers (as opposed to [per-layer filtering]) still applies to the entire
+/// subscriber and thus affects capturing.
+///
+/// [per-layer filtering]: tracing_subscriber::layer#per-layer-filtering
+///
+/// # Reentrancy
+///
+/// Spans and events may be emitted while the storage is [locked](SharedStorage::lock()),
+/// e.g., by a custom [`Predicate`] logging during evaluation or by `Debug` implementations
+/// of captured values. Capturing such data on the thread holding the lock would deadlock,
+/// so the layer skips it instead: spans and events emitted on this thread are not captured
+/// into the locked storage, and operations on previously captured spans (entering, exiting,
+/// recording values etc.) are not reflected in it. Capturing resumes once the lock is released.
+/// Spans and events emitted on other threads are unaffected; they will wait for the lock
+/// to be released as usual.
+///
 /// # Examples
 ///
 /// See [crate-level docs](index.html) for an example of usage.
 pub struct CaptureLayer<S> {
     filter: Option<Box<dyn Filter<S> + Send + Sync>>,
+    max_level: Option<Level>,
+    span_names: Option<HashSet<String>>,
+    late_capture: Option<Box<LateCaptureFn>>,
+    value_transform: Option<Box<ValueTransformFn>>,
+    conversion_options: ConversionOptions<'static>,
+    track_call_sites: bool,
+    timing: bool,
+    clock: Box<dyn CaptureClock>,
+    closed_spans: Option<Mutex<ClosedSpans>>,
+    #[cfg(feature = "json")]
+    json_writer: Option<JsonWriter>,
+    inner: Option<Box<dyn Layer<S> + Send + Sync>>,
+    partitioning: Option<(&'static str, Box<PartitionRouterFn>)>,
     storage: Arc<RwLock<Storage>>,
 }
 
+type LateCaptureFn =
+    dyn Fn(&'static Metadata<'static>, &TracedValues<&'static str>) -> bool + Send + Sync;
+type ValueTransformFn = dyn Fn(&str, &mut TracedValue) + Send + Sync;
+type PartitionRouterFn = dyn Fn(&TracedValue) -> Option<SharedStorage> + Send + Sync;
+/// Most recently closed span for each thread.
+type ClosedSpans = HashMap<ThreadId, (CapturedSpanId, Option<SpanPartition>)>;
+
+/// Storage of a span captured by a [partitioned](CaptureLayer::partitioned_by()) layer,
+/// stored in the span extensions if it differs from the default layer storage.
+#[derive(Debug, Clone)]
+struct SpanPartition {
+    storage: Arc<RwLock<Storage>>,
+    /// Value of the partitioning field that has routed the span or its ancestor.
+    /// Used to distinguish spans from different storages in exported JSON lines.
+    #[cfg_attr(not(feature = "json"), allow(dead_code))]
+    key: TracedValue,
+}
+
+impl SpanPartition {
+    /// Checks whether two partitions refer to the same storage; `None` stands for
+    /// the default layer storage.
+    fn is_same(this: Option<&Self>, other: Option<&Self>) -> bool {
+        match (this, other) {
+            (None, None) => true,
+            (Some(this), Some(other)) => Arc::ptr_eq(&this.storage, &other.storage),
+            _ => false,
+        }
+    }
+}
+
+/// Values of a span skipped by the [`CaptureLayer`] filter, stored in the span extensions
+/// so that the span can be captured later.
+#[derive(Debug)]
+struct SkippedSpanValues(TracedValues<&'static str>);
+
+/// Marker stored in the span extensions for spans disabled by the [teeing](CaptureLayer::teeing())
+/// inner layer, so that hooks for such spans are not forwarded to it.
+#[derive(Debug)]
+struct DisabledForInner;
+
 impl<S> fmt::Debug for CaptureLayer<S> {
     fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
-        formatter
-            .debug_struct("CaptureLayer")
+        let mut debug_struct = formatter.debug_struct("CaptureLayer");
+        debug_struct
             .field("filter", &self.filter.as_ref().map(|_| "Filter"))
+            .field("max_level", &self.max_level)
+            .field("span_names", &self.span_names)
+            .field("late_capture", &self.late_capture.as_ref().map(|_| "Fn"))
+            .field(
+                "value_transform",
+                &self.value_transform.as_ref().map(|_| "Fn"),
+            )
+            .field("conversion_options", &self.conversion_options)
+            .field("track_call_sites", &self.track_call_sites)
+            .field("timing", &self.timing)
+            .field("clock", &"CaptureClock")
+            .field("close_time_events", &self.closed_spans.is_some());
+        #[cfg(feature = "json")]
+        debug_struct.field("json_writer", &self.json_writer);
+        debug_struct
+            .field("inner", &self.inner.as_ref().map(|_| "Layer"))
+            .field(
+                "partitioned_by",
+                &self.partitioning.as_ref().map(|(field, _)| field),
+            )
             .field("storage", &self.storage)
             .finish()
     }
@@ -216,6 +851,19 @@ where
     pub fn new(storage: &SharedStorage) -> Self {
         Self {
             filter: None,
+            max_level: None,
+            span_names: None,
+            late_capture: None,
+            value_transform: None,
+            conversion_options: ConversionOptions::new(),
+            track_call_sites: false,
+            timing: false,
+            clock: Box::new(RealClock),
+            closed_spans: None,
+            #[cfg(feature = "json")]
+            json_writer: None,
+            inner: None,
+            partitioning: None,
             storage: Arc::clone(&storage.inner),
         }
     }
@@ -233,87 +881,950 @@ where
         self
     }
 
-    fn enabled(&self, metadata: &Metadata<'_>, ctx: &Context<'_, S>) -> bool {
+    /// Specifies filtering for this layer based on span / event [`Metadata`]. This is a shortcut
+    /// for calling [`Self::with_filter()`] with a [`filter_fn()`](tracing_subscriber::filter::filter_fn())
+    /// wrapping the provided closure; correspondingly, it replaces the previously set filter.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use tracing_subscriber::{layer::SubscriberExt, Registry};
+    /// # use tracing_capture::{CaptureLayer, SharedStorage};
+    /// let storage = SharedStorage::default();
+    /// let layer = CaptureLayer::new(&storage)
+    ///     .with_metadata_filter(|metadata| metadata.target().starts_with("app"));
+    /// let subscriber = Registry::default().with(layer);
+    /// tracing::subscriber::with_default(subscriber, || {
+    ///     tracing::info!(target: "app::db", "captured");
+    ///     tracing::info!(target: "hyper", "filtered out");
+    /// });
+    ///
+    /// let storage = storage.lock();
+    /// assert_eq!(storage.all_events().len(), 1);
+    /// ```
+    #[must_use]
+    pub fn with_metadata_filter<F>(self, filter: F) -> Self
+    where
+        F: Fn(&Metadata<'_>) -> bool + Send + Sync + 'static,
+    {
+        self.with_filter(filter_fn(filter))
+    }
+
+    /// Specifies the maximum verbosity level of captured spans and events. This is a shortcut
+    /// for the [filter](Self::with_filter()) based on `LevelFilter`; unlike calling
+    /// `with_filter()`, it composes with the existing filter (i.e., a span / event
+    /// is captured only if it passes both the filter and the level check).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use tracing_core::Level;
+    /// # use tracing_subscriber::{filter::filter_fn, layer::SubscriberExt, Registry};
+    /// # use tracing_capture::{CaptureLayer, SharedStorage};
+    /// let storage = SharedStorage::default();
+    /// let layer = CaptureLayer::new(&storage)
+    ///     .with_filter(filter_fn(|metadata| metadata.target() != "noisy"))
+    ///     .with_max_level(Level::INFO);
+    /// let subscriber = Registry::default().with(layer);
+    /// tracing::subscriber::with_default(subscriber, || {
+    ///     tracing::info!("captured");
+    ///     tracing::debug!("too verbose");
+    ///     tracing::warn!(target: "noisy", "filtered out");
+    /// });
+    ///
+    /// let storage = storage.lock();
+    /// assert_eq!(storage.all_events().len(), 1);
+    /// let event = storage.all_events().next().unwrap();
+    /// assert_eq!(event.message(), Some("captured"));
+    /// ```
+    #[must_use]
+    pub fn with_max_level(mut self, level: Level) -> Self {
+        // More verbose levels compare as greater ones.
+        let max_level = self
+            .max_level
+            .map_or(level, |prev_level| prev_level.min(level));
+        self.max_level = Some(max_level);
+        self
+    }
+
+    /// Restricts captured spans to ones with the specified names. Events are captured only
+    /// if they have a captured ancestor span, i.e., are emitted within one of the allowlisted
+    /// spans. If called multiple times, the allowlist contains names from all calls.
+    ///
+    /// Like [`Self::with_max_level()`], the allowlist composes with the [filter](Self::with_filter()).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use tracing_subscriber::{layer::SubscriberExt, Registry};
+    /// # use tracing_capture::{CaptureLayer, SharedStorage};
+    /// let storage = SharedStorage::default();
+    /// let layer = CaptureLayer::new(&storage).with_span_names(&["compute"]);
+    /// let subscriber = Registry::default().with(layer);
+    /// tracing::subscriber::with_default(subscriber, || {
+    ///     tracing::info_span!("compute").in_scope(|| {
+    ///         tracing::info_span!("aggregate").in_scope(|| tracing::info!("captured"));
+    ///     });
+    ///     tracing::info!("not captured");
+    /// });
+    ///
+    /// let storage = storage.lock();
+    /// assert_eq!(storage.all_spans().len(), 1);
+    /// assert_eq!(storage.all_events().len(), 1);
+    /// let event = storage.all_events().next().unwrap();
+    /// assert_eq!(event.parent().unwrap().metadata().name(), "compute");
+    /// ```
+    #[must_use]
+    pub fn with_span_names(mut self, names: &[&str]) -> Self {
+        let span_names = self.span_names.get_or_insert_with(HashSet::new);
+        span_names.extend(names.iter().map(|&name| name.to_owned()));
+        self
+    }
+
+    /// Allows capturing spans skipped by the [filter](Self::with_filter()) when they are created
+    /// if the span values [recorded](tracing_core::Subscriber::record()) later make the span
+    /// interesting. The `predicate` is evaluated on each record for a skipped span, with all
+    /// span values recorded so far (including ones that the span was created with).
+    /// Once the predicate returns `true`, the span is captured, and all subsequent operations
+    /// with it (entering, recording values, emitting events etc.) are captured as well.
+    ///
+    /// # Limitations
+    ///
+    /// A late-captured span is linked to the nearest captured ancestor *at the time of capture*.
+    /// Spans and events captured before the span itself (e.g., its children) are not
+    /// re-linked to it; they remain tied to the previously captured ancestor (or are root).
+    /// Likewise, [`SpanStats`] for a late-captured span only reflect operations after the capture;
+    /// e.g., if the span was entered at the time, the entry is not counted.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use tracing_core::{field, LevelFilter};
+    /// # use tracing_subscriber::{layer::SubscriberExt, Registry};
+    /// # use tracing_capture::{CaptureLayer, SharedStorage};
+    /// let storage = SharedStorage::default();
+    /// let layer = CaptureLayer::new(&storage)
+    ///     .with_filter(LevelFilter::INFO)
+    ///     .with_late_capture(|_, values| {
+    ///         values.get("slow").and_then(|val| val.as_bool()) == Some(true)
+    ///     });
+    /// let subscriber = Registry::default().with(layer);
+    /// tracing::subscriber::with_default(subscriber, || {
+    ///     let span = tracing::debug_span!("request", slow = field::Empty);
+    ///     span.record("slow", true);
+    ///     span.in_scope(|| tracing::info!("processed request"));
+    /// });
+    ///
+    /// let storage = storage.lock();
+    /// let span = storage.all_spans().next().unwrap();
+    /// assert_eq!(span.metadata().name(), "request");
+    /// assert_eq!(span.events().len(), 1);
+    /// ```
+    #[must_use]
+    pub fn with_late_capture<F>(mut self, predicate: F) -> Self
+    where
+        F: Fn(&'static Metadata<'static>, &TracedValues<&'static str>) -> bool
+            + Send
+            + Sync
+            + 'static,
+    {
+        self.late_capture = Some(Box::new(predicate));
+        self
+    }
+
+    /// Specifies a transform applied to each span / event value before it is stored.
+    /// The transform receives the field name and a mutable reference to the value; it can be
+    /// used, e.g., to redact secrets or normalize nondeterministic values.
+    ///
+    /// The transform is applied to values of captured spans (both ones provided on span creation
+    /// and [recorded](tracing_core::Subscriber::record()) later) and events. For
+    /// [late-captured](Self::with_late_capture()) spans, the late capture predicate
+    /// receives untransformed values.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use tracing_subscriber::{layer::SubscriberExt, Registry};
+    /// # use tracing_capture::{CaptureLayer, SharedStorage};
+    /// # use tracing_tunnel::TracedValue;
+    /// let storage = SharedStorage::default();
+    /// let layer = CaptureLayer::new(&storage).with_value_transform(|name, value| {
+    ///     if name == "password" {
+    ///         *value = TracedValue::from("***");
+    ///     }
+    /// });
+    /// let subscriber = Registry::default().with(layer);
+    /// tracing::subscriber::with_default(subscriber, || {
+    ///     tracing::info!(user = "alice", password = "hunter2", "logged in");
+    /// });
+    ///
+    /// let storage = storage.lock();
+    /// let event = storage.all_events().next().unwrap();
+    /// assert_eq!(event["user"], "alice");
+    /// assert_eq!(event["password"], "***");
+    /// ```
+    #[must_use]
+    pub fn with_value_transform<F>(mut self, transform: F) -> Self
+    where
+        F: Fn(&str, &mut TracedValue) + Send + Sync + 'static,
+    {
+        self.value_transform = Some(Box::new(transform));
+        self
+    }
+
+    /// Limits the length of [`Debug`](fmt::Debug) output (in bytes) for captured values.
+    /// See [`ConversionOptions::with_debug_limit()`] for details.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use tracing_subscriber::{layer::SubscriberExt, Registry};
+    /// # use tracing_capture::{CaptureLayer, SharedStorage};
+    /// # use tracing_tunnel::TracedValue;
+    /// let storage = SharedStorage::default();
+    /// let layer = CaptureLayer::new(&storage).with_debug_limit(16);
+    /// let subscriber = Registry::default().with(layer);
+    /// tracing::subscriber::with_default(subscriber, || {
+    ///     tracing::info!(data = ?vec![0_u8; 100], "huge");
+    /// });
+    ///
+    /// let storage = storage.lock();
+    /// let event = storage.all_events().next().unwrap();
+    /// let TracedValue::Object(data) = &event["data"] else { unreachable!() };
+    /// assert!(data.is_truncated());
+    /// ```
+    #[must_use]
+    pub fn with_debug_limit(mut self, limit: usize) -> Self {
+        self.conversion_options = self.conversion_options.with_debug_limit(limit);
+        self
+    }
+
+    /// Enables tracking call sites registered by `tracing`, regardless of whether spans / events
+    /// from these call sites are captured. Tracked call sites can be accessed
+    /// via [`Storage::known_call_sites()`].
+    #[must_use]
+    pub fn with_known_call_sites(mut self) -> Self {
+        self.track_call_sites = true;
+        self
+    }
+
+    /// Enables capturing [timestamps](crate::SpanTimestamps) of span operations (creation,
+    /// entering, exiting and closing). Timestamps can be accessed
+    /// via [`CapturedSpan::timestamps()`].
+    #[must_use]
+    pub fn with_timing(mut self) -> Self {
+        self.timing = true;
+        self
+    }
+
+    /// Sets the clock used to capture timestamps. By default, the [`RealClock`] is used.
+    /// The clock is only used if [timing](Self::with_timing()) is enabled.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use std::{sync::{Arc, Mutex}, time::{Duration, Instant}};
+    /// # use tracing_subscriber::{layer::SubscriberExt, Registry};
+    /// # use tracing_capture::{CaptureClock, CaptureLayer, SharedStorage};
+    /// #[derive(Debug)]
+    /// struct MockClock(Mutex<Instant>);
+    ///
+    /// impl CaptureClock for MockClock {
+    ///     fn now(&self) -> Instant {
+    ///         *self.0.lock().unwrap()
+    ///     }
+    /// }
+    ///
+    /// let clock = Arc::new(MockClock(Mutex::new(Instant::now())));
+    /// let storage = SharedStorage::default();
+    /// let layer = CaptureLayer::new(&storage)
+    ///     .with_timing()
+    ///     .with_clock(Arc::clone(&clock));
+    /// let subscriber = Registry::default().with(layer);
+    /// tracing::subscriber::with_default(subscriber, || {
+    ///     tracing::info_span!("compute").in_scope(|| {
+    ///         *clock.0.lock().unwrap() += Duration::from_millis(5);
+    ///     });
+    /// });
+    ///
+    /// let storage = storage.lock();
+    /// let span = storage.all_spans().next().unwrap();
+    /// assert_eq!(span.busy_durations(), [Duration::from_millis(5)]);
+    /// ```
+    #[must_use]
+    pub fn with_clock(mut self, clock: impl CaptureClock + 'static) -> Self {
+        self.clock = Box::new(clock);
+        self
+    }
+
+    /// Enables attaching close-time events to the closing span. A close-time event is the first
+    /// event emitted on the same thread after a captured span is closed, provided that
+    /// it is emitted outside any spans and no span is entered in between. Such events
+    /// are usually emitted from `Drop` implementations of values that outlive the span guard.
+    /// With this option, these events are tied to the closed span instead of being captured
+    /// as [root events](Storage::root_events()).
+    ///
+    /// Only a single event is tied to the closed span; all subsequent events are captured
+    /// as usual. Events emitted within another span (even a non-captured one) are not affected,
+    /// but still reset the closed span.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use tracing_subscriber::{layer::SubscriberExt, Registry};
+    /// # use tracing_capture::{CaptureLayer, SharedStorage};
+    /// struct Connection;
+    ///
+    /// impl Drop for Connection {
+    ///     fn drop(&mut self) {
+    ///         tracing::info!("connection closed");
+    ///     }
+    /// }
+    ///
+    /// let storage = SharedStorage::default();
+    /// let layer = CaptureLayer::new(&storage).with_close_time_events();
+    /// let subscriber = Registry::default().with(layer);
+    /// tracing::subscriber::with_default(subscriber, || {
+    ///     let _connection = Connection;
+    ///     let _entered = tracing::info_span!("request").entered();
+    ///     // `_entered` is dropped before `_connection`
+    /// });
+    ///
+    /// let storage = storage.lock();
+    /// assert_eq!(storage.root_events().len(), 0);
+    /// let span = storage.all_spans().next().unwrap();
+    /// assert_eq!(span.events().len(), 1);
+    /// ```
+    #[must_use]
+    pub fn with_close_time_events(mut self) -> Self {
+        self.closed_spans = Some(Mutex::default());
+        self
+    }
+
+    /// Exports captured data to the specified `writer` in real time, in addition to storing it.
+    /// The data is written as JSON lines, one line per captured event or span lifecycle operation
+    /// (creation, recording values, entering, exiting and closing). This is useful to debug
+    /// long-running code.
+    ///
+    /// Each line has the `op` field specifying the operation (`new_span`, `record`, `enter`,
+    /// `exit`, `close` or `event`). Spans are identified by the `id` field, which is also used
+    /// in the `parent` field of new spans and events. Events have the same shape as produced
+    /// by [`CapturedEvent::to_json()`]. On `new_span` and `record` operations, `fields` contain
+    /// all span values recorded so far.
+    ///
+    /// The `writer` is called after releasing the storage lock, so slow I/O doesn't block
+    /// other threads accessing the storage. As a consequence, lines produced by different
+    /// threads may be written in a slightly different order than they were captured.
+    /// I/O errors when writing to the `writer` are ignored.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use tracing_subscriber::{layer::SubscriberExt, Registry};
+    /// # use tracing_capture::{CaptureLayer, SharedStorage};
+    /// let storage = SharedStorage::default();
+    /// let layer = CaptureLayer::new(&storage).with_json_writer(std::io::stderr());
+    /// let subscriber = Registry::default().with(layer);
+    /// tracing::subscriber::with_default(subscriber, || {
+    ///     tracing::info_span!("compute").in_scope(|| tracing::info!("done"));
+    /// });
+    /// ```
+    #[cfg(feature = "json")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "json")))]
+    #[must_use]
+    pub fn with_json_writer(mut self, writer: impl std::io::Write + Send + 'static) -> Self {
+        self.json_writer = Some(JsonWriter::new(writer));
+        self
+    }
+
+    /// Wraps the specified `inner` layer, so that all [`Layer`] hooks are forwarded to it
+    /// after capturing. This allows to capture tracing data while retaining the behavior
+    /// of an existing layer (e.g., a formatting layer) without composing layers manually.
+    ///
+    /// Capturing is not affected by filtering performed by the inner layer. Spans and events
+    /// are always enabled by the capturing layer; the inner layer's [`Layer::enabled()`]
+    /// and [`Layer::event_enabled()`] are only consulted to decide whether to forward hooks
+    /// to it. If the inner layer disables a span, no hooks related to the span (entering,
+    /// recording values, closing etc.) are forwarded to the inner layer.
+    ///
+    /// The [`Interest`] returned by the inner layer from [`Layer::register_callsite()`]
+    /// is ignored; the capturing layer always returns [`Interest::always()`]. Hence, the inner
+    /// layer cannot rely on interest caching, and its `enabled()` method is called
+    /// for each span and event instead.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use tracing_subscriber::{fmt, layer::SubscriberExt, Registry};
+    /// # use tracing_capture::{CaptureLayer, SharedStorage};
+    /// let storage = SharedStorage::default();
+    /// let layer = CaptureLayer::new(&storage).teeing(fmt::layer().with_test_writer());
+    /// let subscriber = Registry::default().with(layer);
+    /// tracing::subscriber::with_default(subscriber, || {
+    ///     tracing::info!("captured and logged");
+    /// });
+    /// assert_eq!(storage.lock().all_events().len(), 1);
+    /// ```
+    #[must_use]
+    pub fn teeing<L>(mut self, inner: L) -> Self
+    where
+        L: Layer<S> + Send + Sync + 'static,
+    {
+        self.inner = Some(Box::new(inner));
+        self
+    }
+
+    /// Routes captured spans and events to different storages based on the value
+    /// of the specified span `field`. When a span with the `field` is captured, the `router`
+    /// is called with the field value. If the router returns a storage, the span is captured
+    /// into it; otherwise (or if the span has no such field), the span is captured into
+    /// the same storage as its captured parent, or into the default layer storage if it has
+    /// no captured parent. Descendant spans and events are captured into the storage
+    /// of their captured parent, unless they are routed elsewhere themselves.
+    ///
+    /// The storage is determined once, when the span is captured; values
+    /// [recorded](tracing_core::Subscriber::record()) later do not re-route the span.
+    /// If a span is routed to a storage different from the storage of its parent,
+    /// it becomes a [root span](Storage::root_spans()) in the routed storage. Likewise,
+    /// [follows-from relations](CapturedSpan::follows_from()) are only captured
+    /// for spans in the same storage. [Known call sites](Self::with_known_call_sites())
+    /// are tracked in the default storage only.
+    ///
+    /// If a [JSON writer](Self::with_json_writer()) is set, lines for spans and events captured
+    /// into a storage other than the default one contain the `partition` field with the value
+    /// of the partitioning field that has routed the span (or its ancestor). Span IDs
+    /// in the lines are only unique within a partition.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use std::collections::HashMap;
+    /// # use tracing_subscriber::{layer::SubscriberExt, Registry};
+    /// # use tracing_capture::{CaptureLayer, SharedStorage};
+    /// let storages: HashMap<_, _> = [1_u64, 2].map(|id| (id, SharedStorage::default())).into();
+    /// let router_storages = storages.clone();
+    /// let default_storage = SharedStorage::default();
+    /// let layer = CaptureLayer::new(&default_storage).partitioned_by("workflow_id", move |value| {
+    ///     let id = value.as_uint()?;
+    ///     router_storages.get(&u64::try_from(id).ok()?).cloned()
+    /// });
+    /// let subscriber = Registry::default().with(layer);
+    /// tracing::subscriber::with_default(subscriber, || {
+    ///     tracing::info_span!("workflow", workflow_id = 1_u64).in_scope(|| {
+    ///         tracing::info!("started");
+    ///     });
+    ///     tracing::info!("not routed");
+    /// });
+    ///
+    /// let storage = storages[&1].lock();
+    /// assert_eq!(storage.all_spans().len(), 1);
+    /// assert_eq!(storage.all_events().len(), 1);
+    /// assert_eq!(storages[&2].lock().all_spans().len(), 0);
+    /// assert_eq!(default_storage.lock().all_events().len(), 1);
+    /// ```
+    #[must_use]
+    pub fn partitioned_by<F>(mut self, field: &'static str, router: F) -> Self
+    where
+        F: Fn(&TracedValue) -> Option<SharedStorage> + Send + Sync + 'static,
+    {
+        self.partitioning = Some((field, Box::new(router)));
+        self
+    }
+
+    fn now(&self) -> Option<Instant> {
+        self.timing.then(|| self.clock.now())
+    }
+
+    fn transform_values(&self, values: TracedValues<&'static str>) -> TracedValues<&'static str> {
+        let Some(transform) = &self.value_transform else {
+            return values;
+        };
+        values
+            .into_iter()
+            .map(|(name, mut value)| {
+                transform(name, &mut value);
+                (name, value)
+            })
+            .collect()
+    }
+
+    fn should_capture(&self, metadata: &Metadata<'_>, ctx: &Context<'_, S>) -> bool {
+        if self
+            .max_level
+            .is_some_and(|max_level| *metadata.level() > max_level)
+        {
+            return false;
+        }
+        if let Some(span_names) = &self.span_names {
+            if metadata.is_span() && !span_names.contains(metadata.name()) {
+                return false;
+            }
+        }
         self.filter
             .as_deref()
             .map_or(true, |filter| filter.enabled(metadata, ctx))
     }
 
-    fn lock(&self) -> impl ops::DerefMut<Target = Storage> + '_ {
-        self.storage
-            .write()
-            .expect("failed locking shared tracing data storage for write")
+    fn closed_spans(&self) -> Option<impl ops::DerefMut<Target = ClosedSpans> + '_> {
+        let closed_spans = self.closed_spans.as_ref()?;
+        Some(
+            closed_spans
+                .lock()
+                .expect("failed locking closed spans for write"),
+        )
+    }
+
+    fn lock(&self) -> Option<impl ops::DerefMut<Target = Storage> + '_> {
+        self.lock_partition(None)
+    }
+
+    /// Locks the storage for write. Returns `None` if the storage is read-locked by
+    /// the current thread, in which case locking it would deadlock.
+    fn lock_partition<'a>(
+        &'a self,
+        partition: Option<&'a SpanPartition>,
+    ) -> Option<impl ops::DerefMut<Target = Storage> + 'a> {
+        let storage = partition.map_or(&self.storage, |partition| &partition.storage);
+        if StorageReadGuard::is_locked_by_current_thread(storage) {
+            return None;
+        }
+        Some(
+            storage
+                .write()
+                .expect("failed locking shared tracing data storage for write"),
+        )
     }
 }
 
-impl<S> Layer<S> for CaptureLayer<S>
+/// Closest captured ancestor of a span or event.
+#[derive(Debug, Default)]
+struct CapturedParent {
+    id: Option<CapturedSpanId>,
+    partition: Option<SpanPartition>,
+    skipped_ancestors: usize,
+}
+
+/// Finds the closest captured span in the `scope`, returning it together with the number
+/// of non-captured spans preceding it.
+fn find_captured_parent<'a, R: LookupSpan<'a> + 'a>(
+    scope: impl Iterator<Item = SpanRef<'a, R>>,
+) -> CapturedParent {
+    let mut skipped_ancestors = 0;
+    for span in scope {
+        if let Some((id, partition)) = captured_span(&span) {
+            return CapturedParent {
+                id: Some(id),
+                partition,
+                skipped_ancestors,
+            };
+        }
+        skipped_ancestors += 1;
+    }
+    CapturedParent {
+        skipped_ancestors,
+        ..CapturedParent::default()
+    }
+}
+
+/// Returns the ID of the captured span together with its partition, or `None`
+/// if the span is not captured.
+fn captured_span<'a, R: LookupSpan<'a>>(
+    span: &SpanRef<'a, R>,
+) -> Option<(CapturedSpanId, Option<SpanPartition>)> {
+    let extensions = span.extensions();
+    let id = extensions.get::<CapturedSpanId>().copied()?;
+    Some((id, extensions.get::<SpanPartition>().cloned()))
+}
+
+/// Checks whether the span was disabled by the inner layer (if any).
+fn is_disabled_for_inner<S>(id: &Id, ctx: &Context<'_, S>) -> bool
 where
     S: Subscriber + for<'a> LookupSpan<'a>,
 {
-    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
-        if !self.enabled(attrs.metadata(), &ctx) {
+    ctx.span(id)
+        .is_some_and(|span| span.extensions().get::<DisabledForInner>().is_some())
+}
+
+impl<S> CaptureLayer<S>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn capture_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: &Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return; // the span is unknown to the subscriber; we won't be able to track it
+        };
+        if !self.should_capture(attrs.metadata(), ctx) {
+            if self.late_capture.is_some() {
+                let values = SkippedSpanValues(TracedValues::from_values_with_options(
+                    attrs.values(),
+                    &self.conversion_options,
+                ));
+                span.extensions_mut().insert(values);
+            }
             return;
         }
 
-        let parent_id = if let Some(mut scope) = ctx.span_scope(id) {
-            scope.find_map(|span| span.extensions().get::<CapturedSpanId>().copied())
-        } else {
-            None
+        let values = self.transform_values(TracedValues::from_values_with_options(
+            attrs.values(),
+            &self.conversion_options,
+        ));
+        let Some((arena_id, partition)) = self.store_span(&span, values) else {
+            return;
         };
-        let values = TracedValues::from_values(attrs.values());
-        let arena_id = self.lock().push_span(attrs.metadata(), values, parent_id);
-        ctx.span(id).unwrap().extensions_mut().insert(arena_id);
+        let mut extensions = span.extensions_mut();
+        extensions.insert(arena_id);
+        if let Some(partition) = partition {
+            extensions.insert(partition);
+        }
     }
 
-    fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
-        let span = ctx.span(id).unwrap();
-        if let Some(id) = span.extensions().get::<CapturedSpanId>().copied() {
-            self.lock().on_record(id, TracedValues::from_record(values));
+    /// Stores a newly captured span in the appropriate storage. Returns `None` if the storage
+    /// cannot be locked.
+    fn store_span(
+        &self,
+        span: &SpanRef<'_, S>,
+        values: TracedValues<&'static str>,
+    ) -> Option<(CapturedSpanId, Option<SpanPartition>)> {
+        let mut parent = find_captured_parent(span.scope().skip(1));
+        let partition = self.route_span(span, &values, &mut parent);
+        let now = self.now();
+        let mut storage = self.lock_partition(partition.as_ref())?;
+        let arena_id = storage.push_span(span.metadata(), values, parent.id);
+        let captured_span = &mut storage.spans[arena_id];
+        captured_span.skipped_ancestors = parent.skipped_ancestors;
+        captured_span.raw_id = Some(span.id().into_u64());
+        captured_span.raw_parent_id = span.parent().map(|parent| parent.id().into_u64());
+        #[cfg(feature = "tunnel-source-ids")]
+        {
+            captured_span.source_span_id = tracing_tunnel::TracingEventReceiver::source_span_id();
+        }
+        captured_span.timestamps = now.map(SpanTimestamps::new);
+        #[cfg(feature = "json")]
+        self.export_span_op(storage, partition.as_ref(), SpanOp::New, arena_id);
+        #[cfg(not(feature = "json"))]
+        drop(storage);
+        Some((arena_id, partition))
+    }
+
+    /// Determines the partition of a newly captured span. If the span is routed to a storage
+    /// other than the storage of its captured parent, the parent is reset.
+    fn route_span(
+        &self,
+        span: &SpanRef<'_, S>,
+        values: &TracedValues<&'static str>,
+        parent: &mut CapturedParent,
+    ) -> Option<SpanPartition> {
+        let Some((field, router)) = &self.partitioning else {
+            return None;
         };
+        let Some((key, storage)) = values
+            .get(field)
+            .and_then(|value| Some((value, router(value)?)))
+        else {
+            return parent.partition.clone();
+        };
+
+        let is_default = Arc::ptr_eq(&storage.inner, &self.storage);
+        let partition = (!is_default).then(|| SpanPartition {
+            storage: storage.inner,
+            key: key.clone(),
+        });
+        if !SpanPartition::is_same(partition.as_ref(), parent.partition.as_ref()) {
+            // The captured parent (if any) resides in another storage.
+            parent.id = None;
+            parent.partition = None;
+            parent.skipped_ancestors = span.scope().skip(1).count();
+        }
+        partition
     }
 
-    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
-        if !self.enabled(event.metadata(), &ctx) {
+    fn capture_record(&self, id: &Id, values: &Record<'_>, ctx: &Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return; // the span is unknown to the subscriber, e.g., because it is disabled
+        };
+        if let Some((id, partition)) = captured_span(&span) {
+            let values = self.transform_values(TracedValues::from_record_with_options(
+                values,
+                &self.conversion_options,
+            ));
+            let Some(mut storage) = self.lock_partition(partition.as_ref()) else {
+                return;
+            };
+            storage.on_record(id, values);
+            #[cfg(feature = "json")]
+            self.export_span_op(storage, partition.as_ref(), SpanOp::Record, id);
+            return;
+        }
+
+        let Some(late_capture) = &self.late_capture else {
+            return;
+        };
+        let mut extensions = span.extensions_mut();
+        let Some(SkippedSpanValues(span_values)) = extensions.get_mut::<SkippedSpanValues>() else {
+            return;
+        };
+        span_values.extend(TracedValues::from_record_with_options(
+            values,
+            &self.conversion_options,
+        ));
+        if late_capture(span.metadata(), span_values) {
+            let SkippedSpanValues(span_values) = extensions.remove::<SkippedSpanValues>().unwrap();
+            let span_values = self.transform_values(span_values);
+            if let Some((arena_id, partition)) = self.store_span(&span, span_values) {
+                extensions.insert(arena_id);
+                if let Some(partition) = partition {
+                    extensions.insert(partition);
+                }
+            }
+        }
+    }
+
+    fn capture_event(&self, event: &Event<'_>, ctx: &Context<'_, S>) {
+        if !self.should_capture(event.metadata(), ctx) {
             return;
         }
 
-        let parent_id = if let Some(mut scope) = ctx.event_scope(event) {
-            scope.find_map(|span| span.extensions().get::<CapturedSpanId>().copied())
+        // Only the first event after closing a span can be tied to it.
+        let closed_span = self
+            .closed_spans()
+            .and_then(|mut closed_spans| closed_spans.remove(&thread::current().id()));
+        let parent = if let Some(scope) = ctx.event_scope(event) {
+            find_captured_parent(scope)
         } else {
-            None
+            closed_span.map_or_else(CapturedParent::default, |(id, partition)| CapturedParent {
+                id: Some(id),
+                partition,
+                skipped_ancestors: 0,
+            })
+        };
+        if self.span_names.is_some() && parent.id.is_none() {
+            return; // the event is outside allowlisted spans
+        }
+        let values = self.transform_values(TracedValues::from_event_with_options(
+            event,
+            &self.conversion_options,
+        ));
+        let Some(mut storage) = self.lock_partition(parent.partition.as_ref()) else {
+            return;
         };
-        self.lock()
-            .push_event(event.metadata(), TracedValues::from_event(event), parent_id);
+        let arena_id = storage.push_event(event.metadata(), values, parent.id);
+        storage.events[arena_id].skipped_ancestors = parent.skipped_ancestors;
+        #[cfg(feature = "json")]
+        self.export_event(storage, parent.partition.as_ref(), arena_id);
     }
 
-    fn on_enter(&self, id: &Id, ctx: Context<'_, S>) {
-        let span = ctx.span(id).unwrap();
-        if let Some(id) = span.extensions().get::<CapturedSpanId>().copied() {
-            self.lock().on_span_enter(id);
+    fn capture_enter(&self, id: &Id, ctx: &Context<'_, S>) {
+        if let Some(mut closed_spans) = self.closed_spans() {
+            closed_spans.remove(&thread::current().id());
+        }
+        let Some(span) = ctx.span(id) else {
+            return;
         };
+        if let Some((id, partition)) = captured_span(&span) {
+            let now = self.now();
+            let Some(mut storage) = self.lock_partition(partition.as_ref()) else {
+                return;
+            };
+            storage.on_span_enter(id, now);
+            #[cfg(feature = "json")]
+            self.export_span_op(storage, partition.as_ref(), SpanOp::Enter, id);
+        }
     }
 
-    fn on_exit(&self, id: &Id, ctx: Context<'_, S>) {
-        let span = ctx.span(id).unwrap();
-        if let Some(id) = span.extensions().get::<CapturedSpanId>().copied() {
-            self.lock().on_span_exit(id);
+    fn capture_exit(&self, id: &Id, ctx: &Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
         };
+        if let Some((id, partition)) = captured_span(&span) {
+            let now = self.now();
+            let Some(mut storage) = self.lock_partition(partition.as_ref()) else {
+                return;
+            };
+            storage.on_span_exit(id, now);
+            #[cfg(feature = "json")]
+            self.export_span_op(storage, partition.as_ref(), SpanOp::Exit, id);
+        }
     }
 
-    fn on_close(&self, id: Id, ctx: Context<'_, S>) {
-        let span = ctx.span(&id).unwrap();
-        if let Some(id) = span.extensions().get::<CapturedSpanId>().copied() {
-            self.lock().on_span_closed(id);
+    fn capture_close(&self, id: &Id, ctx: &Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        if let Some((id, partition)) = captured_span(&span) {
+            let now = self.now();
+            let Some(mut storage) = self.lock_partition(partition.as_ref()) else {
+                return;
+            };
+            storage.on_span_closed(id, now);
+            #[cfg(feature = "json")]
+            self.export_span_op(storage, partition.as_ref(), SpanOp::Close, id);
+            #[cfg(not(feature = "json"))]
+            drop(storage);
+            if let Some(mut closed_spans) = self.closed_spans() {
+                closed_spans.insert(thread::current().id(), (id, partition));
+            }
+        };
+    }
+
+    /// Exports a span operation to the JSON writer, if any, releasing the storage lock.
+    /// The line is serialized while the lock is held, but is written after releasing it
+    /// so that blocking I/O doesn't stall other threads accessing the storage.
+    #[cfg(feature = "json")]
+    fn export_span_op(
+        &self,
+        storage: impl ops::Deref<Target = Storage>,
+        partition: Option<&SpanPartition>,
+        op: SpanOp,
+        id: CapturedSpanId,
+    ) {
+        let Some(writer) = &self.json_writer else {
+            return;
+        };
+        let partition_key = partition.map(|partition| &partition.key);
+        let line = JsonWriter::span_op_line(&storage, partition_key, op, id);
+        drop(storage);
+        writer.write_line(&line);
+    }
+
+    /// Exports an event to the JSON writer, if any, releasing the storage lock.
+    #[cfg(feature = "json")]
+    fn export_event(
+        &self,
+        storage: impl ops::Deref<Target = Storage>,
+        partition: Option<&SpanPartition>,
+        id: CapturedEventId,
+    ) {
+        let Some(writer) = &self.json_writer else {
+            return;
         };
+        let partition_key = partition.map(|partition| &partition.key);
+        let line = JsonWriter::event_line(&storage, partition_key, id);
+        drop(storage);
+        writer.write_line(&line);
+    }
+
+    /// Returns the inner layer if hooks for the specified span should be forwarded to it.
+    fn inner_for_span(
+        &self,
+        id: &Id,
+        ctx: &Context<'_, S>,
+    ) -> Option<&(dyn Layer<S> + Send + Sync)> {
+        let inner = self.inner.as_deref()?;
+        (!is_disabled_for_inner(id, ctx)).then_some(inner)
+    }
+
+    fn capture_follows_from(&self, id: &Id, follows_id: &Id, ctx: &Context<'_, S>) {
+        let (Some(span), Some(follows)) = (ctx.span(id), ctx.span(follows_id)) else {
+            return;
+        };
+        let (Some((id, partition)), Some((follows_id, follows_partition))) =
+            (captured_span(&span), captured_span(&follows))
+        else {
+            return;
+        };
+        if SpanPartition::is_same(partition.as_ref(), follows_partition.as_ref()) {
+            if let Some(mut storage) = self.lock_partition(partition.as_ref()) {
+                storage.on_follows_from(id, follows_id);
+            }
+        }
+    }
+}
+
+impl<S> Layer<S> for CaptureLayer<S>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_register_dispatch(&self, subscriber: &Dispatch) {
+        if let Some(inner) = &self.inner {
+            inner.on_register_dispatch(subscriber);
+        }
+    }
+
+    fn on_layer(&mut self, subscriber: &mut S) {
+        if let Some(inner) = &mut self.inner {
+            inner.on_layer(subscriber);
+        }
+    }
+
+    fn register_callsite(&self, metadata: &'static Metadata<'static>) -> Interest {
+        if self.track_call_sites {
+            if let Some(mut storage) = self.lock() {
+                storage.push_call_site(metadata);
+            }
+        }
+        if let Some(inner) = &self.inner {
+            // The returned interest is ignored: we need to capture all call sites.
+            // Filtering by the inner layer is applied when forwarding hooks instead.
+            inner.register_callsite(metadata);
+        }
+        Interest::always()
+    }
+
+    // `enabled()` and `event_enabled()` are not overridden: capturing must not be affected
+    // by filtering in the inner layer.
+
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        self.capture_new_span(attrs, id, &ctx);
+        if let Some(inner) = &self.inner {
+            if inner.enabled(attrs.metadata(), ctx.clone()) {
+                inner.on_new_span(attrs, id, ctx);
+            } else if let Some(span) = ctx.span(id) {
+                span.extensions_mut().insert(DisabledForInner);
+            }
+        }
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+        self.capture_record(id, values, &ctx);
+        if let Some(inner) = self.inner_for_span(id, &ctx) {
+            inner.on_record(id, values, ctx);
+        }
     }
 
     fn on_follows_from(&self, id: &Id, follows_id: &Id, ctx: Context<'_, S>) {
-        let span = ctx.span(id).unwrap();
-        let follows = ctx.span(follows_id).unwrap();
-        if let Some(id) = span.extensions().get::<CapturedSpanId>().copied() {
-            if let Some(follows_id) = follows.extensions().get::<CapturedSpanId>().copied() {
-                self.lock().on_follows_from(id, follows_id);
+        self.capture_follows_from(id, follows_id, &ctx);
+        if let Some(inner) = self.inner_for_span(id, &ctx) {
+            if !is_disabled_for_inner(follows_id, &ctx) {
+                inner.on_follows_from(id, follows_id, ctx);
             }
-        };
+        }
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        self.capture_event(event, &ctx);
+        if let Some(inner) = &self.inner {
+            if inner.enabled(event.metadata(), ctx.clone())
+                && inner.event_enabled(event, ctx.clone())
+            {
+                inner.on_event(event, ctx);
+            }
+        }
+    }
+
+    fn on_enter(&self, id: &Id, ctx: Context<'_, S>) {
+        self.capture_enter(id, &ctx);
+        if let Some(inner) = self.inner_for_span(id, &ctx) {
+            inner.on_enter(id, ctx);
+        }
+    }
+
+    fn on_exit(&self, id: &Id, ctx: Context<'_, S>) {
+        self.capture_exit(id, &ctx);
+        if let Some(inner) = self.inner_for_span(id, &ctx) {
+            inner.on_exit(id, ctx);
+        }
+    }
+
+    fn on_close(&self, id: Id, ctx: Context<'_, S>) {
+        self.capture_close(&id, &ctx);
+        if let Some(inner) = self.inner_for_span(&id, &ctx) {
+            inner.on_close(id, ctx);
+        }
+    }
+
+    fn on_id_change(&self, old: &Id, new: &Id, ctx: Context<'_, S>) {
+        if let Some(inner) = self.inner_for_span(old, &ctx) {
+            inner.on_id_change(old, new, ctx);
+        }
     }
 }
@@ -44,6 +44,36 @@
 //! );
 //! ```
 //!
+//! # Crate features
+//!
+//! ## `serde`
+//!
+//! *(Off by default)*
+//!
+//! Enables serializing captured data via [`Storage::snapshot()`] and restoring it
+//! from a [`StorageSnapshot`]. This allows archiving captured data and querying it offline.
+//!
+//! ## `json`
+//!
+//! *(Off by default)*
+//!
+//! Enables converting captured events to JSON values via [`CapturedEvent::to_json()`].
+//! This is useful for interop with JSON-based assertion tools. Additionally, captured data
+//! can be exported as JSON lines in real time via [`CaptureLayer::with_json_writer()`],
+//! and captured spans can be exported in the Chrome trace format via
+//! [`Storage::to_chrome_trace()`].
+//!
+//! ## `tunnel-source-ids`
+//!
+//! *(Off by default)*
+//!
+//! Enables [`CapturedSpan::source_span_id()`], which correlates spans replayed
+//! by a [`TracingEventReceiver`] with the spans in the source event stream.
+//! Requires the `receiver` feature of the [`tracing-tunnel`] crate.
+//!
+//! [`TracingEventReceiver`]: tracing_tunnel::TracingEventReceiver
+//! [`tracing-tunnel`]: https://docs.rs/tracing-tunnel
+//!
 //! # Alternatives / similar tools
 //!
 //! - [`tracing-test`] is a lower-level alternative.
@@ -54,25 +84,48 @@
 //! [`tracing-fluent-assertions`]: https://docs.rs/tracing-fluent-assertions
 
 // Documentation settings.
+#![cfg_attr(docsrs, feature(doc_cfg))]
 #![doc(html_root_url = "https://docs.rs/tracing-capture/0.2.0-beta.1")]
 // Linter settings.
 #![warn(missing_debug_implementations, missing_docs, bare_trait_objects)]
 #![warn(clippy::all, clippy::pedantic)]
 #![allow(clippy::must_use_candidate, clippy::module_name_repetitions)]
 
+use ::predicates::Predicate;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
 use tracing_core::Metadata;
 
-use std::{cmp, fmt, ops, ptr};
+use std::{
+    cmp,
+    collections::{HashSet, VecDeque},
+    fmt, ops, ptr,
+    time::{Duration, Instant},
+};
 
+mod diff;
 mod iter;
+#[cfg(feature = "json")]
+mod json;
 mod layer;
 pub mod predicates;
+#[cfg(feature = "serde")]
+mod snapshot;
+mod tree;
 
+#[cfg(feature = "serde")]
+pub use crate::snapshot::{SnapshotError, StorageSnapshot};
 pub use crate::{
-    iter::{CapturedEvents, CapturedSpans, DescendantEvents, DescendantSpans},
-    layer::{CaptureLayer, SharedStorage, Storage},
+    diff::StorageDiff,
+    iter::{
+        CapturedEvents, CapturedSpans, DescendantEvents, DescendantSpans, FlatEvent, TimelineItem,
+    },
+    layer::{CaptureClock, CaptureLayer, RealClock, SharedStorage, Storage},
+    tree::SpanTree,
 };
 
+#[cfg(feature = "tunnel-source-ids")]
+use tracing_tunnel::RawSpanId;
 use tracing_tunnel::{TracedValue, TracedValues};
 
 mod sealed {
@@ -84,7 +137,9 @@ struct CapturedEventInner {
     metadata: &'static Metadata<'static>,
     values: TracedValues<&'static str>,
     id: CapturedEventId,
+    seq: u64,
     parent_id: Option<CapturedSpanId>,
+    skipped_ancestors: usize,
 }
 
 type CapturedEventId = id_arena::Id<CapturedEventInner>;
@@ -137,10 +192,49 @@ impl fmt::Debug for CapturedEvent<'_> {
 
 impl<'a> CapturedEvent<'a> {
     /// Provides a reference to the event metadata.
+    ///
+    /// Metadata is always `'static` and thus remains valid for the entire lifetime
+    /// of the [`Storage`] (and beyond). For normal `tracing` spans and events, metadata is backed
+    /// by static call sites. Spans and events replayed by a [`TracingEventReceiver`] have
+    /// dynamic metadata; the receiver leaks it into a global arena, which is never freed.
+    /// Thus, metadata of replayed spans / events remains valid after the receiver is dropped.
+    ///
+    /// [`TracingEventReceiver`]: tracing_tunnel::TracingEventReceiver
     pub fn metadata(&self) -> &'static Metadata<'static> {
         self.inner.metadata
     }
 
+    /// Returns the path to the module where the event is defined, if known. This is a shortcut
+    /// for `self.metadata().module_path()`.
+    pub fn module_path(&self) -> Option<&'static str> {
+        self.inner.metadata.module_path()
+    }
+
+    /// Returns the target of the event. This is a shortcut for `self.metadata().target()`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use tracing_subscriber::{layer::SubscriberExt, Registry};
+    /// # use tracing_capture::{CaptureLayer, SharedStorage};
+    /// let storage = SharedStorage::default();
+    /// let subscriber = Registry::default().with(CaptureLayer::new(&storage));
+    /// tracing::subscriber::with_default(subscriber, || {
+    ///     tracing::info_span!(target: "app::http", "request").in_scope(|| {
+    ///         tracing::info!(target: "app::db", "querying");
+    ///     });
+    /// });
+    ///
+    /// let storage = storage.lock();
+    /// let span = storage.all_spans().next().unwrap();
+    /// assert_eq!(span.target(), "app::http");
+    /// let event = span.events().next().unwrap();
+    /// assert_eq!(event.target(), "app::db");
+    /// ```
+    pub fn target(&self) -> &'static str {
+        self.inner.metadata.target()
+    }
+
     /// Iterates over values associated with the event.
     pub fn values(&self) -> impl Iterator<Item = (&'a str, &'a TracedValue)> + 'a {
         self.inner.values.iter()
@@ -151,6 +245,19 @@ impl<'a> CapturedEvent<'a> {
         self.inner.values.get(name)
     }
 
+    /// Returns the sequence number of this event in the [`Storage`]. Sequence numbers
+    /// are assigned at capture time to both spans and events from a single monotonically
+    /// increasing counter, so they provide a total capture order across spans and events.
+    /// See [`CapturedSpan::seq()`] for details.
+    pub fn seq(&self) -> u64 {
+        self.inner.seq
+    }
+
+    /// Returns the number of values recorded in this event, excluding the `message` field.
+    pub fn field_count(&self) -> usize {
+        self.values().filter(|(name, _)| *name != "message").count()
+    }
+
     /// Returns the message recorded in this event, i.e., the value of the `message` field
     /// if it has a string presentation.
     pub fn message(&self) -> Option<&'a str> {
@@ -162,11 +269,50 @@ impl<'a> CapturedEvent<'a> {
         })
     }
 
+    /// Checks whether this event matches the specified [predicate](crate::predicates).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use predicates::str::contains;
+    /// # use tracing_core::Level;
+    /// # use tracing_subscriber::{layer::SubscriberExt, Registry};
+    /// # use tracing_capture::{predicates::{level, message}, CaptureLayer, SharedStorage};
+    /// let storage = SharedStorage::default();
+    /// let subscriber = Registry::default().with(CaptureLayer::new(&storage));
+    /// tracing::subscriber::with_default(subscriber, || {
+    ///     tracing::info!("started");
+    ///     tracing::warn!("operation failed");
+    ///     tracing::warn!("retry failed");
+    /// });
+    ///
+    /// let storage = storage.lock();
+    /// let predicate = level(Level::WARN) & message(contains("retry"));
+    /// let events: Vec<_> = storage
+    ///     .all_events()
+    ///     .filter(|event| event.matches(&predicate))
+    ///     .collect();
+    /// assert_eq!(events.len(), 1);
+    /// ```
+    pub fn matches(&self, predicate: &impl Predicate<Self>) -> bool {
+        predicate.eval(self)
+    }
+
     /// Returns the parent span for this event, or `None` if is not tied to a captured span.
     pub fn parent(&self) -> Option<CapturedSpan<'a>> {
         self.inner.parent_id.map(|id| self.storage.span(id))
     }
 
+    /// Returns the number of ancestor spans that were not captured (e.g., because of
+    /// [`CaptureLayer` filtering](CaptureLayer::with_filter())) between this event
+    /// and its [captured parent](Self::parent()). If there is no captured parent,
+    /// this is the number of non-captured ancestor spans.
+    ///
+    /// A non-zero value means that the captured parent is not the direct parent of the event.
+    pub fn skipped_ancestors(&self) -> usize {
+        self.inner.skipped_ancestors
+    }
+
     /// Returns the references to the ancestor spans, starting from the direct parent
     /// and ending in one of [root spans](Storage::root_spans()).
     pub fn ancestors(&self) -> impl Iterator<Item = CapturedSpan<'a>> + '_ {
@@ -202,7 +348,18 @@ impl ops::Index<&str> for CapturedEvent<'_> {
 }
 
 /// Statistics about a [`CapturedSpan`].
-#[derive(Debug, Clone, Copy, Default)]
+///
+/// Keep in mind that spans attached to futures (e.g., via [`Instrument`]) are entered and exited
+/// each time the future is polled. Thus, for such spans, [`Self::entered`] and [`Self::exited`]
+/// may be greater than 1 even if the span is logically used only once.
+///
+/// With the `serde` crate feature enabled, stats can be serialized and deserialized.
+/// Deserialization uses default values for missing fields and ignores unknown fields,
+/// so that the serialized stats remain compatible if fields are added or removed.
+///
+/// [`Instrument`]: https://docs.rs/tracing/0.1/tracing/trait.Instrument.html
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(default))]
 #[non_exhaustive]
 pub struct SpanStats {
     /// Number of times the span was entered.
@@ -213,16 +370,63 @@ pub struct SpanStats {
     pub is_closed: bool,
 }
 
+impl SpanStats {
+    /// Returns the number of times the span was entered. This is an alias for [`Self::entered`]
+    /// that makes intent clearer for async code: for a span attached to a future, this is
+    /// the number of times the future was polled (plus, possibly, an entry when the future
+    /// is dropped).
+    pub fn poll_count(&self) -> usize {
+        self.entered
+    }
+}
+
+/// Timestamps of operations with a [`CapturedSpan`]. Timestamps are only captured
+/// if [timing](CaptureLayer::with_timing()) is enabled for the capturing layer.
+#[derive(Debug, Clone, Copy)]
+#[non_exhaustive]
+pub struct SpanTimestamps {
+    /// Instant when the span was created.
+    pub created: Instant,
+    /// Instant when the span was entered for the first time.
+    pub first_entered: Option<Instant>,
+    /// Instant when the span was entered for the last time.
+    pub last_entered: Option<Instant>,
+    /// Instant when the span was exited for the last time.
+    pub last_exited: Option<Instant>,
+    /// Instant when the span was closed (dropped).
+    pub closed: Option<Instant>,
+}
+
+impl SpanTimestamps {
+    pub(crate) fn new(created: Instant) -> Self {
+        Self {
+            created,
+            first_entered: None,
+            last_entered: None,
+            last_exited: None,
+            closed: None,
+        }
+    }
+}
+
 #[derive(Debug)]
 struct CapturedSpanInner {
     metadata: &'static Metadata<'static>,
     values: TracedValues<&'static str>,
     stats: SpanStats,
+    timestamps: Option<SpanTimestamps>,
     id: CapturedSpanId,
+    seq: u64,
     parent_id: Option<CapturedSpanId>,
+    skipped_ancestors: usize,
+    raw_id: Option<u64>,
+    raw_parent_id: Option<u64>,
+    #[cfg(feature = "tunnel-source-ids")]
+    source_span_id: Option<RawSpanId>,
     child_ids: Vec<CapturedSpanId>,
     event_ids: Vec<CapturedEventId>,
     follows_from_ids: Vec<CapturedSpanId>,
+    busy_durations: Vec<Duration>,
 }
 
 type CapturedSpanId = id_arena::Id<CapturedSpanInner>;
@@ -282,12 +486,26 @@ impl fmt::Debug for CapturedSpan<'_> {
 }
 
 impl<'a> CapturedSpan<'a> {
-    /// Provides a reference to the span metadata.
+    /// Provides a reference to the span metadata. See [`CapturedEvent::metadata()`]
+    /// for details on the metadata lifetime.
     pub fn metadata(&self) -> &'static Metadata<'static> {
         self.inner.metadata
     }
 
+    /// Returns the path to the module where the span is defined, if known. This is a shortcut
+    /// for `self.metadata().module_path()`.
+    pub fn module_path(&self) -> Option<&'static str> {
+        self.inner.metadata.module_path()
+    }
+
+    /// Returns the target of the span. This is a shortcut for `self.metadata().target()`.
+    /// See [`CapturedEvent::target()`] for an example of usage.
+    pub fn target(&self) -> &'static str {
+        self.inner.metadata.target()
+    }
+
     /// Iterates over values that the span was created with, or which were recorded later.
+    /// Values recorded after the span is [closed](SpanStats::is_closed) are ignored.
     pub fn values(&self) -> impl Iterator<Item = (&'a str, &'a TracedValue)> + 'a {
         self.inner.values.iter()
     }
@@ -297,21 +515,236 @@ impl<'a> CapturedSpan<'a> {
         self.inner.values.get(name)
     }
 
+    /// Returns the sequence number of this span in the [`Storage`]. Sequence numbers
+    /// are assigned at capture time to both spans and events from a single monotonically
+    /// increasing counter, so they provide a total capture order across spans and events
+    /// (e.g., as used by [`Storage::timeline()`]). Unlike [timestamps](Self::timestamps()),
+    /// sequence numbers are always available and never collide.
+    ///
+    /// Sequence numbers are not retained in storage snapshots; in a restored storage,
+    /// spans are numbered before events.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use tracing_subscriber::{layer::SubscriberExt, Registry};
+    /// # use tracing_capture::{CaptureLayer, SharedStorage};
+    /// let storage = SharedStorage::default();
+    /// let subscriber = Registry::default().with(CaptureLayer::new(&storage));
+    /// tracing::subscriber::with_default(subscriber, || {
+    ///     tracing::info!("started");
+    ///     tracing::info_span!("compute").in_scope(|| tracing::info!("done"));
+    /// });
+    ///
+    /// let storage = storage.lock();
+    /// let span = storage.all_spans().next().unwrap();
+    /// let [started, done] = [0, 1].map(|i| storage.all_events().nth(i).unwrap());
+    /// assert!(started.seq() < span.seq());
+    /// assert!(span.seq() < done.seq());
+    /// ```
+    pub fn seq(&self) -> u64 {
+        self.inner.seq
+    }
+
+    /// Returns the number of values recorded in this span.
+    pub fn field_count(&self) -> usize {
+        self.inner.values.len()
+    }
+
+    /// Checks whether this span matches the specified [predicate](crate::predicates).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use predicates::ord::eq;
+    /// # use tracing_subscriber::{layer::SubscriberExt, Registry};
+    /// # use tracing_capture::{predicates::{field, name}, CaptureLayer, SharedStorage};
+    /// let storage = SharedStorage::default();
+    /// let subscriber = Registry::default().with(CaptureLayer::new(&storage));
+    /// tracing::subscriber::with_default(subscriber, || {
+    ///     for i in 0_i64..3 {
+    ///         tracing::info_span!("compute", i).in_scope(|| { /* ... */ });
+    ///     }
+    /// });
+    ///
+    /// let storage = storage.lock();
+    /// let predicate = name(eq("compute")) & field("i", 1_i64);
+    /// let span = storage.all_spans().find(|span| span.matches(&predicate));
+    /// assert!(span.is_some());
+    /// ```
+    pub fn matches(&self, predicate: &impl Predicate<Self>) -> bool {
+        predicate.eval(self)
+    }
+
     /// Returns statistics about span operations.
     pub fn stats(&self) -> SpanStats {
         self.inner.stats
     }
 
+    /// Checks whether the span was exited the same number of times it was entered.
+    /// An unbalanced span is still entered at the time of the check, or has leaked (e.g.,
+    /// its guard was forgotten). See also [`Storage::unbalanced_spans()`].
+    pub fn is_balanced(&self) -> bool {
+        self.inner.stats.entered == self.inner.stats.exited
+    }
+
+    /// Heuristically checks whether the span is attached to a future, i.e., was entered
+    /// more than once. See [`SpanStats`] docs for more details.
+    pub fn appears_async(&self) -> bool {
+        self.inner.stats.entered > 1
+    }
+
+    /// Returns timestamps of span operations, or `None` if [timing](CaptureLayer::with_timing())
+    /// was not enabled when capturing the span.
+    pub fn timestamps(&self) -> Option<SpanTimestamps> {
+        self.inner.timestamps
+    }
+
+    /// Returns durations of each period when the span was entered (i.e., between entering
+    /// the span and the following exit), in the order of exits. For spans attached to futures,
+    /// each duration corresponds to a single poll of the future. This allows asserting
+    /// on the distribution of busy time (e.g., its median or maximum) rather than only
+    /// on the total.
+    ///
+    /// Durations are only captured if [timing](CaptureLayer::with_timing()) is enabled;
+    /// otherwise, the returned slice is empty.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use tracing_subscriber::{layer::SubscriberExt, Registry};
+    /// # use tracing_capture::{CaptureLayer, SharedStorage};
+    /// let storage = SharedStorage::default();
+    /// let layer = CaptureLayer::new(&storage).with_timing();
+    /// let subscriber = Registry::default().with(layer);
+    /// tracing::subscriber::with_default(subscriber, || {
+    ///     let span = tracing::info_span!("poll");
+    ///     for _ in 0..3 {
+    ///         span.in_scope(|| { /* do some work */ });
+    ///     }
+    /// });
+    ///
+    /// let storage = storage.lock();
+    /// let span = storage.all_spans().next().unwrap();
+    /// let durations = span.busy_durations();
+    /// assert_eq!(durations.len(), 3);
+    /// ```
+    pub fn busy_durations(&self) -> &'a [Duration] {
+        &self.inner.busy_durations
+    }
+
+    /// Checks whether this span was closed before the `other` span was first entered.
+    /// Returns `None` if this span is not closed, the `other` span was never entered,
+    /// or timestamps are unavailable for either of spans (e.g., because
+    /// [timing](CaptureLayer::with_timing()) is not enabled).
+    pub fn closed_before(&self, other: &CapturedSpan<'_>) -> Option<bool> {
+        let closed = self.inner.timestamps?.closed?;
+        let other_entered = other.inner.timestamps?.first_entered?;
+        Some(closed < other_entered)
+    }
+
     /// Returns events attached to this span.
     pub fn events(&self) -> CapturedEvents<'a> {
         CapturedEvents::from_slice(self.storage, &self.inner.event_ids)
     }
 
+    /// Returns the number of events directly attached to this span. This is equivalent
+    /// to `self.events().len()`, but is more concise.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use tracing_subscriber::{layer::SubscriberExt, Registry};
+    /// # use tracing_capture::{CaptureLayer, SharedStorage};
+    /// let storage = SharedStorage::default();
+    /// let subscriber = Registry::default().with(CaptureLayer::new(&storage));
+    /// tracing::subscriber::with_default(subscriber, || {
+    ///     let _entered = tracing::info_span!("compute").entered();
+    ///     tracing::info!("started");
+    ///     tracing::info_span!("inner").in_scope(|| tracing::info!("working"));
+    ///     tracing::info!("finished");
+    /// });
+    ///
+    /// let storage = storage.lock();
+    /// let span = storage.all_spans().next().unwrap();
+    /// assert_eq!(span.event_count(), 2);
+    /// assert_eq!(span.child_count(), 1);
+    /// ```
+    pub fn event_count(&self) -> usize {
+        self.inner.event_ids.len()
+    }
+
     /// Returns the reference to the parent span, if any.
     pub fn parent(&self) -> Option<Self> {
         self.inner.parent_id.map(|id| self.storage.span(id))
     }
 
+    /// Returns the number of ancestor spans that were not captured (e.g., because of
+    /// [`CaptureLayer` filtering](CaptureLayer::with_filter())) between this span
+    /// and its [captured parent](Self::parent()). If there is no captured parent,
+    /// this is the number of non-captured ancestor spans.
+    ///
+    /// A non-zero value means that the captured parent is not the direct parent of the span.
+    pub fn skipped_ancestors(&self) -> usize {
+        self.inner.skipped_ancestors
+    }
+
+    /// Returns the raw ID of this span as assigned by the tracing [`Subscriber`].
+    /// This allows correlating the span with external systems that know raw span IDs.
+    ///
+    /// Keep in mind that raw IDs are only meaningful within the subscriber that has captured
+    /// the span, and can be reused by the subscriber after the span is closed.
+    /// Returns `None` if the span was not captured by a [`CaptureLayer`].
+    ///
+    /// [`Subscriber`]: tracing_core::Subscriber
+    pub fn raw_id(&self) -> Option<u64> {
+        self.inner.raw_id
+    }
+
+    /// Returns the raw ID of the direct parent span as assigned by the tracing [`Subscriber`].
+    /// Unlike [`Self::parent()`], the parent is not necessarily captured (e.g., it may be
+    /// filtered out by the [`CaptureLayer`]). The same caveats as for [`Self::raw_id()`] apply.
+    ///
+    /// [`Subscriber`]: tracing_core::Subscriber
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use tracing_core::Level;
+    /// # use tracing_subscriber::{layer::SubscriberExt, Registry};
+    /// # use tracing_capture::{CaptureLayer, SharedStorage};
+    /// let storage = SharedStorage::default();
+    /// let layer = CaptureLayer::new(&storage).with_max_level(Level::INFO);
+    /// let subscriber = Registry::default().with(layer);
+    /// tracing::subscriber::with_default(subscriber, || {
+    ///     let _outer = tracing::info_span!("outer").entered();
+    ///     let middle = tracing::debug_span!("middle");
+    ///     let _middle_guard = middle.enter();
+    ///     let _inner = tracing::info_span!("inner").entered();
+    ///     assert_eq!(
+    ///         storage.lock().all_spans().nth(1).unwrap().raw_parent_id(),
+    ///         middle.id().map(|id| id.into_u64())
+    ///     );
+    /// });
+    /// ```
+    pub fn raw_parent_id(&self) -> Option<u64> {
+        self.inner.raw_parent_id
+    }
+
+    /// Returns the ID of the span in the source [`TracingEvent`] stream if the span was created
+    /// by replaying the stream with a [`TracingEventReceiver`]. This allows correlating captured
+    /// spans with the spans in the source stream. See
+    /// [`TracingEventReceiver::source_span_id()`] for details.
+    ///
+    /// [`TracingEvent`]: tracing_tunnel::TracingEvent
+    /// [`TracingEventReceiver`]: tracing_tunnel::TracingEventReceiver
+    /// [`TracingEventReceiver::source_span_id()`]: tracing_tunnel::TracingEventReceiver::source_span_id()
+    #[cfg(feature = "tunnel-source-ids")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "tunnel-source-ids")))]
+    pub fn source_span_id(&self) -> Option<RawSpanId> {
+        self.inner.source_span_id
+    }
+
     /// Returns the references to the ancestor spans, starting from the direct parent
     /// and ending in one of [root spans](Storage::root_spans()).
     pub fn ancestors(&self) -> impl Iterator<Item = CapturedSpan<'a>> + '_ {
@@ -323,6 +756,24 @@ impl<'a> CapturedSpan<'a> {
         CapturedSpans::from_slice(self.storage, &self.inner.child_ids)
     }
 
+    /// Iterates over the siblings of this span, i.e., other children of its [parent](Self::parent()),
+    /// in the order of their capture. If this span is a root span, iterates over
+    /// other [root spans](Storage::root_spans()). The span itself is not included.
+    pub fn siblings(&self) -> impl Iterator<Item = CapturedSpan<'a>> + '_ {
+        let peers = match self.parent() {
+            Some(parent) => parent.children(),
+            None => self.storage.root_spans(),
+        };
+        peers.filter(move |span| span != self)
+    }
+
+    /// Returns the number of direct children of this span. This is equivalent
+    /// to `self.children().len()`, but is more concise. See [`Self::event_count()`]
+    /// for an example of usage.
+    pub fn child_count(&self) -> usize {
+        self.inner.child_ids.len()
+    }
+
     /// Iterates over the descendants of this span.
     ///
     /// In the simplest case (spans are not re-entered, span parents are contextual), the iteration
@@ -339,10 +790,65 @@ impl<'a> CapturedSpan<'a> {
         DescendantEvents::new(self)
     }
 
+    /// Returns all events in the subtree rooted at this span, i.e., events
+    /// [directly attached](Self::events()) to this span and its
+    /// [descendant events](Self::descendant_events()). Unlike with `descendant_events()`,
+    /// the events are ordered by capture, consistently with their [`PartialOrd`] implementation.
+    pub fn all_events_ordered(&self) -> Vec<CapturedEvent<'a>> {
+        let mut events: Vec<_> = self.events().chain(self.descendant_events()).collect();
+        events.sort_unstable_by_key(|event| event.inner.id);
+        events
+    }
+
     /// Iterates over the spans this span follows from.
     pub fn follows_from(&self) -> CapturedSpans<'a> {
         CapturedSpans::from_slice(self.storage, &self.inner.follows_from_ids)
     }
+
+    /// Iterates over the transitive closure of the [follows-from](Self::follows_from())
+    /// relation, i.e., spans this span follows from, spans *they* follow from, etc.
+    /// Spans are yielded in the breadth-first order, so direct relations come first.
+    /// Each span is yielded once, and this span itself is never yielded, even if
+    /// the relations form a cycle.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use tracing_subscriber::{layer::SubscriberExt, Registry};
+    /// # use tracing_capture::{CaptureLayer, SharedStorage};
+    /// let storage = SharedStorage::default();
+    /// let subscriber = Registry::default().with(CaptureLayer::new(&storage));
+    /// tracing::subscriber::with_default(subscriber, || {
+    ///     let request = tracing::info_span!("request");
+    ///     let job = tracing::info_span!("job");
+    ///     job.follows_from(&request);
+    ///     let retry = tracing::info_span!("retry");
+    ///     retry.follows_from(&job);
+    /// });
+    ///
+    /// let storage = storage.lock();
+    /// let retry = storage.all_spans().last().unwrap();
+    /// let names: Vec<_> = retry
+    ///     .follows_from_transitive()
+    ///     .map(|span| span.metadata().name())
+    ///     .collect();
+    /// assert_eq!(names, ["job", "request"]);
+    /// ```
+    pub fn follows_from_transitive(&self) -> impl Iterator<Item = CapturedSpan<'a>> + 'a {
+        let storage = self.storage;
+        let mut visited = HashSet::from([self.inner.id]);
+        let mut queue: VecDeque<_> = self.inner.follows_from_ids.iter().copied().collect();
+        std::iter::from_fn(move || {
+            while let Some(id) = queue.pop_front() {
+                if visited.insert(id) {
+                    let span = storage.span(id);
+                    queue.extend(span.inner.follows_from_ids.iter().copied());
+                    return Some(span);
+                }
+            }
+            None
+        })
+    }
 }
 
 impl PartialEq for CapturedSpan<'_> {
@@ -377,10 +883,16 @@ impl ops::Index<&str> for CapturedSpan<'_> {
 pub trait Captured<'a>: Eq + PartialOrd + sealed::Sealed {
     /// Provides a reference to the span / event metadata.
     fn metadata(&self) -> &'static Metadata<'static>;
+    /// Returns the target of the span / event. This is a shortcut for `self.metadata().target()`.
+    fn target(&self) -> &'static str {
+        self.metadata().target()
+    }
     /// Returns a value for the specified field, or `None` if the value is not defined.
     fn value(&self, name: &str) -> Option<&'a TracedValue>;
     /// Returns the reference to the parent span, if any.
     fn parent(&self) -> Option<CapturedSpan<'a>>;
+    /// Checks whether this is a [`CapturedSpan`] (as opposed to a [`CapturedEvent`]).
+    fn is_span(&self) -> bool;
 }
 
 impl sealed::Sealed for CapturedSpan<'_> {}
@@ -400,6 +912,11 @@ impl<'a> Captured<'a> for CapturedSpan<'a> {
     fn parent(&self) -> Option<CapturedSpan<'a>> {
         self.parent()
     }
+
+    #[inline]
+    fn is_span(&self) -> bool {
+        true
+    }
 }
 
 impl sealed::Sealed for CapturedEvent<'_> {}
@@ -419,6 +936,11 @@ impl<'a> Captured<'a> for CapturedEvent<'a> {
     fn parent(&self) -> Option<CapturedSpan<'a>> {
         self.parent()
     }
+
+    #[inline]
+    fn is_span(&self) -> bool {
+        false
+    }
 }
 
 #[cfg(doctest)]
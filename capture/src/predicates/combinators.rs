@@ -113,7 +113,189 @@ where
     }
 }
 
+fn fmt_joined<P: fmt::Display>(
+    formatter: &mut fmt::Formatter<'_>,
+    predicates: &[P],
+    op: &str,
+    empty: &str,
+) -> fmt::Result {
+    if predicates.is_empty() {
+        return formatter.write_str(empty);
+    }
+    formatter.write_str("(")?;
+    for (i, predicate) in predicates.iter().enumerate() {
+        if i > 0 {
+            write!(formatter, " {op} ")?;
+        }
+        fmt::Display::fmt(predicate, formatter)?;
+    }
+    formatter.write_str(")")
+}
+
+/// Boolean "and" combinator for a dynamic collection of homogeneous predicates.
+/// Produced by the [`all_of()`] function.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AllOf<P> {
+    predicates: Vec<P>,
+}
+
+/// Creates a predicate that matches iff all of the provided `predicates` match.
+/// If there are no predicates, the created predicate always matches.
+///
+/// Unlike chaining predicates with the `&` operator, this allows building predicates
+/// programmatically, e.g. in a loop.
+///
+/// # Examples
+///
+/// ```
+/// # use tracing_capture::{predicates::{all_of, field, ScanExt}, Storage};
+/// # fn test_wrapper(storage: &Storage) {
+/// let storage: &Storage = // ...
+/// #   storage;
+/// let expected_fields = [("i", 1_i64), ("j", 2), ("k", 3)];
+/// let predicate = all_of(
+///     expected_fields
+///         .into_iter()
+///         .map(|(name, value)| field(name, value)),
+/// );
+/// let event = storage.scan_events().single(&predicate);
+/// # }
+/// ```
+pub fn all_of<P: PredicateReflection>(predicates: impl IntoIterator<Item = P>) -> AllOf<P> {
+    AllOf {
+        predicates: predicates.into_iter().collect(),
+    }
+}
+
+impl<P: fmt::Display> fmt::Display for AllOf<P> {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt_joined(formatter, &self.predicates, "&&", "true")
+    }
+}
+
+impl<P: PredicateReflection> PredicateReflection for AllOf<P> {}
+
+impl<P, Item: ?Sized> Predicate<Item> for AllOf<P>
+where
+    P: Predicate<Item>,
+{
+    fn eval(&self, variable: &Item) -> bool {
+        self.predicates
+            .iter()
+            .all(|predicate| predicate.eval(variable))
+    }
+
+    fn find_case(&self, expected: bool, variable: &Item) -> Option<Case<'_>> {
+        let case = Case::new(Some(self), expected);
+        if expected {
+            // We need all child cases.
+            self.predicates.iter().try_fold(case, |case, predicate| {
+                let child = predicate.find_case(expected, variable)?;
+                Some(case.add_child(child))
+            })
+        } else {
+            // Return the first failing case if present.
+            self.predicates
+                .iter()
+                .find_map(|predicate| predicate.find_case(expected, variable))
+                .map(|child| case.add_child(child))
+        }
+    }
+}
+
+/// Boolean "or" combinator for a dynamic collection of homogeneous predicates.
+/// Produced by the [`any_of()`] function.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AnyOf<P> {
+    predicates: Vec<P>,
+}
+
+/// Creates a predicate that matches iff any of the provided `predicates` match.
+/// If there are no predicates, the created predicate never matches.
+///
+/// Unlike chaining predicates with the `|` operator, this allows building predicates
+/// programmatically, e.g. in a loop.
+///
+/// # Examples
+///
+/// ```
+/// # use predicates::ord::eq;
+/// # use tracing_capture::{predicates::{any_of, name, ScanExt}, Storage};
+/// # fn test_wrapper(storage: &Storage) {
+/// let storage: &Storage = // ...
+/// #   storage;
+/// let names = ["fib", "compute"];
+/// let predicate = any_of(names.into_iter().map(|s| name(eq(s))));
+/// storage.scan_spans().all(&predicate);
+/// # }
+/// ```
+pub fn any_of<P: PredicateReflection>(predicates: impl IntoIterator<Item = P>) -> AnyOf<P> {
+    AnyOf {
+        predicates: predicates.into_iter().collect(),
+    }
+}
+
+impl<P: fmt::Display> fmt::Display for AnyOf<P> {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt_joined(formatter, &self.predicates, "||", "false")
+    }
+}
+
+impl<P: PredicateReflection> PredicateReflection for AnyOf<P> {}
+
+impl<P, Item: ?Sized> Predicate<Item> for AnyOf<P>
+where
+    P: Predicate<Item>,
+{
+    fn eval(&self, variable: &Item) -> bool {
+        self.predicates
+            .iter()
+            .any(|predicate| predicate.eval(variable))
+    }
+
+    fn find_case(&self, expected: bool, variable: &Item) -> Option<Case<'_>> {
+        let case = Case::new(Some(self), expected);
+        if expected {
+            // Return the first matching case if present.
+            self.predicates
+                .iter()
+                .find_map(|predicate| predicate.find_case(expected, variable))
+                .map(|child| case.add_child(child))
+        } else {
+            // We need all child cases.
+            self.predicates.iter().try_fold(case, |case, predicate| {
+                let child = predicate.find_case(expected, variable)?;
+                Some(case.add_child(child))
+            })
+        }
+    }
+}
+
 macro_rules! impl_bool_ops {
+    ($name:ident) => {
+        impl<Rhs> core::ops::BitAnd<Rhs> for $name
+        where
+            Rhs: predicates::reflection::PredicateReflection,
+        {
+            type Output = $crate::predicates::And<Self, Rhs>;
+
+            fn bitand(self, rhs: Rhs) -> Self::Output {
+                $crate::predicates::And::new(self, rhs)
+            }
+        }
+
+        impl<Rhs> core::ops::BitOr<Rhs> for $name
+        where
+            Rhs: predicates::reflection::PredicateReflection,
+        {
+            type Output = $crate::predicates::Or<Self, Rhs>;
+
+            fn bitor(self, rhs: Rhs) -> Self::Output {
+                $crate::predicates::Or::new(self, rhs)
+            }
+        }
+    };
+
     ($name:ident <$($ty_var:ident),+>) => {
         impl<Rhs, $($ty_var,)+> core::ops::BitAnd<Rhs> for $name<$($ty_var,)+>
         where
@@ -143,3 +325,5 @@ macro_rules! impl_bool_ops {
 
 impl_bool_ops!(And<T, U>);
 impl_bool_ops!(Or<T, U>);
+impl_bool_ops!(AllOf<P>);
+impl_bool_ops!(AnyOf<P>);
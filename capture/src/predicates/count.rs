@@ -0,0 +1,135 @@
+//! `event_count()` and `subtree_event_count()` predicate factories.
+
+use predicates::{
+    reflection::{Case, PredicateReflection, Product},
+    Predicate,
+};
+
+use std::fmt;
+
+use crate::CapturedSpan;
+
+/// Creates a predicate for the number of events [directly attached](CapturedSpan::events())
+/// to a [`CapturedSpan`].
+///
+/// # Arguments
+///
+/// The argument of this function can be any `usize` predicate, e.g. `gt(2)`.
+///
+/// # Examples
+///
+/// ```
+/// # use predicates::ord::{eq, gt};
+/// # use tracing_subscriber::{layer::SubscriberExt, Registry};
+/// # use tracing_capture::{predicates::{event_count, ScanExt}, CaptureLayer, SharedStorage};
+/// let storage = SharedStorage::default();
+/// let subscriber = Registry::default().with(CaptureLayer::new(&storage));
+/// tracing::subscriber::with_default(subscriber, || {
+///     tracing::info_span!("compute").in_scope(|| {
+///         for i in 0..3 {
+///             tracing::info!(i, "iteration");
+///         }
+///     });
+///     tracing::info_span!("idle").in_scope(|| { /* do nothing */ });
+/// });
+///
+/// let storage = storage.lock();
+/// let span = storage.scan_spans().single(&event_count(gt(2)));
+/// assert_eq!(span.metadata().name(), "compute");
+/// let _ = storage.scan_spans().single(&event_count(eq(0)));
+/// ```
+pub fn event_count<P: Predicate<usize>>(matches: P) -> EventCountPredicate<P> {
+    EventCountPredicate {
+        matches,
+        subtree: false,
+    }
+}
+
+/// Creates a predicate for the total number of events in the subtree rooted at
+/// a [`CapturedSpan`], i.e., events attached to the span itself and to all its
+/// [descendants](CapturedSpan::descendants()).
+///
+/// # Arguments
+///
+/// The argument of this function can be any `usize` predicate, e.g. `gt(2)`.
+///
+/// # Examples
+///
+/// ```
+/// # use predicates::ord::eq;
+/// # use tracing_subscriber::{layer::SubscriberExt, Registry};
+/// # use tracing_capture::{
+/// #     predicates::{event_count, name, subtree_event_count, ScanExt}, CaptureLayer, SharedStorage,
+/// # };
+/// let storage = SharedStorage::default();
+/// let subscriber = Registry::default().with(CaptureLayer::new(&storage));
+/// tracing::subscriber::with_default(subscriber, || {
+///     tracing::info_span!("outer").in_scope(|| {
+///         tracing::info!("started");
+///         tracing::info_span!("inner").in_scope(|| tracing::info!("working"));
+///     });
+/// });
+///
+/// let storage = storage.lock();
+/// let predicate = name(eq("outer")) & event_count(eq(1)) & subtree_event_count(eq(2));
+/// let _ = storage.scan_spans().single(&predicate);
+/// ```
+pub fn subtree_event_count<P: Predicate<usize>>(matches: P) -> EventCountPredicate<P> {
+    EventCountPredicate {
+        matches,
+        subtree: true,
+    }
+}
+
+/// Predicate for the number of events in a [`CapturedSpan`] returned by
+/// the [`event_count()`] and [`subtree_event_count()`] functions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EventCountPredicate<P> {
+    matches: P,
+    subtree: bool,
+}
+
+impl_bool_ops!(EventCountPredicate<P>);
+
+impl<P: Predicate<usize>> EventCountPredicate<P> {
+    fn count(&self, span: &CapturedSpan<'_>) -> usize {
+        if self.subtree {
+            span.event_count() + span.descendant_events().count()
+        } else {
+            span.event_count()
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        if self.subtree {
+            "subtree_event_count"
+        } else {
+            "event_count"
+        }
+    }
+}
+
+impl<P: Predicate<usize>> fmt::Display for EventCountPredicate<P> {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(formatter, "{}({})", self.name(), self.matches)
+    }
+}
+
+impl<P: Predicate<usize>> PredicateReflection for EventCountPredicate<P> {}
+
+impl<P: Predicate<usize>> Predicate<CapturedSpan<'_>> for EventCountPredicate<P> {
+    fn eval(&self, variable: &CapturedSpan<'_>) -> bool {
+        self.matches.eval(&self.count(variable))
+    }
+
+    fn find_case(&self, expected: bool, variable: &CapturedSpan<'_>) -> Option<Case<'_>> {
+        let count = self.count(variable);
+        let child = self.matches.find_case(expected, &count)?;
+        let product = Product::new(self.name(), count);
+        Some(
+            Case::new(Some(self), expected)
+                .add_child(child)
+                .add_product(product),
+        )
+    }
+}
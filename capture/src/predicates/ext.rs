@@ -112,6 +112,26 @@ where
         first
     }
 
+    /// Finds at most one item matching the predicate. Unlike [`Self::single()`], returns `None`
+    /// if no items match the predicate.
+    ///
+    /// # Panics
+    ///
+    /// Panics with an informative message if multiple items match the predicate.
+    pub fn at_most_one<P: Predicate<I::Item> + ?Sized>(self, predicate: &P) -> Option<I::Item> {
+        let mut iter = self.iter();
+        let first = iter.find(|item| predicate.eval(item))?;
+
+        let second = iter.find(|item| predicate.eval(item));
+        if let Some(second) = second {
+            panic!(
+                "multiple items match predicate {predicate}: {:#?}",
+                [first, second]
+            );
+        }
+        Some(first)
+    }
+
     /// Finds the first item matching the predicate.
     ///
     /// # Panics
@@ -123,6 +143,13 @@ where
             .unwrap_or_else(|| panic!("no items have matched predicate {predicate}"))
     }
 
+    /// Collects all items matching the predicate, in the iteration order (e.g., for spans
+    /// and events, this is the capture order). Unlike other methods, this method
+    /// does not panic if no items match.
+    pub fn matching<P: Predicate<I::Item> + ?Sized>(self, predicate: &P) -> Vec<I::Item> {
+        self.iter().filter(|item| predicate.eval(item)).collect()
+    }
+
     /// Checks that all of the items match the predicate.
     ///
     /// # Panics
@@ -5,7 +5,7 @@ use predicates::{
     Predicate,
 };
 
-use std::{any::type_name, borrow::Borrow, fmt, marker::PhantomData};
+use std::{any::type_name, borrow::Borrow, cmp::Ordering, fmt, marker::PhantomData};
 
 use crate::{Captured, CapturedEvent};
 use tracing_tunnel::{FromTracedValue, TracedValue};
@@ -113,7 +113,7 @@ impl<'a, P: Predicate<TracedValue>, T: Captured<'a>> Predicate<T> for FieldPredi
     fn eval(&self, variable: &T) -> bool {
         variable
             .value(self.name)
-            .map_or(false, |value| self.matches.eval(value))
+            .is_some_and(|value| self.matches.eval(value))
     }
 
     fn find_case(&self, expected: bool, variable: &T) -> Option<Case<'_>> {
@@ -160,6 +160,142 @@ impl<V: fmt::Debug + PartialEq<TracedValue>> Predicate<TracedValue> for EquivPre
     }
 }
 
+#[doc(hidden)] // implementation detail (yet?)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ApproxPredicate {
+    value: f64,
+    epsilon: f64,
+}
+
+impl fmt::Display for ApproxPredicate {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            formatter,
+            "var ~= {:?} (eps = {:?})",
+            self.value, self.epsilon
+        )
+    }
+}
+
+impl PredicateReflection for ApproxPredicate {}
+
+impl Predicate<TracedValue> for ApproxPredicate {
+    fn eval(&self, variable: &TracedValue) -> bool {
+        variable.approx_eq(self.value, self.epsilon)
+    }
+
+    fn find_case(&self, expected: bool, variable: &TracedValue) -> Option<Case<'_>> {
+        if self.eval(variable) == expected {
+            let product = Product::new("var", format!("{variable:?}"));
+            Some(Case::new(Some(self), expected).add_product(product))
+        } else {
+            None
+        }
+    }
+}
+
+/// Creates a predicate checking that a particular numeric field of a [`CapturedSpan`]
+/// or [`CapturedEvent`] is within `epsilon` of the `target` value. This is a more robust
+/// alternative to comparing floating-point fields with [`field()`]. Integer fields are matched
+/// as well; see [`TracedValue::approx_eq()`] for details.
+///
+/// [`CapturedSpan`]: crate::CapturedSpan
+///
+/// # Examples
+///
+/// ```
+/// # use tracing_subscriber::{layer::SubscriberExt, Registry};
+/// # use tracing_capture::{predicates::{field_approx, ScanExt}, CaptureLayer, SharedStorage};
+/// let storage = SharedStorage::default();
+/// let subscriber = Registry::default().with(CaptureLayer::new(&storage));
+/// tracing::subscriber::with_default(subscriber, || {
+///     tracing::info!(ratio = 1.0 / 3.0, latency_ms = 12_u64, "computed");
+/// });
+///
+/// let storage = storage.lock();
+/// let events = storage.scan_events();
+/// let _ = events.single(&field_approx("ratio", 0.333, 1e-3));
+/// let _ = events.single(&field_approx("latency_ms", 10.0, 5.0));
+/// ```
+pub fn field_approx(
+    name: &'static str,
+    target: f64,
+    epsilon: f64,
+) -> FieldPredicate<ApproxPredicate> {
+    FieldPredicate {
+        name,
+        matches: ApproxPredicate {
+            value: target,
+            epsilon,
+        },
+    }
+}
+
+/// Creates a predicate checking that a particular field of a [`CapturedSpan`]
+/// or [`CapturedEvent`] is equal to any of the specified `values`. Values are compared
+/// to the [`TracedValue`] using the corresponding [`PartialEq`] implementation, similar to
+/// [`field()`]. If the field is missing or `values` are empty, the predicate is false.
+///
+/// [`CapturedSpan`]: crate::CapturedSpan
+///
+/// # Examples
+///
+/// ```
+/// # use tracing_subscriber::{layer::SubscriberExt, Registry};
+/// # use tracing_capture::{predicates::{field_in, ScanExt}, CaptureLayer, SharedStorage};
+/// let storage = SharedStorage::default();
+/// let subscriber = Registry::default().with(CaptureLayer::new(&storage));
+/// tracing::subscriber::with_default(subscriber, || {
+///     tracing::info!(status = 204_u64, "request processed");
+/// });
+///
+/// let storage = storage.lock();
+/// let events = storage.scan_events();
+/// let _ = events.single(&field_in("status", [200_u64, 204, 301]));
+/// events.none(&field_in("status", [400_u64, 404]));
+/// ```
+pub fn field_in<V, I>(name: &'static str, values: I) -> FieldPredicate<InSetPredicate<V>>
+where
+    V: fmt::Debug + PartialEq<TracedValue>,
+    I: IntoIterator<Item = V>,
+{
+    FieldPredicate {
+        name,
+        matches: InSetPredicate {
+            values: values.into_iter().collect(),
+        },
+    }
+}
+
+#[doc(hidden)] // implementation detail (yet?)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InSetPredicate<V> {
+    values: Vec<V>,
+}
+
+impl<V: fmt::Debug> fmt::Display for InSetPredicate<V> {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(formatter, "var in {:?}", self.values)
+    }
+}
+
+impl<V: fmt::Debug> PredicateReflection for InSetPredicate<V> {}
+
+impl<V: fmt::Debug + PartialEq<TracedValue>> Predicate<TracedValue> for InSetPredicate<V> {
+    fn eval(&self, variable: &TracedValue) -> bool {
+        self.values.iter().any(|value| *value == *variable)
+    }
+
+    fn find_case(&self, expected: bool, variable: &TracedValue) -> Option<Case<'_>> {
+        if self.eval(variable) == expected {
+            let product = Product::new("var", format!("{variable:?}"));
+            Some(Case::new(Some(self), expected).add_product(product))
+        } else {
+            None
+        }
+    }
+}
+
 /// Creates a predicate for a [`TracedValue`] that checks whether the value matches
 /// the specified criteria for a particular subtype (e.g., an unsigned integer).
 /// If the value has another subtype, the predicate is false.
@@ -240,7 +376,7 @@ where
     P: Predicate<T>,
 {
     fn eval(&self, variable: &TracedValue) -> bool {
-        T::from_value(variable).map_or(false, |value| self.matches.eval(value.borrow()))
+        T::from_value(variable).is_some_and(|value| self.matches.eval(value.borrow()))
     }
 
     fn find_case(&self, expected: bool, variable: &TracedValue) -> Option<Case<'_>> {
@@ -273,6 +409,111 @@ where
     }
 }
 
+/// Creates a predicate comparing two fields of the same [`CapturedSpan`] or [`CapturedEvent`].
+/// The predicate is true if the value of the `lhs` field relates to the value of the `rhs` field
+/// as specified by `ordering`.
+///
+/// Numeric values (signed / unsigned integers and floats) are compared after a lossy
+/// conversion to `f64`; thus, fields of different numeric types can be compared.
+/// If any of the fields is missing or is not numeric, or if the values are incomparable
+/// (e.g., one of them is NaN), the predicate is false.
+///
+/// [`CapturedSpan`]: crate::CapturedSpan
+///
+/// # Examples
+///
+/// ```
+/// # use std::cmp::Ordering;
+/// # use tracing_subscriber::{layer::SubscriberExt, Registry};
+/// # use tracing_capture::{predicates::{field_cmp, ScanExt}, CaptureLayer, SharedStorage};
+/// let storage = SharedStorage::default();
+/// let subscriber = Registry::default().with(CaptureLayer::new(&storage));
+/// tracing::subscriber::with_default(subscriber, || {
+///     tracing::info!(start = 3_u64, end = 5_i64, "range");
+/// });
+///
+/// let storage = storage.lock();
+/// let _ = storage
+///     .scan_events()
+///     .single(&field_cmp("end", Ordering::Greater, "start"));
+/// ```
+pub fn field_cmp(lhs: &'static str, ordering: Ordering, rhs: &'static str) -> FieldCmpPredicate {
+    FieldCmpPredicate { lhs, ordering, rhs }
+}
+
+/// Predicate comparing two fields of a [`CapturedSpan`] or [`CapturedEvent`] returned by
+/// the [`field_cmp()`] function.
+///
+/// [`CapturedSpan`]: crate::CapturedSpan
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FieldCmpPredicate {
+    lhs: &'static str,
+    ordering: Ordering,
+    rhs: &'static str,
+}
+
+impl_bool_ops!(FieldCmpPredicate);
+
+impl FieldCmpPredicate {
+    fn as_number(value: &TracedValue) -> Option<f64> {
+        #[allow(clippy::cast_precision_loss)] // lossy conversion is documented
+        match value {
+            TracedValue::Int(value) => Some(*value as f64),
+            TracedValue::UInt(value) => Some(*value as f64),
+            TracedValue::Float(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    fn compare(&self, lhs: Option<&TracedValue>, rhs: Option<&TracedValue>) -> bool {
+        let lhs = lhs.and_then(Self::as_number);
+        let rhs = rhs.and_then(Self::as_number);
+        if let (Some(lhs), Some(rhs)) = (lhs, rhs) {
+            lhs.partial_cmp(&rhs) == Some(self.ordering)
+        } else {
+            false
+        }
+    }
+}
+
+impl fmt::Display for FieldCmpPredicate {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let op = match self.ordering {
+            Ordering::Less => "<",
+            Ordering::Equal => "==",
+            Ordering::Greater => ">",
+        };
+        write!(formatter, "fields.{} {op} fields.{}", self.lhs, self.rhs)
+    }
+}
+
+impl PredicateReflection for FieldCmpPredicate {}
+
+impl<'a, T: Captured<'a>> Predicate<T> for FieldCmpPredicate {
+    fn eval(&self, variable: &T) -> bool {
+        self.compare(variable.value(self.lhs), variable.value(self.rhs))
+    }
+
+    fn find_case(&self, expected: bool, variable: &T) -> Option<Case<'_>> {
+        let lhs = variable.value(self.lhs);
+        let rhs = variable.value(self.rhs);
+        if self.compare(lhs, rhs) != expected {
+            return None;
+        }
+
+        let format_value = |value: Option<&TracedValue>| {
+            value.map_or_else(|| "None".to_owned(), |value| format!("{value:?}"))
+        };
+        let lhs = Product::new(format!("fields.{}", self.lhs), format_value(lhs));
+        let rhs = Product::new(format!("fields.{}", self.rhs), format_value(rhs));
+        Some(
+            Case::new(Some(self), expected)
+                .add_product(lhs)
+                .add_product(rhs),
+        )
+    }
+}
+
 /// Creates a predicate for the message of a [`CapturedEvent`].
 ///
 /// # Arguments
@@ -323,7 +564,7 @@ impl<P: Predicate<str>> Predicate<CapturedEvent<'_>> for MessagePredicate<P> {
     fn eval(&self, variable: &CapturedEvent<'_>) -> bool {
         variable
             .message()
-            .map_or(false, |value| self.matches.eval(value))
+            .is_some_and(|value| self.matches.eval(value))
     }
 
     fn find_case(&self, expected: bool, variable: &CapturedEvent<'_>) -> Option<Case<'_>> {
@@ -1,12 +1,12 @@
 //! `level()` predicate factory.
 
 use predicates::{
-    reflection::{Case, PredicateReflection},
+    reflection::{Case, PredicateReflection, Product},
     Predicate,
 };
 use tracing_core::{Level, LevelFilter};
 
-use std::fmt;
+use std::{fmt, ops::RangeInclusive};
 
 use crate::Captured;
 
@@ -44,6 +44,48 @@ impl IntoLevelPredicate for LevelFilter {
     }
 }
 
+impl IntoLevelPredicate for RangeInclusive<Level> {
+    type Predicate = LevelRangePredicate;
+
+    fn into_predicate(self) -> Self::Predicate {
+        let (start, end) = self.into_inner();
+        LevelRangePredicate {
+            min: start.min(end),
+            max: start.max(end),
+        }
+    }
+}
+
+#[doc(hidden)] // implementation detail (yet?)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LevelRangePredicate {
+    min: Level,
+    max: Level,
+}
+
+impl fmt::Display for LevelRangePredicate {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(formatter, "var in {}..={}", self.min, self.max)
+    }
+}
+
+impl PredicateReflection for LevelRangePredicate {}
+
+impl Predicate<Level> for LevelRangePredicate {
+    fn eval(&self, variable: &Level) -> bool {
+        (self.min..=self.max).contains(variable)
+    }
+
+    fn find_case(&self, expected: bool, variable: &Level) -> Option<Case<'_>> {
+        if self.eval(variable) == expected {
+            let product = Product::new("var", variable.to_string());
+            Some(Case::new(Some(self), expected).add_product(product))
+        } else {
+            None
+        }
+    }
+}
+
 /// Creates a predicate for the [`Level`] of a [`CapturedSpan`] or [`CapturedEvent`].
 ///
 /// # Arguments
@@ -52,6 +94,7 @@ impl IntoLevelPredicate for LevelFilter {
 ///
 /// - [`Level`]: will be compared exactly
 /// - [`LevelFilter`]: will be compared as per ordinary rules
+/// - Inclusive range of [`Level`]s: see [`level_range()`]
 /// - Any `Predicate` for [`Level`]. To bypass Rust orphaning rules, the predicate
 ///   must be enclosed in square brackets (i.e., a one-value array).
 ///
@@ -86,8 +129,37 @@ pub fn level<P: IntoLevelPredicate>(matches: P) -> LevelPredicate<P::Predicate>
     }
 }
 
+/// Creates a predicate checking that the [`Level`] of a [`CapturedSpan`] or [`CapturedEvent`]
+/// is within the specified inclusive range. The order of range bounds does not matter;
+/// e.g., `Level::WARN..=Level::ERROR` and `Level::ERROR..=Level::WARN` are equivalent
+/// (recall that in `tracing`, more verbose levels compare as greater ones).
+///
+/// [`CapturedSpan`]: crate::CapturedSpan
+/// [`CapturedEvent`]: crate::CapturedEvent
+///
+/// # Examples
+///
+/// ```
+/// # use tracing_core::Level;
+/// # use tracing_subscriber::{layer::SubscriberExt, Registry};
+/// # use tracing_capture::{predicates::{level_range, ScanExt}, CaptureLayer, SharedStorage};
+/// let storage = SharedStorage::default();
+/// let subscriber = Registry::default().with(CaptureLayer::new(&storage));
+/// tracing::subscriber::with_default(subscriber, || {
+///     tracing::info!("started");
+///     tracing::warn!("retrying");
+/// });
+///
+/// let storage = storage.lock();
+/// let event = storage.scan_events().single(&level_range(Level::WARN..=Level::ERROR));
+/// assert_eq!(event.message(), Some("retrying"));
+/// ```
+pub fn level_range(range: RangeInclusive<Level>) -> LevelPredicate<LevelRangePredicate> {
+    level(range)
+}
+
 /// Predicate for the [`Level`] of a [`CapturedSpan`] or [`CapturedEvent`] returned by
-/// the [`level()`] function.
+/// the [`level()`] and [`level_range()`] functions.
 ///
 /// [`CapturedSpan`]: crate::CapturedSpan
 /// [`CapturedEvent`]: crate::CapturedEvent
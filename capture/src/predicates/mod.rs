@@ -5,12 +5,20 @@
 //! A predicate can be created with the functions from this module:
 //!
 //! - [`level()`] checks the span / event level
+//! - [`level_range()`] checks that the span / event level is within a range
 //! - [`name()`] checks the span name
 //! - [`target()`] checks the span / event target
+//! - [`module_path()`] checks the span / event module path
 //! - [`field()`] checks a specific span / event field
+//! - [`field_cmp()`] compares two fields of the same span / event
+//! - [`field_approx()`] checks that a numeric field is approximately equal to a value
+//! - [`field_in()`] checks that a field is equal to one of the specified values
 //! - [`message()`] checks the event message
+//! - [`event_count()`] and [`subtree_event_count()`] check the number of events in a span
+//! - [`entered()`], [`exited()`] and [`is_closed()`] check the span [stats](crate::SpanStats)
 //! - [`parent()`] checks the direct parent span of an event / span
 //! - [`ancestor()`] checks the ancestor spans of an event / span
+//! - [`ancestor_within()`] checks the ancestor spans within the specified depth
 //!
 //! These predicates can be combined with bitwise operators, `&` and `|`.
 //! The [`ScanExt`] trait may be used to simplify assertions with predicates. The remaining
@@ -46,25 +54,32 @@ use predicates::Predicate;
 
 #[macro_use]
 mod combinators;
+mod count;
 mod ext;
 mod field;
 mod level;
+mod module_path;
 mod name;
 mod parent;
+mod stats;
 mod target;
 
 #[cfg(test)]
 mod tests;
 
 pub use self::{
-    combinators::{And, Or},
+    combinators::{all_of, any_of, AllOf, And, AnyOf, Or},
+    count::{event_count, subtree_event_count, EventCountPredicate},
     ext::{ScanExt, Scanner},
     field::{
-        field, message, value, FieldPredicate, IntoFieldPredicate, MessagePredicate, ValuePredicate,
+        field, field_approx, field_cmp, field_in, message, value, FieldCmpPredicate,
+        FieldPredicate, IntoFieldPredicate, MessagePredicate, ValuePredicate,
     },
-    level::{level, IntoLevelPredicate, LevelPredicate},
+    level::{level, level_range, IntoLevelPredicate, LevelPredicate},
+    module_path::{module_path, IntoModulePathPredicate, ModulePathPredicate},
     name::{name, NamePredicate},
-    parent::{ancestor, parent, AncestorPredicate, ParentPredicate},
+    parent::{ancestor, ancestor_within, parent, AncestorPredicate, ParentPredicate},
+    stats::{entered, exited, is_closed, IsClosedPredicate, SpanStatsPredicate},
     target::{target, IntoTargetPredicate, TargetPredicate},
 };
 
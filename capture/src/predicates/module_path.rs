@@ -0,0 +1,149 @@
+//! `module_path()` predicate factory.
+
+use predicates::{
+    reflection::{Case, PredicateReflection, Product},
+    Predicate,
+};
+
+use std::fmt;
+
+use crate::Captured;
+
+/// Conversion into a predicate for the module path used in the [`module_path()`] function.
+pub trait IntoModulePathPredicate {
+    /// Predicate output of the conversion. The exact type should be considered an implementation
+    /// detail and should not be relied upon.
+    type Predicate: Predicate<str>;
+    /// Performs the conversion.
+    fn into_predicate(self) -> Self::Predicate;
+}
+
+impl<P: Predicate<str>> IntoModulePathPredicate for [P; 1] {
+    type Predicate = P;
+
+    fn into_predicate(self) -> Self::Predicate {
+        self.into_iter().next().unwrap()
+    }
+}
+
+impl<'a> IntoModulePathPredicate for &'a str {
+    type Predicate = ModulePathStrPredicate<'a>;
+
+    fn into_predicate(self) -> Self::Predicate {
+        ModulePathStrPredicate { prefix: self }
+    }
+}
+
+#[doc(hidden)] // implementation detail (yet?)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ModulePathStrPredicate<'a> {
+    prefix: &'a str,
+}
+
+impl fmt::Display for ModulePathStrPredicate<'_> {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(formatter, "module_path ^= {}", self.prefix)
+    }
+}
+
+impl PredicateReflection for ModulePathStrPredicate<'_> {}
+
+impl Predicate<str> for ModulePathStrPredicate<'_> {
+    fn eval(&self, variable: &str) -> bool {
+        variable
+            .strip_prefix(self.prefix)
+            .is_some_and(|stripped| stripped.is_empty() || stripped.starts_with("::"))
+    }
+
+    fn find_case(&self, expected: bool, variable: &str) -> Option<Case<'_>> {
+        if self.eval(variable) == expected {
+            let product = Product::new("module_path", variable.to_owned());
+            Some(Case::new(Some(self), expected).add_product(product))
+        } else {
+            None
+        }
+    }
+}
+
+/// Creates a predicate for the module path of a [`CapturedSpan`] or [`CapturedEvent`].
+/// Spans / events without a module path never match the predicate.
+///
+/// # Arguments
+///
+/// The argument of this function may be:
+///
+/// - `&str`: will be compared similarly to [`target()`](crate::predicates::target()).
+///   E.g., `module_path("app::db")` will match `app::db` and `app::db::pool` module paths,
+///   but not `app::dbx`.
+/// - Any `str` `Predicate`. To bypass Rust orphaning rules, the predicate
+///   must be enclosed in square brackets (i.e., a one-value array).
+///
+/// [`CapturedSpan`]: crate::CapturedSpan
+/// [`CapturedEvent`]: crate::CapturedEvent
+///
+/// # Examples
+///
+/// ```
+/// # use predicates::str::ends_with;
+/// # use tracing_subscriber::{layer::SubscriberExt, Registry};
+/// # use tracing_capture::{predicates::{module_path, ScanExt}, CaptureLayer, SharedStorage};
+/// mod db {
+///     pub fn connect() {
+///         tracing::info!("connected");
+///     }
+/// }
+///
+/// let storage = SharedStorage::default();
+/// let subscriber = Registry::default().with(CaptureLayer::new(&storage));
+/// tracing::subscriber::with_default(subscriber, db::connect);
+///
+/// let storage = storage.lock();
+/// let _ = storage.scan_events().single(&module_path([ends_with("::db")]));
+/// ```
+pub fn module_path<P: IntoModulePathPredicate>(matches: P) -> ModulePathPredicate<P::Predicate> {
+    ModulePathPredicate {
+        matches: matches.into_predicate(),
+    }
+}
+
+/// Predicate for the module path of a [`CapturedSpan`] or [`CapturedEvent`] returned by
+/// the [`module_path()`] function.
+///
+/// [`CapturedSpan`]: crate::CapturedSpan
+/// [`CapturedEvent`]: crate::CapturedEvent
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ModulePathPredicate<P> {
+    matches: P,
+}
+
+impl_bool_ops!(ModulePathPredicate<P>);
+
+impl<P: Predicate<str>> fmt::Display for ModulePathPredicate<P> {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(formatter, "module_path({})", self.matches)
+    }
+}
+
+impl<P: Predicate<str>> PredicateReflection for ModulePathPredicate<P> {}
+
+impl<'a, P: Predicate<str>, T: Captured<'a>> Predicate<T> for ModulePathPredicate<P> {
+    fn eval(&self, variable: &T) -> bool {
+        variable
+            .metadata()
+            .module_path()
+            .is_some_and(|path| self.matches.eval(path))
+    }
+
+    fn find_case(&self, expected: bool, variable: &T) -> Option<Case<'_>> {
+        let Some(path) = variable.metadata().module_path() else {
+            return if expected {
+                None // was expecting a module path, but there is none
+            } else {
+                let product = Product::new("module_path", "None");
+                Some(Case::new(Some(self), expected).add_product(product))
+            };
+        };
+        let child = self.matches.find_case(expected, path)?;
+        Some(Case::new(Some(self), expected).add_child(child))
+    }
+}
@@ -68,7 +68,7 @@ where
 {
     fn eval(&self, variable: &T) -> bool {
         let parent = variable.parent();
-        parent.map_or(false, |parent| self.matches.eval(&parent))
+        parent.is_some_and(|parent| self.matches.eval(&parent))
     }
 
     fn find_case(&self, expected: bool, variable: &T) -> Option<Case<'_>> {
@@ -115,16 +115,67 @@ pub fn ancestor<P>(matches: P) -> AncestorPredicate<P>
 where
     P: for<'a> Predicate<CapturedSpan<'a>>,
 {
-    AncestorPredicate { matches }
+    AncestorPredicate {
+        matches,
+        max_depth: None,
+    }
+}
+
+/// Creates a predicate for ancestor [`CapturedSpan`]s of a span or a [`CapturedEvent`]
+/// located within `depth` levels from it. That is, depth 1 corresponds to the [parent](parent()),
+/// depth 2 to the parent and grandparent, etc. Otherwise, the predicate works
+/// the same as [`ancestor()`].
+///
+/// [`CapturedEvent`]: crate::CapturedEvent
+///
+/// # Examples
+///
+/// ```
+/// # use predicates::ord::eq;
+/// # use tracing_subscriber::{layer::SubscriberExt, Registry};
+/// # use tracing_capture::{predicates::*, CaptureLayer, SharedStorage};
+/// let storage = SharedStorage::default();
+/// let subscriber = Registry::default().with(CaptureLayer::new(&storage));
+/// tracing::subscriber::with_default(subscriber, || {
+///     let _entered = tracing::info_span!("wrapper").entered();
+///     tracing::info_span!("compute").in_scope(|| {
+///         tracing::info!(answer = 42, "done");
+///     });
+/// });
+///
+/// let storage = storage.lock();
+/// let events = storage.scan_events();
+/// let _ = events.single(&ancestor_within(2, name(eq("wrapper"))));
+/// events.none(&ancestor_within(1, name(eq("wrapper"))));
+/// ```
+pub fn ancestor_within<P>(depth: usize, matches: P) -> AncestorPredicate<P>
+where
+    P: for<'a> Predicate<CapturedSpan<'a>>,
+{
+    AncestorPredicate {
+        matches,
+        max_depth: Some(depth),
+    }
 }
 
 /// Predicate for the ancestors of a [`CapturedSpan`] or [`CapturedEvent`] returned
-/// by the [`ancestor()`] function.
+/// by the [`ancestor()`] and [`ancestor_within()`] functions.
 ///
 /// [`CapturedEvent`]: crate::CapturedEvent
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct AncestorPredicate<P> {
     matches: P,
+    max_depth: Option<usize>,
+}
+
+impl<P> AncestorPredicate<P> {
+    fn ancestors<'a, T: Captured<'a>>(
+        &self,
+        variable: &T,
+    ) -> impl Iterator<Item = CapturedSpan<'a>> {
+        let ancestors = iter::successors(variable.parent(), CapturedSpan::parent);
+        ancestors.take(self.max_depth.unwrap_or(usize::MAX))
+    }
 }
 
 impl_bool_ops!(AncestorPredicate<P>);
@@ -134,7 +185,11 @@ where
     P: for<'a> Predicate<CapturedSpan<'a>>,
 {
     fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(formatter, "ancestor({})", self.matches)
+        if let Some(depth) = self.max_depth {
+            write!(formatter, "ancestor_within({depth}, {})", self.matches)
+        } else {
+            write!(formatter, "ancestor({})", self.matches)
+        }
     }
 }
 
@@ -146,12 +201,12 @@ where
     P: for<'p> Predicate<CapturedSpan<'p>>,
 {
     fn eval(&self, variable: &T) -> bool {
-        let mut ancestors = iter::successors(variable.parent(), CapturedSpan::parent);
+        let mut ancestors = self.ancestors(variable);
         ancestors.any(|span| self.matches.eval(&span))
     }
 
     fn find_case(&self, expected: bool, variable: &T) -> Option<Case<'_>> {
-        let mut ancestors = iter::successors(variable.parent(), CapturedSpan::parent);
+        let mut ancestors = self.ancestors(variable);
         if expected {
             // Return the first of ancestor cases.
             let child = ancestors.find_map(|span| self.matches.find_case(expected, &span))?;
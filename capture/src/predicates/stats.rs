@@ -0,0 +1,161 @@
+//! `entered()`, `exited()` and `is_closed()` predicate factories.
+
+use predicates::{
+    reflection::{Case, PredicateReflection, Product},
+    Predicate,
+};
+
+use std::fmt;
+
+use crate::CapturedSpan;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum StatKind {
+    Entered,
+    Exited,
+}
+
+impl StatKind {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Entered => "entered",
+            Self::Exited => "exited",
+        }
+    }
+
+    fn get(self, span: &CapturedSpan<'_>) -> usize {
+        let stats = span.stats();
+        match self {
+            Self::Entered => stats.entered,
+            Self::Exited => stats.exited,
+        }
+    }
+}
+
+/// Creates a predicate for the number of times a [`CapturedSpan`] was
+/// [entered](crate::SpanStats::entered).
+///
+/// # Arguments
+///
+/// The argument of this function can be any `usize` predicate, e.g. `eq(1)`.
+///
+/// # Examples
+///
+/// ```
+/// # use predicates::ord::{eq, gt};
+/// # use tracing_subscriber::{layer::SubscriberExt, Registry};
+/// # use tracing_capture::{
+/// #     predicates::{entered, exited, is_closed, name, ScanExt}, CaptureLayer, SharedStorage,
+/// # };
+/// let storage = SharedStorage::default();
+/// let subscriber = Registry::default().with(CaptureLayer::new(&storage));
+/// tracing::subscriber::with_default(subscriber, || {
+///     let span = tracing::info_span!("compute");
+///     for _ in 0..3 {
+///         span.in_scope(|| tracing::info!("working"));
+///     }
+/// });
+///
+/// let storage = storage.lock();
+/// let predicate = name(eq("compute")) & entered(eq(3)) & exited(gt(2)) & is_closed();
+/// let _ = storage.scan_spans().single(&predicate);
+/// ```
+pub fn entered<P: Predicate<usize>>(matches: P) -> SpanStatsPredicate<P> {
+    SpanStatsPredicate {
+        matches,
+        kind: StatKind::Entered,
+    }
+}
+
+/// Creates a predicate for the number of times a [`CapturedSpan`] was
+/// [exited](crate::SpanStats::exited).
+///
+/// # Arguments
+///
+/// The argument of this function can be any `usize` predicate, e.g. `eq(1)`.
+///
+/// # Examples
+///
+/// See [`entered()`] for an example of usage.
+pub fn exited<P: Predicate<usize>>(matches: P) -> SpanStatsPredicate<P> {
+    SpanStatsPredicate {
+        matches,
+        kind: StatKind::Exited,
+    }
+}
+
+/// Predicate for the [stats](crate::SpanStats) of a [`CapturedSpan`] returned by
+/// the [`entered()`] and [`exited()`] functions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SpanStatsPredicate<P> {
+    matches: P,
+    kind: StatKind,
+}
+
+impl_bool_ops!(SpanStatsPredicate<P>);
+
+impl<P: Predicate<usize>> fmt::Display for SpanStatsPredicate<P> {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(formatter, "{}({})", self.kind.as_str(), self.matches)
+    }
+}
+
+impl<P: Predicate<usize>> PredicateReflection for SpanStatsPredicate<P> {}
+
+impl<P: Predicate<usize>> Predicate<CapturedSpan<'_>> for SpanStatsPredicate<P> {
+    fn eval(&self, variable: &CapturedSpan<'_>) -> bool {
+        self.matches.eval(&self.kind.get(variable))
+    }
+
+    fn find_case(&self, expected: bool, variable: &CapturedSpan<'_>) -> Option<Case<'_>> {
+        let value = self.kind.get(variable);
+        let child = self.matches.find_case(expected, &value)?;
+        let product = Product::new(self.kind.as_str(), value);
+        Some(
+            Case::new(Some(self), expected)
+                .add_child(child)
+                .add_product(product),
+        )
+    }
+}
+
+/// Creates a predicate checking whether a [`CapturedSpan`] is
+/// [closed](crate::SpanStats::is_closed).
+///
+/// # Examples
+///
+/// See [`entered()`] for an example of usage.
+pub fn is_closed() -> IsClosedPredicate {
+    IsClosedPredicate(())
+}
+
+/// Predicate checking whether a [`CapturedSpan`] is closed returned by the [`is_closed()`]
+/// function.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IsClosedPredicate(());
+
+impl_bool_ops!(IsClosedPredicate);
+
+impl fmt::Display for IsClosedPredicate {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter.write_str("is_closed()")
+    }
+}
+
+impl PredicateReflection for IsClosedPredicate {}
+
+impl Predicate<CapturedSpan<'_>> for IsClosedPredicate {
+    fn eval(&self, variable: &CapturedSpan<'_>) -> bool {
+        variable.stats().is_closed
+    }
+
+    fn find_case(&self, expected: bool, variable: &CapturedSpan<'_>) -> Option<Case<'_>> {
+        let is_closed = variable.stats().is_closed;
+        if is_closed == expected {
+            let product = Product::new("is_closed", is_closed);
+            Some(Case::new(Some(self), expected).add_product(product))
+        } else {
+            None
+        }
+    }
+}
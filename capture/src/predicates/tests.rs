@@ -11,8 +11,10 @@ use tracing_core::{
     callsite::DefaultCallsite, field::FieldSet, Kind, Level, LevelFilter, Metadata,
 };
 
+use std::cmp::Ordering;
+
 use super::*;
-use crate::Storage;
+use crate::{Captured, Storage};
 use tracing_tunnel::{TracedValue, TracedValues};
 
 static SITE: DefaultCallsite = DefaultCallsite::new(METADATA);
@@ -60,6 +62,25 @@ fn level_predicates() {
     assert!(!predicate.eval(&span));
 }
 
+#[test]
+fn level_range_predicates() {
+    let mut storage = Storage::new();
+    let span_id = storage.push_span(METADATA, TracedValues::new(), None);
+    let span = storage.span(span_id);
+
+    let predicate = level_range(Level::WARN..=Level::ERROR);
+    assert_eq!(predicate.to_string(), "level(var in ERROR..=WARN)");
+    assert!(!predicate.eval(&span));
+    let predicate = level_range(Level::ERROR..=Level::INFO);
+    assert!(predicate.eval(&span));
+
+    let level_predicate = (Level::WARN..=Level::ERROR).into_predicate();
+    assert!(level_predicate.eval(&Level::ERROR));
+    assert!(level_predicate.eval(&Level::WARN));
+    assert!(!level_predicate.eval(&Level::INFO));
+    assert!(!level_predicate.eval(&Level::TRACE));
+}
+
 #[test]
 fn target_predicates() {
     let mut storage = Storage::new();
@@ -76,6 +97,41 @@ fn target_predicates() {
     assert!(!predicate.eval(&span));
 }
 
+#[test]
+fn module_path_predicates() {
+    static NO_MODULE_METADATA: &Metadata<'static> = &Metadata::new(
+        "test_span",
+        "tracing_capture::predicate",
+        Level::INFO,
+        None,
+        None,
+        None,
+        FieldSet::new(&[], tracing_core::identify_callsite!(&SITE)),
+        Kind::SPAN,
+    );
+
+    let mut storage = Storage::new();
+    let span_id = storage.push_span(METADATA, TracedValues::new(), None);
+    let span = storage.span(span_id);
+    assert_eq!(span.module_path(), Some("predicate"));
+
+    let predicate = module_path("predicate");
+    assert!(predicate.eval(&span));
+    let predicate = module_path("pred");
+    assert!(!predicate.eval(&span));
+    let predicate = module_path([starts_with("pred")]);
+    assert!(predicate.eval(&span));
+
+    let span_id = storage.push_span(NO_MODULE_METADATA, TracedValues::new(), None);
+    let span = storage.span(span_id);
+    assert_eq!(span.module_path(), None);
+    assert!(!predicate.eval(&span));
+    let case = predicate.find_case(false, &span).unwrap();
+    let products: Vec<_> = case.products().map(ToString::to_string).collect();
+    assert_eq!(products, ["module_path: None"]);
+    assert!(predicate.find_case(true, &span).is_none());
+}
+
 #[test]
 fn name_predicates() {
     let mut storage = Storage::new();
@@ -225,3 +281,214 @@ fn using_extensions() {
     let event = scanner.first(&field("val", value(gt(2_i64))));
     assert_eq!(event["val"], 3_i64);
 }
+
+fn storage_with_events() -> Storage {
+    let mut storage = Storage::new();
+    for val in 0_i64..3 {
+        let values = TracedValues::from_iter([("val", val.into())]);
+        storage.push_event(EVENT_METADATA, values, None);
+    }
+    storage
+}
+
+#[test]
+fn at_most_one_with_zero_or_one_match() {
+    let storage = storage_with_events();
+    let scanner = storage.scan_events();
+
+    assert!(scanner.at_most_one(&field("val", 5_i64)).is_none());
+    let event = scanner.at_most_one(&field("val", 1_i64)).unwrap();
+    assert_eq!(event["val"], 1_i64);
+}
+
+#[test]
+#[should_panic(expected = "multiple items match predicate")]
+fn at_most_one_with_multiple_matches() {
+    let storage = storage_with_events();
+    storage
+        .scan_events()
+        .at_most_one(&field("val", value(gt(0_i64))));
+}
+
+#[test]
+fn dynamic_compound_predicates() {
+    let mut storage = Storage::new();
+    for val in 0_i64..5 {
+        let values = TracedValues::from_iter([("val", val.into())]);
+        storage.push_event(EVENT_METADATA, values, None);
+    }
+    let scanner = storage.scan_events();
+
+    let mut predicates = vec![];
+    for bound in [1_i64, 2, 3] {
+        predicates.push(field("val", value(gt(bound))));
+    }
+    let predicate = all_of(predicates.clone());
+    assert_eq!(
+        predicate.to_string(),
+        "(fields.val(is<i64>(var > 1)) && fields.val(is<i64>(var > 2)) && \
+         fields.val(is<i64>(var > 3)))"
+    );
+    let event = scanner.single(&predicate);
+    assert_eq!(event["val"], 4_i64);
+
+    let event = scanner.first(&(all_of(predicates.clone()) | field("val", 0_i64)));
+    assert_eq!(event["val"], 0_i64);
+
+    let predicate = any_of(predicates);
+    let event = scanner.first(&predicate);
+    assert_eq!(event["val"], 2_i64);
+    let event = storage.event(storage.events.iter().next().unwrap().0);
+    let case = predicate.find_case(false, &event).unwrap();
+    assert_eq!(case.children().count(), 3);
+
+    let predicate = any_of((0_i64..3).map(|val| field("val", val)));
+    assert_eq!(storage.all_events().filter(into_fn(predicate)).count(), 3);
+    let no_predicates: [LevelPredicate<predicates::ord::EqPredicate<Level>>; 0] = [];
+    scanner.all(&all_of(no_predicates));
+    scanner.none(&any_of(no_predicates));
+}
+
+fn describe<'a>(captured: &impl Captured<'a>) -> String {
+    let kind = if captured.is_span() { "span" } else { "event" };
+    format!("{kind} {}", captured.metadata().name())
+}
+
+#[test]
+fn distinguishing_spans_and_events_generically() {
+    let mut storage = Storage::new();
+    let span_id = storage.push_span(METADATA, TracedValues::new(), None);
+    let event_id = storage.push_event(EVENT_METADATA, TracedValues::new(), Some(span_id));
+    let span = storage.span(span_id);
+    let event = storage.event(event_id);
+
+    assert!(span.is_span());
+    assert!(!event.is_span());
+    assert_eq!(describe(&span), "span test_span");
+    assert_eq!(
+        describe(&event),
+        "event event at tracing_capture/predicates.rs:42"
+    );
+}
+
+#[test]
+fn comparing_fields() {
+    let mut storage = Storage::new();
+    let ranges: [(TracedValue, TracedValue); 4] = [
+        (1_i64.into(), 5_u64.into()),
+        (3_u64.into(), 3.0.into()),
+        (7.5.into(), 2_i64.into()),
+        ("1".into(), 5_i64.into()),
+    ];
+    for (start, end) in ranges {
+        let values = TracedValues::from_iter([("start", start), ("end", end)]);
+        storage.push_event(EVENT_METADATA, values, None);
+    }
+    let scanner = storage.scan_events();
+
+    let event = scanner.single(&field_cmp("end", Ordering::Greater, "start"));
+    assert_eq!(event["end"], 5_u64);
+    let event = scanner.single(&field_cmp("end", Ordering::Equal, "start"));
+    assert_eq!(event["start"], 3_u64);
+    let event = scanner.single(&field_cmp("end", Ordering::Less, "start"));
+    assert_eq!(event["end"], 2_i64);
+    scanner.none(&field_cmp("end", Ordering::Greater, "missing"));
+
+    let predicate = field_cmp("end", Ordering::Greater, "start");
+    assert_eq!(predicate.to_string(), "fields.end > fields.start");
+    let event = storage.all_events().next_back().unwrap();
+    let case = predicate.find_case(false, &event).unwrap();
+    let products: Vec<_> = case
+        .products()
+        .map(|product| (product.name(), product.value().to_string()))
+        .collect();
+    assert_eq!(products.len(), 2);
+    assert_eq!(products[1], ("fields.start", "String(\"1\")".to_owned()));
+}
+
+#[test]
+fn approximate_field_predicates() {
+    let mut storage = Storage::new();
+    for ratio in [TracedValue::from(0.3333), 1_u64.into(), "0.333".into()] {
+        let values = TracedValues::from_iter([("ratio", ratio)]);
+        storage.push_event(EVENT_METADATA, values, None);
+    }
+    let scanner = storage.scan_events();
+
+    let predicate = field_approx("ratio", 0.333, 1e-3);
+    assert_eq!(
+        predicate.to_string(),
+        "fields.ratio(var ~= 0.333 (eps = 0.001))"
+    );
+    let event = scanner.single(&predicate);
+    assert_eq!(event["ratio"], 0.3333);
+    scanner.none(&field_approx("ratio", 0.333, 1e-5));
+    let event = scanner.single(&field_approx("ratio", 1.05, 0.1));
+    assert_eq!(event["ratio"], 1_u64);
+    scanner.none(&field_approx("missing", 0.0, f64::INFINITY));
+}
+
+#[test]
+fn field_in_set_predicates() {
+    let mut storage = Storage::new();
+    for status in [
+        TracedValue::from(200_u64),
+        404_u64.into(),
+        204_i64.into(),
+        "301".into(),
+    ] {
+        let values = TracedValues::from_iter([("status", status)]);
+        storage.push_event(EVENT_METADATA, values, None);
+    }
+    let scanner = storage.scan_events();
+
+    let predicate = field_in("status", [200_u64, 204, 301]);
+    assert_eq!(
+        predicate.to_string(),
+        "fields.status(var in [200, 204, 301])"
+    );
+    let event = scanner.single(&predicate);
+    assert_eq!(event["status"], 200_u64);
+    let event = scanner.single(&field_in("status", [204_i64, 301]));
+    assert_eq!(event["status"], 204_i64);
+    let event = scanner.single(&field_in("status", [404_u64, 500]));
+    assert_eq!(event["status"], 404_u64);
+
+    scanner.none(&field_in("status", [201_u64, 302, 500]));
+    scanner.none(&field_in("status", Vec::<u64>::new()));
+    scanner.none(&field_in("missing", [200_u64]));
+
+    let event = storage.all_events().nth(1).unwrap();
+    let case = predicate.find_case(false, &event).unwrap();
+    let child = case.children().next().unwrap();
+    let product = child.products().next().unwrap();
+    assert_eq!(product.value().to_string(), "UInt(404)");
+}
+
+#[test]
+fn ancestor_predicates_with_depth() {
+    let mut storage = Storage::new();
+    let mut parent_id = None;
+    for val in 0_i64..3 {
+        let values = TracedValues::from_iter([("val", val.into())]);
+        parent_id = Some(storage.push_span(METADATA, values, parent_id));
+    }
+    storage.push_event(EVENT_METADATA, TracedValues::new(), parent_id);
+    let event = storage.all_events().next().unwrap();
+
+    assert!(ancestor_within(1, field("val", 2_i64)).eval(&event));
+    assert!(!ancestor_within(1, field("val", 1_i64)).eval(&event));
+    assert!(ancestor_within(2, field("val", 1_i64)).eval(&event));
+    assert!(!ancestor_within(2, field("val", 0_i64)).eval(&event));
+    assert!(ancestor_within(3, field("val", 0_i64)).eval(&event));
+    assert!(ancestor(field("val", 0_i64)).eval(&event));
+    assert!(!ancestor_within(0, always()).eval(&event));
+
+    let predicate = ancestor_within(2, field("val", 0_i64));
+    assert_eq!(
+        predicate.to_string(),
+        "ancestor_within(2, fields.val(var == 0))"
+    );
+    let case = predicate.find_case(false, &event).unwrap();
+    assert_eq!(case.children().count(), 2);
+}
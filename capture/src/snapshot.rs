@@ -0,0 +1,466 @@
+//! Serializable snapshots of captured tracing data.
+
+use serde::{Deserialize, Serialize};
+use tracing_core::{field::FieldSet, Callsite, Interest, Kind, Level, Metadata};
+
+use std::{
+    borrow::Cow,
+    collections::{hash_map::DefaultHasher, HashMap},
+    error, fmt,
+    hash::{Hash, Hasher},
+    sync::{Mutex, OnceLock, PoisonError},
+    time::{Duration, Instant},
+};
+
+use crate::{CapturedSpanId, SpanStats, SpanTimestamps, Storage};
+use tracing_tunnel::{CallSiteData, CallSiteKind, TracedValues, TracingLevel};
+
+/// Serializable snapshot of a [`Storage`] produced by [`Storage::snapshot()`].
+///
+/// A snapshot can be persisted (e.g., as a CI artifact) and then [restored](Self::restore())
+/// into a `Storage` to be queried offline with the same API as the live storage
+/// (including [predicates](crate::predicates)).
+///
+/// # Limitations
+///
+/// - [Timestamps](crate::SpanTimestamps) are restored relative to the instant of restoration;
+///   durations between timestamps are preserved.
+/// - Restoring a snapshot leaks the [`Metadata`] of the captured spans and events, similar to
+///   `TracingEventReceiver` from `tracing-tunnel`. Metadata is leaked once per distinct
+///   call site, so restoring the same snapshot repeatedly doesn't leak memory.
+///   Still, it's not advised to restore large numbers of snapshots with distinct call sites.
+///
+/// # Examples
+///
+/// ```
+/// # use predicates::ord::eq;
+/// # use tracing_subscriber::{layer::SubscriberExt, Registry};
+/// # use tracing_capture::{
+/// #     predicates::{name, ScanExt}, CaptureLayer, SharedStorage, StorageSnapshot,
+/// # };
+/// let storage = SharedStorage::default();
+/// let subscriber = Registry::default().with(CaptureLayer::new(&storage));
+/// tracing::subscriber::with_default(subscriber, || {
+///     tracing::info_span!("test", num = 42_i64).in_scope(|| tracing::info!("done"));
+/// });
+///
+/// let snapshot = storage.lock().snapshot();
+/// let json = serde_json::to_string(&snapshot)?;
+/// let snapshot: StorageSnapshot = serde_json::from_str(&json)?;
+/// let storage = snapshot.restore()?;
+/// let span = storage.scan_spans().single(&name(eq("test")));
+/// assert_eq!(span["num"], 42_i64);
+/// assert_eq!(span.events().len(), 1);
+/// # Ok::<_, Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(docsrs, doc(cfg(feature = "serde")))]
+pub struct StorageSnapshot {
+    call_sites: Vec<CallSiteData>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    known_call_sites: Vec<usize>,
+    spans: Vec<SpanSnapshot>,
+    events: Vec<EventSnapshot>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct SpanSnapshot {
+    seq: u64,
+    call_site: usize,
+    parent: Option<usize>,
+    #[serde(default, skip_serializing_if = "is_zero")]
+    skipped_ancestors: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    raw_id: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    raw_parent_id: Option<u64>,
+    values: TracedValues<String>,
+    #[serde(flatten)]
+    stats: SpanStats,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    timestamps: Option<TimestampsSnapshot>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    busy_durations: Vec<Duration>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    follows_from: Vec<usize>,
+}
+
+/// [`SpanTimestamps`] represented as offsets relative to the earliest span creation
+/// in the snapshot.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+struct TimestampsSnapshot {
+    created: Duration,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    first_entered: Option<Duration>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    last_entered: Option<Duration>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    last_exited: Option<Duration>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    closed: Option<Duration>,
+}
+
+impl TimestampsSnapshot {
+    fn new(timestamps: &SpanTimestamps, origin: Instant) -> Self {
+        let offset = |instant: Instant| instant.saturating_duration_since(origin);
+        Self {
+            created: offset(timestamps.created),
+            first_entered: timestamps.first_entered.map(offset),
+            last_entered: timestamps.last_entered.map(offset),
+            last_exited: timestamps.last_exited.map(offset),
+            closed: timestamps.closed.map(offset),
+        }
+    }
+
+    fn restore(&self, origin: Instant) -> SpanTimestamps {
+        SpanTimestamps {
+            created: origin + self.created,
+            first_entered: self.first_entered.map(|offset| origin + offset),
+            last_entered: self.last_entered.map(|offset| origin + offset),
+            last_exited: self.last_exited.map(|offset| origin + offset),
+            closed: self.closed.map(|offset| origin + offset),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct EventSnapshot {
+    seq: u64,
+    call_site: usize,
+    parent: Option<usize>,
+    #[serde(default, skip_serializing_if = "is_zero")]
+    skipped_ancestors: usize,
+    values: TracedValues<String>,
+}
+
+#[allow(clippy::trivially_copy_pass_by_ref)] // required by serde
+fn is_zero(value: &usize) -> bool {
+    *value == 0
+}
+
+fn owned_values(values: &TracedValues<&'static str>) -> TracedValues<String> {
+    values
+        .iter()
+        .map(|(name, value)| (name.to_owned(), value.clone()))
+        .collect()
+}
+
+#[derive(Debug, Default)]
+struct CallSites {
+    indices: HashMap<*const Metadata<'static>, usize>,
+    data: Vec<CallSiteData>,
+}
+
+impl CallSites {
+    fn index(&mut self, metadata: &'static Metadata<'static>) -> usize {
+        let data = &mut self.data;
+        *self.indices.entry(metadata as *const _).or_insert_with(|| {
+            data.push(CallSiteData::from(metadata));
+            data.len() - 1
+        })
+    }
+}
+
+impl Storage {
+    /// Creates a serializable snapshot of this storage.
+    #[cfg_attr(docsrs, doc(cfg(feature = "serde")))]
+    pub fn snapshot(&self) -> StorageSnapshot {
+        let mut call_sites = CallSites::default();
+        let known_call_sites = self
+            .known_call_sites()
+            .iter()
+            .map(|&metadata| call_sites.index(metadata))
+            .collect();
+        let origin = self
+            .spans
+            .iter()
+            .filter_map(|(_, span)| Some(span.timestamps?.created))
+            .min();
+        let span_indices: HashMap<_, _> = self
+            .spans
+            .iter()
+            .enumerate()
+            .map(|(idx, (id, _))| (id, idx))
+            .collect();
+
+        let spans = self.spans.iter().map(|(_, span)| SpanSnapshot {
+            seq: span.seq,
+            call_site: call_sites.index(span.metadata),
+            parent: span.parent_id.map(|id| span_indices[&id]),
+            skipped_ancestors: span.skipped_ancestors,
+            raw_id: span.raw_id,
+            raw_parent_id: span.raw_parent_id,
+            values: owned_values(&span.values),
+            stats: span.stats,
+            timestamps: span
+                .timestamps
+                .zip(origin)
+                .map(|(timestamps, origin)| TimestampsSnapshot::new(&timestamps, origin)),
+            busy_durations: span.busy_durations.clone(),
+            follows_from: span
+                .follows_from_ids
+                .iter()
+                .map(|id| span_indices[id])
+                .collect(),
+        });
+        let spans = spans.collect();
+
+        let events = self.events.iter().map(|(_, event)| EventSnapshot {
+            seq: event.seq,
+            call_site: call_sites.index(event.metadata),
+            parent: event.parent_id.map(|id| span_indices[&id]),
+            skipped_ancestors: event.skipped_ancestors,
+            values: owned_values(&event.values),
+        });
+        let events = events.collect();
+
+        StorageSnapshot {
+            call_sites: call_sites.data,
+            known_call_sites,
+            spans,
+            events,
+        }
+    }
+}
+
+impl StorageSnapshot {
+    /// Restores a [`Storage`] from this snapshot.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the snapshot is malformed (e.g., contains references
+    /// to non-existing spans or call sites).
+    pub fn restore(&self) -> Result<Storage, SnapshotError> {
+        let call_sites: Vec<_> = self.call_sites.iter().map(restore_metadata).collect();
+        let call_site = |index: usize, kind: CallSiteKind| {
+            let metadata: &'static Metadata<'static> = call_sites
+                .get(index)
+                .copied()
+                .ok_or(SnapshotError::UnknownCallSite(index))?;
+            if metadata.is_span() != matches!(kind, CallSiteKind::Span) {
+                return Err(SnapshotError::CallSiteKindMismatch(index));
+            }
+            Ok(metadata)
+        };
+        let origin = Instant::now();
+
+        let mut storage = Storage::new();
+        for &index in &self.known_call_sites {
+            let metadata = call_sites
+                .get(index)
+                .ok_or(SnapshotError::UnknownCallSite(index))?;
+            storage.push_call_site(metadata);
+        }
+
+        // Spans and events are restored in the capture order, so that the restored storage
+        // has the same timeline as the original one.
+        let mut span_ids = Vec::with_capacity(self.spans.len());
+        let mut spans = self.spans.iter().peekable();
+        let mut events = self.events.iter().peekable();
+        loop {
+            let next_event_seq = events.peek().map(|event| event.seq);
+            let is_span_next =
+                |span: &&SpanSnapshot| next_event_seq.map_or(true, |seq| span.seq < seq);
+            if let Some(snapshot) = spans.next_if(is_span_next) {
+                let metadata = call_site(snapshot.call_site, CallSiteKind::Span)?;
+                // Parents always precede their children in the capture order.
+                let parent_id = restore_parent(snapshot.parent, &span_ids)?;
+                let values = restore_values(metadata, &snapshot.values)?;
+                let id = storage.push_span(metadata, values, parent_id);
+                let span = &mut storage.spans[id];
+                span.skipped_ancestors = snapshot.skipped_ancestors;
+                span.raw_id = snapshot.raw_id;
+                span.raw_parent_id = snapshot.raw_parent_id;
+                span.stats = snapshot.stats;
+                span.timestamps = snapshot
+                    .timestamps
+                    .map(|timestamps| timestamps.restore(origin));
+                span.busy_durations.clone_from(&snapshot.busy_durations);
+                span_ids.push(id);
+            } else if let Some(snapshot) = events.next() {
+                let metadata = call_site(snapshot.call_site, CallSiteKind::Event)?;
+                let parent_id = restore_parent(snapshot.parent, &span_ids)?;
+                let values = restore_values(metadata, &snapshot.values)?;
+                let id = storage.push_event(metadata, values, parent_id);
+                storage.events[id].skipped_ancestors = snapshot.skipped_ancestors;
+            } else {
+                break;
+            }
+        }
+
+        for (snapshot, &id) in self.spans.iter().zip(&span_ids) {
+            for &idx in &snapshot.follows_from {
+                let follows_id = *span_ids.get(idx).ok_or(SnapshotError::UnknownSpan(idx))?;
+                storage.spans[id].follows_from_ids.push(follows_id);
+            }
+        }
+        Ok(storage)
+    }
+}
+
+/// Error restoring a [`Storage`] from a [`StorageSnapshot`].
+#[derive(Debug)]
+#[non_exhaustive]
+#[cfg_attr(docsrs, doc(cfg(feature = "serde")))]
+pub enum SnapshotError {
+    /// Reference to a call site with the specified index missing from the snapshot.
+    UnknownCallSite(usize),
+    /// Call site with the specified index has an unexpected kind (e.g., a span is created
+    /// with an event call site).
+    CallSiteKindMismatch(usize),
+    /// Reference to a span with the specified index missing from the snapshot, or (for parent
+    /// references) not preceding the referencing span.
+    UnknownSpan(usize),
+    /// Recorded value is not declared as a field of the call site.
+    UnknownField {
+        /// Name of the call site.
+        call_site: &'static str,
+        /// Name of the value.
+        name: String,
+    },
+}
+
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnknownCallSite(idx) => write!(formatter, "unknown call site: {idx}"),
+            Self::CallSiteKindMismatch(idx) => {
+                write!(formatter, "unexpected kind of call site {idx}")
+            }
+            Self::UnknownSpan(idx) => write!(formatter, "unknown span: {idx}"),
+            Self::UnknownField { call_site, name } => write!(
+                formatter,
+                "value `{name}` is not a field of call site `{call_site}`"
+            ),
+        }
+    }
+}
+
+impl error::Error for SnapshotError {}
+
+fn restore_parent(
+    parent: Option<usize>,
+    span_ids: &[CapturedSpanId],
+) -> Result<Option<CapturedSpanId>, SnapshotError> {
+    parent
+        .map(|idx| {
+            span_ids
+                .get(idx)
+                .copied()
+                .ok_or(SnapshotError::UnknownSpan(idx))
+        })
+        .transpose()
+}
+
+fn restore_values(
+    metadata: &'static Metadata<'static>,
+    values: &TracedValues<String>,
+) -> Result<TracedValues<&'static str>, SnapshotError> {
+    let fields = metadata.fields();
+    let values = values.iter().map(|(name, value)| {
+        let field = fields
+            .field(name)
+            .ok_or_else(|| SnapshotError::UnknownField {
+                call_site: metadata.name(),
+                name: name.to_owned(),
+            })?;
+        Ok((field.name(), value.clone()))
+    });
+    values.collect()
+}
+
+/// Call site for restored metadata. Such call sites are not registered in `tracing`.
+#[derive(Debug, Default)]
+struct RestoredCallSite {
+    metadata: OnceLock<&'static Metadata<'static>>,
+}
+
+impl Callsite for RestoredCallSite {
+    fn set_interest(&self, _interest: Interest) {
+        // Does nothing
+    }
+
+    fn metadata(&self) -> &Metadata<'_> {
+        self.metadata
+            .get()
+            .copied()
+            .expect("metadata not initialized")
+    }
+}
+
+/// Metadata leaked when restoring snapshots, keyed by the hash of `CallSiteData`.
+type MetadataMap = HashMap<u64, Vec<&'static Metadata<'static>>>;
+
+/// Restored metadata deduplicated by call site data, so that it's leaked once per call site.
+static RESTORED_METADATA: OnceLock<Mutex<MetadataMap>> = OnceLock::new();
+
+fn restore_metadata(data: &CallSiteData) -> &'static Metadata<'static> {
+    let mut hasher = DefaultHasher::new();
+    data.hash(&mut hasher);
+    let hash_value = hasher.finish();
+
+    let mut lock = RESTORED_METADATA
+        .get_or_init(Mutex::default)
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner);
+    let bucket = lock.entry(hash_value).or_default();
+    if let Some(metadata) = bucket.iter().find(|metadata| eq_metadata(data, metadata)) {
+        return metadata;
+    }
+    let metadata = leak_metadata(data);
+    bucket.push(metadata);
+    metadata
+}
+
+fn leak_metadata(data: &CallSiteData) -> &'static Metadata<'static> {
+    fn leak(s: &str) -> &'static str {
+        Box::leak(s.into())
+    }
+
+    let call_site: &'static RestoredCallSite = Box::leak(Box::default());
+    let call_site_id = tracing_core::identify_callsite!(call_site);
+    let fields: Box<[_]> = data.fields.iter().map(|field| leak(field)).collect();
+    let fields = FieldSet::new(Box::leak(fields), call_site_id);
+    let kind = match data.kind {
+        CallSiteKind::Span => Kind::SPAN,
+        CallSiteKind::Event => Kind::EVENT,
+    };
+    let metadata = Metadata::new(
+        leak(&data.name),
+        leak(&data.target),
+        level(data.level),
+        data.file.as_deref().map(leak),
+        data.line,
+        data.module_path.as_deref().map(leak),
+        fields,
+        kind,
+    );
+    let metadata: &'static _ = Box::leak(Box::new(metadata));
+    call_site.metadata.set(metadata).ok();
+    metadata
+}
+
+fn level(level: TracingLevel) -> Level {
+    match level {
+        TracingLevel::Error => Level::ERROR,
+        TracingLevel::Warn => Level::WARN,
+        TracingLevel::Info => Level::INFO,
+        TracingLevel::Debug => Level::DEBUG,
+        TracingLevel::Trace => Level::TRACE,
+    }
+}
+
+fn eq_metadata(data: &CallSiteData, metadata: &Metadata<'_>) -> bool {
+    matches!(data.kind, CallSiteKind::Span) == metadata.is_span()
+        && level(data.level) == *metadata.level()
+        && data.line == metadata.line()
+        && data.name == metadata.name()
+        && data.target == metadata.target()
+        && data.module_path.as_deref() == metadata.module_path()
+        && data.file.as_deref() == metadata.file()
+        && data
+            .fields
+            .iter()
+            .map(Cow::as_ref)
+            .eq(metadata.fields().iter().map(|field| field.name()))
+}
@@ -0,0 +1,214 @@
+//! Structural assertions for span trees.
+
+use std::fmt;
+
+use crate::{CapturedSpan, Storage};
+
+/// Shape of a forest of spans described by span names. Can be obtained from captured data
+/// via [`Storage::span_tree()`], or constructed manually (usually via
+/// the [`span_tree!`](crate::span_tree!) macro) to be used in [`Storage::assert_span_tree_shape()`].
+///
+/// The [`Display`](fmt::Display) implementation outputs one span name per line, with children
+/// indented by 2 spaces relative to their parent.
+///
+/// # Examples
+///
+/// ```
+/// # use tracing_capture::{span_tree, SpanTree};
+/// let tree = span_tree! {
+///     "fib" => { "compute" => {} },
+///     "other" => {},
+/// };
+/// let same_tree = SpanTree::new()
+///     .with_child("fib", SpanTree::new().with_child("compute", SpanTree::new()))
+///     .with_child("other", SpanTree::new());
+/// assert_eq!(tree, same_tree);
+/// assert_eq!(tree.to_string(), "fib\n  compute\nother\n");
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SpanTree {
+    children: Vec<(String, SpanTree)>,
+}
+
+impl SpanTree {
+    /// Creates an empty tree.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a child span with the specified name and subtree of its own children.
+    #[must_use]
+    pub fn with_child(mut self, name: impl Into<String>, subtree: Self) -> Self {
+        self.children.push((name.into(), subtree));
+        self
+    }
+
+    /// Iterates over direct children of this tree in the order they were added.
+    pub fn children(&self) -> impl Iterator<Item = (&str, &Self)> + '_ {
+        self.children
+            .iter()
+            .map(|(name, subtree)| (name.as_str(), subtree))
+    }
+
+    /// Checks whether this tree is empty (i.e., has no spans).
+    pub fn is_empty(&self) -> bool {
+        self.children.is_empty()
+    }
+
+    fn from_spans<'a>(spans: impl Iterator<Item = CapturedSpan<'a>>) -> Self {
+        let children = spans
+            .map(|span| {
+                let subtree = Self::from_spans(span.children());
+                (span.metadata().name().to_owned(), subtree)
+            })
+            .collect();
+        Self { children }
+    }
+
+    fn write_lines(&self, indent: usize, lines: &mut Vec<String>) {
+        for (name, subtree) in &self.children {
+            lines.push(format!("{:indent$}{name}", ""));
+            subtree.write_lines(indent + 2, lines);
+        }
+    }
+
+    fn lines(&self) -> Vec<String> {
+        let mut lines = vec![];
+        self.write_lines(0, &mut lines);
+        lines
+    }
+}
+
+impl fmt::Display for SpanTree {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for line in self.lines() {
+            writeln!(formatter, "{line}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Creates a [`SpanTree`] from a nested description of span names.
+///
+/// Each span is specified as `name => { children }`, where `name` is an expression convertible
+/// to `String` and `children` are specified recursively using the same syntax.
+///
+/// # Examples
+///
+/// See [`SpanTree`] and [`Storage::assert_span_tree_shape()`].
+#[macro_export]
+macro_rules! span_tree {
+    ($($name:expr => { $($children:tt)* }),* $(,)?) => {
+        $crate::SpanTree::new()
+            $(.with_child($name, $crate::span_tree!($($children)*)))*
+    };
+}
+
+/// Computes a line-based diff of `expected` and `actual` lines using the longest common
+/// subsequence. Lines only present in `expected` are prefixed with `-`, lines only present
+/// in `actual` with `+`.
+fn diff_lines(expected: &[String], actual: &[String]) -> String {
+    let (n, m) = (expected.len(), actual.len());
+    // `lcs[i][j]` is the LCS length for `expected[i..]` and `actual[j..]`.
+    let mut lcs = vec![vec![0_usize; m + 1]; n + 1];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            lcs[i][j] = if expected[i] == actual[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut output = String::new();
+    let (mut i, mut j) = (0, 0);
+    while i < n || j < m {
+        let line = if i < n && j < m && expected[i] == actual[j] {
+            i += 1;
+            j += 1;
+            format!("  {}", expected[i - 1])
+        } else if j < m && (i == n || lcs[i][j + 1] >= lcs[i + 1][j]) {
+            j += 1;
+            format!("+ {}", actual[j - 1])
+        } else {
+            i += 1;
+            format!("- {}", expected[i - 1])
+        };
+        output.push_str(&line);
+        output.push('\n');
+    }
+    output
+}
+
+impl Storage {
+    /// Returns the shape of the captured span forest, starting from
+    /// the [root spans](Self::root_spans()). Spans are ordered by capture order.
+    pub fn span_tree(&self) -> SpanTree {
+        SpanTree::from_spans(self.root_spans())
+    }
+
+    /// Returns the maximum nesting depth of captured spans, i.e., the maximum number
+    /// of captured [ancestors](CapturedSpan::ancestors()) across all spans. Root spans have
+    /// depth 0; if no spans are captured, returns 0 as well. This is useful to assert
+    /// recursion bounds.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use tracing_subscriber::{layer::SubscriberExt, Registry};
+    /// # use tracing_capture::{CaptureLayer, SharedStorage};
+    /// #[tracing::instrument]
+    /// fn countdown(value: u32) {
+    ///     if value > 0 {
+    ///         countdown(value - 1);
+    ///     }
+    /// }
+    ///
+    /// let storage = SharedStorage::default();
+    /// let subscriber = Registry::default().with(CaptureLayer::new(&storage));
+    /// tracing::subscriber::with_default(subscriber, || countdown(3));
+    /// assert_eq!(storage.lock().max_span_depth(), 3);
+    /// ```
+    pub fn max_span_depth(&self) -> usize {
+        self.all_spans()
+            .map(|span| span.ancestors().count())
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Asserts that the shape of the captured span forest (as returned by [`Self::span_tree()`])
+    /// is equal to the `expected` one. The ordering of spans matters.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the shapes differ. The panic message contains a line-based diff of the expected
+    /// and actual shapes.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use tracing_subscriber::{layer::SubscriberExt, Registry};
+    /// # use tracing_capture::{span_tree, CaptureLayer, SharedStorage};
+    /// let storage = SharedStorage::default();
+    /// let subscriber = Registry::default().with(CaptureLayer::new(&storage));
+    /// tracing::subscriber::with_default(subscriber, || {
+    ///     tracing::info_span!("outer").in_scope(|| {
+    ///         tracing::info_span!("inner").in_scope(|| { /* do work */ });
+    ///         tracing::info_span!("inner").in_scope(|| { /* do more work */ });
+    ///     });
+    /// });
+    ///
+    /// storage.lock().assert_span_tree_shape(&span_tree! {
+    ///     "outer" => { "inner" => {}, "inner" => {} },
+    /// });
+    /// ```
+    #[track_caller]
+    pub fn assert_span_tree_shape(&self, expected: &SpanTree) {
+        let actual = self.span_tree();
+        if actual != *expected {
+            let diff = diff_lines(&expected.lines(), &actual.lines());
+            panic!("span tree shape mismatch (-expected, +actual):\n{diff}");
+        }
+    }
+}
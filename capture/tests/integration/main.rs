@@ -1,21 +1,47 @@
 //! Integration tests for tracing capture.
 
 use assert_matches::assert_matches;
-use predicates::ord::eq;
-use tracing_core::{Level, LevelFilter};
-use tracing_subscriber::{layer::SubscriberExt, Registry};
+use predicates::{
+    ord::{eq, gt},
+    Predicate,
+};
+use tracing::Instrument;
+use tracing_core::{
+    span::{Attributes, Current, Id, Record},
+    Event, Interest, Level, LevelFilter, Metadata, Subscriber,
+};
+use tracing_subscriber::{
+    layer::{Context, SubscriberExt},
+    registry::{LookupSpan, SpanData},
+    Layer, Registry,
+};
 
-use std::{borrow::Cow, panic, thread, time::Duration};
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    panic, ptr,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+};
 
 mod fib;
+#[cfg(feature = "serde")]
+mod snapshot;
 
 use tracing_capture::{
-    predicates::{ancestor, field, level, message, name, parent, ScanExt},
-    CaptureLayer, SharedStorage, Storage,
+    predicates::{
+        ancestor, entered, event_count, exited, field, is_closed, level, message, name, parent,
+        subtree_event_count, target, ScanExt,
+    },
+    span_tree, CaptureClock, CaptureLayer, CapturedSpan, SharedStorage, Storage, TimelineItem,
 };
 use tracing_tunnel::{
     CallSiteData, CallSiteKind, LocalSpans, TracedValue, TracedValues, TracingEvent,
-    TracingEventReceiver, TracingLevel,
+    TracingEventReceiver, TracingEventSender, TracingLevel,
 };
 
 const CALL_SITE_DATA: CallSiteData = CallSiteData {
@@ -131,6 +157,58 @@ fn recorded_span_values_are_restored() {
     assert_eq!(event["message"].as_str(), Some("test"));
 }
 
+// This is also a `TracingEventReceiver` test.
+#[test]
+fn follows_from_relations_are_restored_for_non_local_spans() {
+    let events = [
+        TracingEvent::NewCallSite {
+            id: 0,
+            data: CALL_SITE_DATA.with_field("i"),
+        },
+        TracingEvent::NewSpan {
+            id: 0,
+            parent_id: None,
+            metadata_id: 0,
+            values: TracedValues::from_iter([("i".to_owned(), TracedValue::from(0_i64))]),
+        },
+        TracingEvent::NewSpan {
+            id: 1,
+            parent_id: None,
+            metadata_id: 0,
+            values: TracedValues::from_iter([("i".to_owned(), TracedValue::from(1_i64))]),
+        },
+    ];
+    let mut receiver = TracingEventReceiver::default();
+    receiver.extend(events);
+    let metadata = receiver.persist_metadata();
+    let (spans, _) = receiver.persist();
+
+    // Emulate host restart; neither of the spans is entered before the relation is established.
+    let more_events = [
+        TracingEvent::FollowsFrom {
+            id: 1,
+            follows_from: 0,
+        },
+        TracingEvent::SpanEntered { id: 1 },
+        TracingEvent::SpanExited { id: 1 },
+    ];
+    let storage = SharedStorage::default();
+    let subscriber = Registry::default().with(CaptureLayer::new(&storage));
+    tracing::subscriber::with_default(subscriber, || {
+        let mut receiver = TracingEventReceiver::new(metadata, spans, LocalSpans::default());
+        receiver.extend(more_events);
+    });
+
+    let storage = storage.lock();
+    assert_eq!(storage.all_spans().len(), 2);
+    let source_span = storage.scan_spans().single(&field("i", 0_i64));
+    assert_eq!(source_span.stats().entered, 0);
+    let span = storage.scan_spans().single(&field("i", 1_i64));
+    assert_eq!(span.stats().entered, 1);
+    let follows_from: Vec<_> = span.follows_from().collect();
+    assert_eq!(follows_from, [source_span]);
+}
+
 // This is also a `TracingEventReceiver` test.
 #[test]
 fn spans_are_exited_on_receiver_drop() {
@@ -159,6 +237,13 @@ fn spans_are_exited_on_receiver_drop() {
     for event in events {
         receiver.receive(event);
     }
+    {
+        let storage = storage.lock();
+        let span = storage.unbalanced_spans().next().unwrap();
+        assert!(!span.is_balanced());
+        assert_eq!(span.stats().entered, 1);
+        assert_eq!(span.stats().exited, 0);
+    }
     let metadata = receiver.persist_metadata();
     let (spans, local_spans) = receiver.persist();
 
@@ -167,6 +252,8 @@ fn spans_are_exited_on_receiver_drop() {
         let span = storage.all_spans().next().unwrap();
         assert_eq!(span.stats().entered, 1);
         assert_eq!(span.stats().exited, 1); // <<< force-exited on receiver drop
+        assert!(span.is_balanced());
+        assert_eq!(storage.unbalanced_spans().count(), 0);
         assert!(!span.stats().is_closed);
     }
 
@@ -196,6 +283,52 @@ fn spans_are_exited_on_receiver_drop() {
     assert_eq!(spans[1].stats().exited, 1); // <<< force-exited on receiver drop
     assert!(spans[1].stats().is_closed);
     // ^ auto-closed since the span is created by the discarded execution
+    assert!(spans.iter().all(CapturedSpan::is_balanced));
+}
+
+// This is also a `TracingEventReceiver` test.
+#[test]
+fn entered_spans_are_exited_on_persist() {
+    let events = [
+        TracingEvent::NewCallSite {
+            id: 0,
+            data: CALL_SITE_DATA,
+        },
+        TracingEvent::NewSpan {
+            id: 0,
+            parent_id: None,
+            metadata_id: 0,
+            values: TracedValues::new(),
+        },
+        TracingEvent::SpanEntered { id: 0 },
+        TracingEvent::NewSpan {
+            id: 1,
+            parent_id: Some(0),
+            metadata_id: 0,
+            values: TracedValues::new(),
+        },
+        TracingEvent::SpanEntered { id: 1 },
+    ];
+
+    let storage = SharedStorage::default();
+    let subscriber = Registry::default().with(CaptureLayer::new(&storage));
+    let _guard = tracing::subscriber::set_default(subscriber);
+
+    let mut receiver = TracingEventReceiver::default();
+    receiver.extend(events);
+    assert!(tracing::Span::current().id().is_some());
+    let _persisted = receiver.persist();
+    // The subscriber span stack should be balanced after persisting.
+    assert!(tracing::Span::current().id().is_none());
+
+    let storage = storage.lock();
+    let captured_spans: Vec<_> = storage.all_spans().collect();
+    assert_eq!(captured_spans.len(), 2);
+    for span in captured_spans {
+        assert_eq!(span.stats().entered, 1);
+        assert_eq!(span.stats().exited, 1);
+        assert!(!span.stats().is_closed);
+    }
 }
 
 #[test]
@@ -213,29 +346,160 @@ fn assert_captured_spans(storage: &Storage) {
         .find(|span| span.metadata().name() == "compute")
         .unwrap();
     assert_eq!(fib_span.metadata().target(), "fib");
+    assert_eq!(fib_span.module_path(), Some("integration::fib"));
     assert_eq!(fib_span.stats().entered, 1);
     assert!(fib_span.stats().is_closed);
     assert_matches!(fib_span["count"], TracedValue::UInt(5));
+    assert_eq!(fib_span.field_count(), 1);
 
     assert_eq!(fib_span.events().len(), 6); // 5 iterations + return
     let iter_events = fib_span.events().take(5);
     for (i, event) in iter_events.enumerate() {
         assert_eq!(event.metadata().target(), "fib");
+        assert_eq!(event.module_path(), Some("integration::fib"));
         assert_eq!(*event.metadata().level(), Level::DEBUG);
         assert_eq!(event.message(), Some("performing iteration"));
         assert_eq!(event["i"], i as u64);
+        assert_eq!(event.field_count(), 2); // `i` and `current`
     }
-    let return_event = fib_span.events().last().unwrap();
+    let return_event = fib_span.events().next_back().unwrap();
     assert_eq!(*return_event.metadata().level(), Level::INFO);
     assert!(return_event["return"].is_debug(&5));
+    assert_eq!(return_event.field_count(), 1);
 
     let outer_span = storage.all_spans().next().unwrap();
     assert_eq!(outer_span.metadata().name(), "fib");
     assert_eq!(outer_span["approx"], 5.0_f64);
+    assert_eq!(outer_span.field_count(), 1);
     assert_eq!(outer_span.events().len(), 2);
     let warn_event = outer_span.events().next().unwrap();
     assert_eq!(*warn_event.metadata().level(), Level::WARN);
     assert_eq!(warn_event.message(), Some("count looks somewhat large"));
+    assert_eq!(warn_event.field_count(), 1);
+}
+
+#[test]
+fn replaying_compacted_events() {
+    fn replay(events: Vec<TracingEvent>) -> SharedStorage {
+        let storage = SharedStorage::default();
+        let subscriber = Registry::default().with(CaptureLayer::new(&storage));
+        tracing::subscriber::with_default(subscriber, || {
+            TracingEventReceiver::default().extend(events);
+        });
+        storage
+    }
+
+    let events = fib::record_events(5);
+    let compacted = TracingEvent::compact(events.clone());
+    assert!(compacted.len() < events.len());
+
+    let original = replay(events);
+    let compacted = replay(compacted);
+    let (original, compacted) = (original.lock(), compacted.lock());
+    assert!(original.diff(&compacted).is_empty());
+    assert_captured_spans(&compacted);
+}
+
+#[cfg(feature = "tunnel-source-ids")]
+#[test]
+fn capturing_source_span_ids_for_replayed_events() {
+    let events = fib::record_events(5);
+    let mut call_site_names = HashMap::new();
+    let mut source_spans = HashMap::new();
+    for event in &events {
+        match event {
+            TracingEvent::NewCallSite { id, data } => {
+                call_site_names.insert(*id, data.name.clone());
+            }
+            TracingEvent::NewSpan {
+                id, metadata_id, ..
+            } => {
+                source_spans.insert(*id, call_site_names[metadata_id].clone());
+            }
+            _ => { /* do nothing */ }
+        }
+    }
+    assert_eq!(source_spans.len(), 2);
+
+    let storage = SharedStorage::default();
+    let subscriber = Registry::default().with(CaptureLayer::new(&storage));
+    tracing::subscriber::with_default(subscriber, || {
+        TracingEventReceiver::default().extend(events);
+        tracing::info_span!("local").in_scope(|| { /* do nothing */ });
+    });
+
+    let storage = storage.lock();
+    assert_eq!(storage.all_spans().len(), 3);
+    for span in storage.all_spans().take(2) {
+        let source_id = span.source_span_id().unwrap();
+        assert_eq!(source_spans[&source_id], span.metadata().name());
+    }
+    let local_span = storage.scan_spans().single(&name(eq("local")));
+    assert_eq!(local_span.source_span_id(), None);
+}
+
+#[test]
+fn event_count_predicates() {
+    let storage = SharedStorage::default();
+    let subscriber = Registry::default().with(CaptureLayer::new(&storage));
+    tracing::subscriber::with_default(subscriber, || fib::fib(5));
+
+    let storage = storage.lock();
+    let compute_span = storage.scan_spans().single(&event_count(eq(6)));
+    assert_eq!(compute_span.metadata().name(), "compute");
+    let outer_span = storage.scan_spans().single(&event_count(eq(2)));
+    assert_eq!(outer_span.metadata().name(), "fib");
+    assert!(subtree_event_count(eq(8)).eval(&outer_span));
+    assert!(subtree_event_count(eq(6)).eval(&compute_span));
+
+    let predicate = event_count(gt(6));
+    assert_eq!(predicate.to_string(), "event_count(var > 6)");
+    storage.scan_spans().none(&predicate);
+    let case = predicate.find_case(false, &compute_span).unwrap();
+    let products: Vec<_> = case.products().map(ToString::to_string).collect();
+    assert_eq!(products, ["event_count: 6"]);
+}
+
+#[test]
+fn span_stats_predicates() {
+    let storage = SharedStorage::default();
+    let subscriber = Registry::default().with(CaptureLayer::new(&storage));
+    tracing::subscriber::with_default(subscriber, || fib::fib(5));
+
+    let storage = storage.lock();
+    let predicate = name(eq("compute")) & entered(eq(1)) & exited(eq(1)) & is_closed();
+    storage.scan_spans().single(&predicate);
+    storage.scan_spans().all(&(entered(eq(1)) & is_closed()));
+    storage.scan_spans().none(&entered(gt(1)));
+
+    let compute_span = storage.scan_spans().single(&name(eq("compute")));
+    let predicate = entered(gt(1));
+    assert_eq!(predicate.to_string(), "entered(var > 1)");
+    let case = predicate.find_case(false, &compute_span).unwrap();
+    let products: Vec<_> = case.products().map(ToString::to_string).collect();
+    assert_eq!(products, ["entered: 1"]);
+
+    assert!(is_closed().eval(&compute_span));
+    assert!(is_closed().find_case(false, &compute_span).is_none());
+    assert_eq!(is_closed().to_string(), "is_closed()");
+}
+
+#[test]
+fn collecting_matching_events() {
+    let storage = SharedStorage::default();
+    let subscriber = Registry::default().with(CaptureLayer::new(&storage));
+    tracing::subscriber::with_default(subscriber, || fib::fib(5));
+
+    let storage = storage.lock();
+    let predicate = message(eq("performing iteration"));
+    let events = storage.scan_events().matching(&predicate);
+    let indices: Vec<_> = events.iter().map(|event| event["i"].clone()).collect();
+    assert_eq!(indices, [0_u64, 1, 2, 3, 4].map(TracedValue::from));
+
+    let compute_span = storage.scan_spans().single(&name(eq("compute")));
+    assert_eq!(compute_span.scan_events().matching(&predicate).len(), 5);
+    let outer_span = storage.scan_spans().single(&name(eq("fib")));
+    assert!(outer_span.scan_events().matching(&predicate).is_empty());
 }
 
 #[test]
@@ -254,6 +518,164 @@ fn capturing_spans_for_replayed_events() {
     assert_captured_spans(&storage.lock());
 }
 
+fn capture_replayed_events(events: &[TracingEvent]) -> SharedStorage {
+    let storage = SharedStorage::default();
+    let subscriber = Registry::default().with(CaptureLayer::new(&storage));
+    tracing::subscriber::with_default(subscriber, || {
+        let mut consumer = TracingEventReceiver::default();
+        consumer.extend(events.iter().cloned());
+    });
+    storage
+}
+
+#[test]
+fn metadata_of_replayed_events_outlives_receiver() {
+    let events = fib::record_events(5);
+    let call_sites: HashMap<_, _> = events
+        .iter()
+        .filter_map(|event| match event {
+            TracingEvent::NewCallSite { data, .. } => Some((data.name.as_ref(), data)),
+            _ => None,
+        })
+        .collect();
+
+    // The receiver and the subscriber are dropped by this point.
+    let storage = capture_replayed_events(&events);
+    let storage = storage.lock();
+    let metadata = storage
+        .all_spans()
+        .map(|span| span.metadata())
+        .chain(storage.all_events().map(|event| event.metadata()));
+    for metadata in metadata {
+        let data = call_sites[metadata.name()];
+        assert_eq!(metadata.target(), data.target);
+        assert_eq!(metadata.module_path(), data.module_path.as_deref());
+        assert_eq!(metadata.file(), data.file.as_deref());
+        assert_eq!(metadata.line(), data.line);
+        let field_names: Vec<_> = metadata.fields().iter().map(|field| field.name()).collect();
+        assert_eq!(field_names, data.fields);
+    }
+
+    // Metadata is shared among storages since leaked call sites are reused.
+    let other_storage = capture_replayed_events(&events);
+    let other_storage = other_storage.lock();
+    for (span, other_span) in storage.all_spans().zip(other_storage.all_spans()) {
+        assert!(ptr::eq(span.metadata(), other_span.metadata()));
+    }
+    drop(other_storage);
+    assert_captured_spans(&storage);
+}
+
+#[test]
+fn remapping_targets_for_replayed_events() {
+    let events = fib::record_events(5);
+
+    let storage = SharedStorage::default();
+    let subscriber = Registry::default().with(CaptureLayer::new(&storage));
+    tracing::subscriber::with_default(subscriber, || {
+        let mut consumer = TracingEventReceiver::default()
+            .with_target_mapper(|target| Cow::Owned(format!("wasm::{target}")));
+        for event in events {
+            consumer.receive(event);
+        }
+    });
+
+    let storage = storage.lock();
+    assert!(storage.all_spans().len() > 0);
+    for span in storage.all_spans() {
+        assert!(span.metadata().target().starts_with("wasm::"), "{span:?}");
+    }
+    assert!(storage.all_events().len() > 0);
+    storage.scan_events().all(&target("wasm"));
+    let outer_span = storage.scan_spans().single(&name(eq("fib")));
+    assert_eq!(outer_span.metadata().target(), "wasm::integration::fib");
+}
+
+#[test]
+fn filtering_by_target_with_closure() {
+    let storage = SharedStorage::default();
+    let layer =
+        CaptureLayer::new(&storage).with_metadata_filter(|metadata| metadata.target() != "other");
+    let subscriber = Registry::default().with(layer);
+    tracing::subscriber::with_default(subscriber, || {
+        tracing::info_span!(target: "other", "other").in_scope(|| {
+            fib::fib(5);
+            tracing::info!(target: "other", "filtered out");
+        });
+    });
+
+    let storage = storage.lock();
+    assert_captured_spans(&storage);
+    storage.scan_spans().none(&target("other"));
+    storage.scan_events().none(&target("other"));
+}
+
+#[test]
+fn disabled_spans_are_not_captured() {
+    let storage = SharedStorage::default();
+    let subscriber = Registry::default()
+        .with(CaptureLayer::new(&storage))
+        .with(LevelFilter::INFO);
+    tracing::subscriber::with_default(subscriber, || {
+        let none = tracing::Span::none();
+        none.in_scope(|| tracing::info!("in none"));
+        none.record("value", 42_i64);
+
+        let disabled = tracing::debug_span!("disabled", value = tracing::field::Empty);
+        assert!(disabled.is_disabled());
+        disabled.record("value", 42_i64);
+        let outer = tracing::info_span!("outer");
+        outer.follows_from(&disabled);
+        disabled.follows_from(&outer);
+        outer.follows_from(&none);
+        disabled.in_scope(|| {
+            tracing::info!("in disabled");
+            outer.in_scope(|| tracing::info!("in outer"));
+        });
+        drop(disabled);
+    });
+
+    let storage = storage.lock();
+    assert_eq!(storage.all_spans().len(), 1);
+    let outer = storage.all_spans().next().unwrap();
+    assert_eq!(outer.metadata().name(), "outer");
+    assert!(outer.follows_from().next().is_none());
+    assert_eq!(outer.stats().entered, 1);
+    assert!(outer.stats().is_closed);
+    let root_messages: Vec<_> = storage
+        .root_events()
+        .map(|event| event.message().unwrap())
+        .collect();
+    assert_eq!(root_messages, ["in none", "in disabled"]);
+    let outer_event = outer.events().next().unwrap();
+    assert_eq!(outer_event.message(), Some("in outer"));
+}
+
+#[test]
+fn capturing_raw_parent_ids() {
+    let storage = SharedStorage::default();
+    let layer = CaptureLayer::new(&storage).with_filter(LevelFilter::INFO);
+    let subscriber = Registry::default().with(layer);
+    let middle_id = tracing::subscriber::with_default(subscriber, || {
+        let _outer = tracing::info_span!("outer").entered();
+        let middle = tracing::debug_span!("middle");
+        middle.in_scope(|| tracing::info_span!("inner").in_scope(|| { /* do nothing */ }));
+        middle.id().unwrap().into_u64()
+    });
+
+    let storage = storage.lock();
+    assert_eq!(storage.all_spans().len(), 2);
+    let outer = storage.scan_spans().single(&name(eq("outer")));
+    assert!(outer.raw_id().is_some());
+    assert_eq!(outer.raw_parent_id(), None);
+
+    let inner = storage.scan_spans().single(&name(eq("inner")));
+    assert_eq!(inner.parent(), Some(outer));
+    assert_eq!(inner.skipped_ancestors(), 1);
+    assert_eq!(inner.raw_parent_id(), Some(middle_id));
+    assert_ne!(inner.raw_parent_id(), outer.raw_id());
+}
+
 #[test]
 fn capturing_events_with_indirect_ancestor() {
     #[tracing::instrument(level = "debug", ret)]
@@ -290,11 +712,146 @@ fn capturing_events_with_indirect_ancestor() {
     let root_event = root_events.next().unwrap();
     assert_eq!(root_event["value"], -3_i64);
 
+    let contexts: Vec<_> = storage
+        .events_with_context()
+        .map(|(event, span)| (event["value"].as_int(), span))
+        .collect();
+    assert_eq!(contexts.len(), 2);
+    assert_eq!(contexts[0].0, Some(5));
+    assert_eq!(contexts[0].1, Some(span));
+    assert_eq!(contexts[1].0, Some(-3));
+    assert_eq!(contexts[1].1, None);
+
     let predicate = message(eq("doubled")) & parent(level(Level::INFO) & name(eq("wrapper")));
     let span_event = storage.scan_events().single(&predicate);
     assert_eq!(span_event["value"], 5_i64);
 }
 
+#[test]
+fn merging_spans_and_events_into_timeline() {
+    let storage = SharedStorage::default();
+    let subscriber = Registry::default().with(CaptureLayer::new(&storage));
+    tracing::subscriber::with_default(subscriber, || {
+        tracing::info!("started");
+        let outer = tracing::info_span!("outer");
+        let inner = outer.in_scope(|| {
+            tracing::info!("outer work");
+            tracing::info_span!("inner")
+        });
+        inner.in_scope(|| tracing::info!("inner work"));
+        outer.in_scope(|| tracing::info!("more outer work"));
+        tracing::info!("finished");
+    });
+
+    let storage = storage.lock();
+    let timeline: Vec<_> = storage
+        .timeline()
+        .map(|item| match item {
+            TimelineItem::Span(span) => format!("span {}", span.metadata().name()),
+            TimelineItem::Event(event) => event.message().unwrap().to_owned(),
+        })
+        .collect();
+    assert_eq!(
+        timeline,
+        [
+            "started",
+            "span outer",
+            "outer work",
+            "span inner",
+            "inner work",
+            "more outer work",
+            "finished",
+        ]
+    );
+    assert_eq!(
+        storage.timeline().count(),
+        storage.all_spans().len() + storage.all_events().len()
+    );
+}
+
+#[test]
+fn sequence_numbers_increase_across_spans_and_events() {
+    let storage = SharedStorage::default();
+    let subscriber = Registry::default().with(CaptureLayer::new(&storage));
+    tracing::subscriber::with_default(subscriber, || fib::fib(5));
+
+    let storage = storage.lock();
+    let seqs: Vec<_> = storage
+        .timeline()
+        .map(|item| match item {
+            TimelineItem::Span(span) => span.seq(),
+            TimelineItem::Event(event) => event.seq(),
+        })
+        .collect();
+    let expected_len = storage.all_spans().len() + storage.all_events().len();
+    assert_eq!(seqs.len(), expected_len);
+    assert!(
+        seqs.windows(2).all(|window| window[0] < window[1]),
+        "{seqs:?}"
+    );
+
+    for span in storage.all_spans() {
+        for event in span.events() {
+            assert!(span.seq() < event.seq());
+        }
+        for child in span.children() {
+            assert!(span.seq() < child.seq());
+        }
+    }
+}
+
+#[test]
+fn replaying_span_subtree() {
+    let events = Arc::new(Mutex::new(vec![]));
+    let events_ = Arc::clone(&events);
+    let sender = TracingEventSender::new(move |event| {
+        events_.lock().unwrap().push(event);
+    });
+    tracing::subscriber::with_default(sender, || graph(&mut 0, 0));
+    let events = events.lock().unwrap().clone();
+
+    let subtree_root_id = events
+        .iter()
+        .find_map(|event| match event {
+            TracingEvent::NewSpan { id, values, .. } if values["counter"] == 2_u64 => Some(*id),
+            _ => None,
+        })
+        .unwrap();
+    let subtree_events = TracingEvent::filter_subtree(events.clone(), subtree_root_id);
+    assert!(subtree_events.len() < events.len());
+
+    let full_storage = capture_replayed_events(&events);
+    let full_storage = full_storage.lock();
+    let expected_root = full_storage.scan_spans().single(&field("counter", 2_u64));
+    let storage = capture_replayed_events(&subtree_events);
+    let storage = storage.lock();
+
+    assert_eq!(storage.root_spans().len(), 1);
+    assert_eq!(storage.root_events().len(), 0);
+    let root = storage.root_spans().next().unwrap();
+    assert_eq!(root["counter"], 2_u64);
+    assert_eq!(root.stats().entered, 1);
+    assert!(root.stats().is_closed);
+    assert_eq!(
+        storage.all_spans().len(),
+        expected_root.descendants().count() + 1
+    );
+    assert_eq!(
+        storage.all_events().len(),
+        expected_root.all_events_ordered().len()
+    );
+
+    let counters: Vec<_> = root
+        .descendants()
+        .filter_map(|span| span["counter"].as_uint())
+        .collect();
+    let expected_counters: Vec<_> = expected_root
+        .descendants()
+        .filter_map(|span| span["counter"].as_uint())
+        .collect();
+    assert_eq!(counters, expected_counters);
+}
+
 #[test]
 fn capturing_span_hierarchy() {
     #[tracing::instrument(level = "debug", ret)]
@@ -316,6 +873,7 @@ fn capturing_span_hierarchy() {
     assert_eq!(storage.all_events().len(), 12);
     assert_eq!(storage.root_spans().len(), 1);
     assert_eq!(storage.root_events().len(), 0);
+    assert_eq!(storage.max_span_depth(), 5);
 
     let inner_span = storage.all_spans().next_back().unwrap();
     assert_eq!(inner_span["value"], 0_u64);
@@ -331,6 +889,9 @@ fn capturing_span_hierarchy() {
         .filter_map(|span| span["value"].as_uint())
         .collect();
     assert_eq!(ancestor_values, [4, 5]);
+    assert_eq!(middle_span.siblings().count(), 0);
+    let root_span = storage.root_spans().next().unwrap();
+    assert_eq!(root_span.siblings().count(), 0);
 
     let event_filter = parent(field("value", 3_u64)) & message(eq("doubled"));
     storage.scan_events().single(&event_filter);
@@ -338,23 +899,23 @@ fn capturing_span_hierarchy() {
     storage.scan_events().single(&event_filter);
 }
 
-#[test]
-fn capturing_wide_span_graph() {
+#[tracing::instrument(level = "debug")]
+fn graph(counter: &mut u64, depth: usize) {
     const MAX_DEPTH: usize = 3;
 
-    #[tracing::instrument(level = "debug")]
-    fn graph(counter: &mut u64, depth: usize) {
-        *counter += 1;
-        if depth == MAX_DEPTH {
-            tracing::debug!(depth, "reached max depth");
-        } else {
-            let children_count = if *counter % 2 == 0 { 2 } else { 3 };
-            for _ in 0..children_count {
-                graph(counter, depth + 1);
-            }
+    *counter += 1;
+    if depth == MAX_DEPTH {
+        tracing::debug!(depth, "reached max depth");
+    } else {
+        let children_count = if *counter % 2 == 0 { 2 } else { 3 };
+        for _ in 0..children_count {
+            graph(counter, depth + 1);
         }
     }
+}
 
+#[test]
+fn capturing_wide_span_graph() {
     let storage = SharedStorage::default();
     let subscriber = Registry::default().with(CaptureLayer::new(&storage));
     tracing::subscriber::with_default(subscriber, || graph(&mut 0, 0));
@@ -378,6 +939,16 @@ fn capturing_wide_span_graph() {
 
     assert_eq!(storage.root_spans().len(), 1);
     let root = storage.root_spans().next().unwrap();
+    assert_eq!(root.siblings().count(), 0);
+    for span in root.descendants() {
+        let parent = span.parent().unwrap();
+        let siblings: Vec<_> = span.siblings().collect();
+        assert_eq!(siblings.len(), parent.child_count() - 1);
+        assert!(siblings.iter().all(|sibling| *sibling != span));
+        assert!(siblings
+            .iter()
+            .all(|sibling| sibling.parent() == Some(parent)));
+    }
     let counters: Vec<_> = root
         .descendants()
         .filter_map(|span| span["counter"].as_uint())
@@ -394,6 +965,19 @@ fn capturing_wide_span_graph() {
         .filter_map(|span| span["counter"].as_uint())
         .collect();
     assert_eq!(ancestor_counters, [3, 2, 1, 0]);
+
+    let ordered_events = root.all_events_ordered();
+    assert_eq!(ordered_events.len(), storage.all_events().len());
+    let event_pairs = ordered_events.iter().zip(&ordered_events[1..]);
+    for (prev, next) in event_pairs {
+        assert!(prev <= next);
+    }
+    let child = root.children().nth(1).unwrap();
+    let child_events = child.all_events_ordered();
+    assert!(!child_events.is_empty());
+    assert!(child_events
+        .iter()
+        .all(|event| event.ancestors().any(|span| span == child)));
 }
 
 #[test]
@@ -499,6 +1083,134 @@ fn recording_follows_from_relations() {
     }
 }
 
+#[test]
+fn transitive_follows_from_relations() {
+    let storage = SharedStorage::default();
+    let subscriber = Registry::default().with(CaptureLayer::new(&storage));
+    tracing::subscriber::with_default(subscriber, || {
+        let span_c = tracing::info_span!("c");
+        let span_b = tracing::info_span!("b");
+        span_b.follows_from(&span_c);
+        let span_a = tracing::info_span!("a");
+        span_a.follows_from(&span_b);
+        // Create a cycle, which should not lead to infinite iteration or duplicate spans.
+        span_c.follows_from(&span_a);
+        span_a.follows_from(&span_c);
+        let _unrelated = tracing::info_span!("unrelated");
+    });
+
+    let storage = storage.lock();
+    let [span_c, span_b, span_a, unrelated] = ["c", "b", "a", "unrelated"]
+        .map(|span_name| storage.scan_spans().single(&name(eq(span_name))));
+
+    let transitive: Vec<_> = span_a.follows_from_transitive().collect();
+    assert_eq!(transitive, [span_b, span_c]);
+    let transitive: Vec<_> = span_b.follows_from_transitive().collect();
+    assert_eq!(transitive, [span_c, span_a]);
+    assert_eq!(unrelated.follows_from_transitive().count(), 0);
+}
+
+#[test]
+fn partitioning_captures_by_field_value() {
+    let storages: HashMap<u64, SharedStorage> = [1, 2]
+        .into_iter()
+        .map(|id| (id, SharedStorage::default()))
+        .collect();
+    let default_storage = SharedStorage::default();
+    let router_storages = storages.clone();
+    let layer = CaptureLayer::new(&default_storage)
+        .with_close_time_events()
+        .partitioned_by("workflow_id", move |value| {
+            let id = u64::try_from(value.as_uint()?).ok()?;
+            router_storages.get(&id).cloned()
+        });
+    let subscriber = Registry::default().with(layer);
+    tracing::subscriber::with_default(subscriber, || {
+        let outer = tracing::info_span!("outer");
+        let _outer_guard = outer.enter();
+        let workflows = [1_u64, 2].map(|id| tracing::info_span!("workflow", workflow_id = id));
+        for (i, workflow) in workflows.iter().enumerate() {
+            workflow.in_scope(|| {
+                tracing::info!(i, "started");
+                tracing::info_span!("step", i).in_scope(|| tracing::debug!("working"));
+            });
+        }
+        workflows[1].follows_from(&workflows[0]); // should not be captured
+        workflows[1].follows_from(&outer); // should not be captured
+        tracing::info_span!("workflow", workflow_id = 3_u64).in_scope(|| {
+            tracing::info!("unknown workflow");
+        });
+    });
+
+    for (id, storage) in &storages {
+        let storage = storage.lock();
+        storage.assert_span_tree_shape(&span_tree! { "workflow" => { "step" => {} } });
+        let workflow = storage.root_spans().next().unwrap();
+        assert_eq!(workflow["workflow_id"], *id);
+        assert_eq!(workflow.skipped_ancestors(), 1);
+        assert!(workflow.stats().is_closed);
+        assert_eq!(workflow.follows_from().len(), 0);
+        assert_eq!(storage.all_events().len(), 2);
+        assert_eq!(storage.root_events().len(), 0);
+        assert_eq!(workflow.events().next().unwrap()["i"], *id - 1);
+    }
+
+    let storage = default_storage.lock();
+    storage.assert_span_tree_shape(&span_tree! { "outer" => { "workflow" => {} } });
+    let workflow = storage.scan_spans().single(&name(eq("workflow")));
+    assert_eq!(workflow["workflow_id"], 3_u64);
+    assert_eq!(workflow.events().len(), 1);
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn exporting_json_lines_with_partitions() {
+    let buffer = SharedBuffer::default();
+    let storages = [SharedStorage::default(), SharedStorage::default()];
+    let router_storages = storages.clone();
+    let default_storage = SharedStorage::default();
+    let layer = CaptureLayer::new(&default_storage)
+        .with_json_writer(buffer.clone())
+        .partitioned_by("workflow_id", move |value| {
+            let id = usize::try_from(value.as_uint()?).ok()?;
+            router_storages.get(id).cloned()
+        });
+    let subscriber = Registry::default().with(layer);
+    tracing::subscriber::with_default(subscriber, || {
+        for id in [0_u64, 1] {
+            tracing::info_span!("workflow", workflow_id = id).in_scope(|| {
+                tracing::info_span!("step").in_scope(|| tracing::info!("working"));
+            });
+        }
+        tracing::info_span!("other").in_scope(|| tracing::info!("not routed"));
+    });
+
+    let lines = buffer.json_lines();
+    let new_spans: Vec<_> = lines
+        .iter()
+        .filter(|line| line["op"] == "new_span")
+        .map(|line| (line["partition"].clone(), line["id"].clone()))
+        .collect();
+    // Span IDs are only unique within a partition.
+    let partition = |id: u64| serde_json::json!({ "u_int": id });
+    assert_eq!(
+        new_spans,
+        [
+            (partition(0), 0.into()),
+            (partition(0), 1.into()),
+            (partition(1), 0.into()),
+            (partition(1), 1.into()),
+            (serde_json::Value::Null, 0.into()),
+        ]
+    );
+
+    let events: Vec<_> = lines.iter().filter(|line| line["op"] == "event").collect();
+    assert_eq!(events.len(), 3);
+    assert_eq!(events[1]["partition"], partition(1));
+    assert_eq!(events[1]["parent"], 1);
+    assert!(events[2].get("partition").is_none());
+}
+
 #[test]
 fn failed_assertion_while_storage_is_locked() {
     let storage = SharedStorage::default();
@@ -520,3 +1232,1118 @@ fn failed_assertion_while_storage_is_locked() {
     let storage = storage.lock();
     assert_eq!(storage.all_events().len(), 1);
 }
+
+#[test]
+fn logging_in_predicate_while_storage_is_locked() {
+    let storage = SharedStorage::default();
+    let subscriber = Registry::default().with(CaptureLayer::new(&storage));
+    let _guard = tracing::subscriber::set_default(subscriber);
+
+    let span = tracing::info_span!("compute", x = 1_i64);
+    span.in_scope(|| tracing::info!("started"));
+
+    let logging_predicate = predicates::function::function(|value: &TracedValue| {
+        tracing::info_span!("predicate").in_scope(|| {
+            tracing::info!(?value, "evaluating predicate");
+        });
+        *value == 1_i64
+    });
+    {
+        let storage = storage.lock();
+        // Operations on captured spans are not deadlocked either.
+        span.in_scope(|| tracing::warn!("in locked scope"));
+        let predicate = field("x", [logging_predicate]);
+        let compute_span = storage.scan_spans().single(&predicate);
+        assert_eq!(compute_span.metadata().name(), "compute");
+        assert!(predicate.find_case(true, &compute_span).is_some());
+    }
+
+    span.in_scope(|| tracing::info!("finished"));
+    drop(span);
+    let storage = storage.lock();
+    storage.assert_span_tree_shape(&span_tree! { "compute" => {} });
+    let compute_span = storage.all_spans().next().unwrap();
+    let messages: Vec<_> = compute_span
+        .events()
+        .filter_map(|event| event.message())
+        .collect();
+    assert_eq!(messages, ["started", "finished"]);
+    assert_eq!(compute_span.stats().entered, 2);
+    assert!(compute_span.stats().is_closed);
+}
+
+#[test]
+fn capturing_span_after_recording_values() {
+    let storage = SharedStorage::default();
+    let layer = CaptureLayer::new(&storage)
+        .with_filter(LevelFilter::INFO)
+        .with_late_capture(|metadata, values| {
+            metadata.name() == "lazy" && values.get("answer").is_some_and(|val| *val == 42_i64)
+        });
+    let subscriber = Registry::default().with(layer);
+    tracing::subscriber::with_default(subscriber, || {
+        tracing::info_span!("wrapper").in_scope(|| {
+            let span = tracing::debug_span!("lazy", answer = tracing::field::Empty, ignored = 1);
+            let _entered = span.enter();
+            tracing::info!("before record");
+            span.record("answer", 23_i64);
+            tracing::info!("still boring");
+            span.record("answer", 42_i64);
+            tracing::info!("after record");
+        });
+        // Spans without records are never captured.
+        tracing::debug_span!("lazy").in_scope(|| tracing::info!("skipped"));
+    });
+
+    let storage = storage.lock();
+    assert_eq!(storage.all_spans().len(), 2);
+    let lazy_span = storage.scan_spans().single(&name(eq("lazy")));
+    assert_eq!(lazy_span["answer"], 42_i64);
+    assert_eq!(lazy_span["ignored"], 1_i64);
+    assert_eq!(lazy_span.parent().unwrap().metadata().name(), "wrapper");
+    // The span was already entered when captured, so the entry is not counted.
+    assert_eq!(lazy_span.stats().entered, 0);
+    assert_eq!(lazy_span.stats().exited, 1);
+    assert!(lazy_span.stats().is_closed);
+
+    let messages: Vec<_> = lazy_span
+        .events()
+        .filter_map(|event| event.message())
+        .collect();
+    assert_eq!(messages, ["after record"]);
+    let wrapper = lazy_span.parent().unwrap();
+    let messages: Vec<_> = wrapper
+        .events()
+        .filter_map(|event| event.message())
+        .collect();
+    assert_eq!(messages, ["before record", "still boring"]);
+    let messages: Vec<_> = storage
+        .root_events()
+        .filter_map(|event| event.message())
+        .collect();
+    assert_eq!(messages, ["skipped"]);
+}
+
+#[test]
+fn flattening_storage() {
+    let storage = SharedStorage::default();
+    let subscriber = Registry::default().with(CaptureLayer::new(&storage));
+    tracing::subscriber::with_default(subscriber, || {
+        fib::fib(5);
+        tracing::info!("root event");
+    });
+
+    let storage = storage.lock();
+    let flat_events: Vec<_> = storage.flattened().collect();
+    assert_eq!(flat_events.len(), storage.all_events().len());
+
+    let iteration_events = flat_events
+        .iter()
+        .filter(|flat_event| flat_event.event.message() == Some("performing iteration"));
+    for flat_event in iteration_events {
+        let span_names: Vec<_> = flat_event.span_names().collect();
+        assert_eq!(span_names, ["fib", "compute"]);
+        assert_eq!(flat_event.span_path[0]["approx"], 5.0_f64);
+        assert_eq!(flat_event.span_path[1]["count"], 5_u64);
+        assert_eq!(
+            flat_event.span_path.last(),
+            flat_event.event.parent().as_ref()
+        );
+    }
+
+    let root_event = flat_events.last().unwrap();
+    assert_eq!(root_event.event.message(), Some("root event"));
+    assert!(root_event.span_path.is_empty());
+}
+
+#[test]
+fn counting_skipped_ancestors() {
+    let storage = SharedStorage::default();
+    let layer = CaptureLayer::new(&storage).with_filter(LevelFilter::INFO);
+    let subscriber = Registry::default().with(layer);
+    tracing::subscriber::with_default(subscriber, || {
+        tracing::info_span!("outer").in_scope(|| {
+            tracing::debug_span!("middle").in_scope(|| {
+                tracing::info!("in middle");
+                tracing::info_span!("inner").in_scope(|| tracing::info!("in inner"));
+            });
+        });
+        tracing::debug_span!("root").in_scope(|| tracing::info!("orphan"));
+    });
+
+    let storage = storage.lock();
+    let outer = storage
+        .all_spans()
+        .find(|span| span.metadata().name() == "outer");
+    let outer = outer.unwrap();
+    assert_eq!(outer.skipped_ancestors(), 0);
+    let inner = storage
+        .all_spans()
+        .find(|span| span.metadata().name() == "inner");
+    let inner = inner.unwrap();
+    assert_eq!(inner.parent(), Some(outer));
+    assert_eq!(inner.skipped_ancestors(), 1);
+
+    let middle_event = storage.scan_events().single(&message(eq("in middle")));
+    assert_eq!(middle_event.parent(), Some(outer));
+    assert_eq!(middle_event.skipped_ancestors(), 1);
+    let inner_event = storage.scan_events().single(&message(eq("in inner")));
+    assert_eq!(inner_event.parent(), Some(inner));
+    assert_eq!(inner_event.skipped_ancestors(), 0);
+    let orphan_event = storage.scan_events().single(&message(eq("orphan")));
+    assert!(orphan_event.parent().is_none());
+    assert_eq!(orphan_event.skipped_ancestors(), 1);
+}
+
+#[test]
+fn tracking_known_call_sites() {
+    let storage = SharedStorage::default();
+    let layer = CaptureLayer::new(&storage)
+        .with_filter(LevelFilter::INFO)
+        .with_known_call_sites();
+    let subscriber = Registry::default().with(layer);
+    tracing::subscriber::with_default(subscriber, || {
+        tracing::info!(target: "known_call_sites", "captured");
+        tracing::debug!(target: "known_call_sites", "filtered");
+    });
+
+    let storage = storage.lock();
+    let event = storage.scan_events().single(&target("known_call_sites"));
+    assert_eq!(event.message(), Some("captured"));
+
+    let known_call_sites: Vec<_> = storage
+        .known_call_sites()
+        .iter()
+        .filter(|metadata| metadata.target() == "known_call_sites")
+        .collect();
+    assert_eq!(known_call_sites.len(), 2);
+    assert!(known_call_sites
+        .iter()
+        .any(|metadata| *metadata.level() == Level::DEBUG));
+}
+
+#[test]
+fn capturing_span_timestamps() {
+    let storage = SharedStorage::default();
+    let subscriber = Registry::default().with(CaptureLayer::new(&storage).with_timing());
+    tracing::subscriber::with_default(subscriber, || {
+        tracing::info_span!("first").in_scope(|| thread::sleep(Duration::from_millis(1)));
+        tracing::info_span!("second").in_scope(|| thread::sleep(Duration::from_millis(1)));
+    });
+
+    let storage = storage.lock();
+    let first = storage.scan_spans().single(&name(eq("first")));
+    let second = storage.scan_spans().single(&name(eq("second")));
+    let timestamps = first.timestamps().unwrap();
+    let first_entered = timestamps.first_entered.unwrap();
+    let last_exited = timestamps.last_exited.unwrap();
+    assert!(timestamps.created <= first_entered);
+    assert!(first_entered < last_exited);
+    assert!(last_exited <= timestamps.closed.unwrap());
+
+    assert_eq!(first.closed_before(&second), Some(true));
+    assert_eq!(second.closed_before(&first), Some(false));
+}
+
+#[test]
+fn capturing_busy_durations() {
+    const SLEEPS: [u64; 3] = [1, 10, 3];
+
+    let storage = SharedStorage::default();
+    let subscriber = Registry::default().with(CaptureLayer::new(&storage).with_timing());
+    tracing::subscriber::with_default(subscriber, || {
+        let span = tracing::info_span!("poll");
+        for millis in SLEEPS {
+            span.in_scope(|| thread::sleep(Duration::from_millis(millis)));
+            thread::sleep(Duration::from_millis(1)); // idle time should not be counted
+        }
+    });
+
+    let storage = storage.lock();
+    let span = storage.all_spans().next().unwrap();
+    let durations = span.busy_durations();
+    assert_eq!(durations.len(), SLEEPS.len());
+    for (duration, millis) in durations.iter().zip(SLEEPS) {
+        assert!(*duration >= Duration::from_millis(millis), "{durations:?}");
+    }
+    let max_duration = durations.iter().max().unwrap();
+    assert!(*max_duration >= Duration::from_millis(10));
+
+    let timestamps = span.timestamps().unwrap();
+    let busy_time: Duration = durations.iter().sum();
+    let total_time = timestamps.last_exited.unwrap() - timestamps.first_entered.unwrap();
+    assert!(busy_time < total_time, "{busy_time:?} vs {total_time:?}");
+}
+
+#[derive(Debug)]
+struct MockClock(Mutex<Instant>);
+
+impl MockClock {
+    fn advance(&self, duration: Duration) {
+        *self.0.lock().unwrap() += duration;
+    }
+}
+
+impl CaptureClock for MockClock {
+    fn now(&self) -> Instant {
+        *self.0.lock().unwrap()
+    }
+}
+
+#[test]
+fn capturing_timestamps_with_mock_clock() {
+    let start = Instant::now();
+    let clock = Arc::new(MockClock(Mutex::new(start)));
+    let storage = SharedStorage::default();
+    let layer = CaptureLayer::new(&storage)
+        .with_timing()
+        .with_clock(Arc::clone(&clock));
+    let subscriber = Registry::default().with(layer);
+    tracing::subscriber::with_default(subscriber, || {
+        let span = tracing::info_span!("poll");
+        for millis in [3, 7] {
+            clock.advance(Duration::from_millis(1)); // idle time
+            span.in_scope(|| clock.advance(Duration::from_millis(millis)));
+        }
+        clock.advance(Duration::from_millis(1));
+    });
+
+    let storage = storage.lock();
+    let span = storage.all_spans().next().unwrap();
+    assert_eq!(
+        span.busy_durations(),
+        [Duration::from_millis(3), Duration::from_millis(7)]
+    );
+    let timestamps = span.timestamps().unwrap();
+    assert_eq!(timestamps.created, start);
+    assert_eq!(
+        timestamps.first_entered,
+        Some(start + Duration::from_millis(1))
+    );
+    assert_eq!(
+        timestamps.last_entered,
+        Some(start + Duration::from_millis(5))
+    );
+    assert_eq!(
+        timestamps.last_exited,
+        Some(start + Duration::from_millis(12))
+    );
+    assert_eq!(timestamps.closed, Some(start + Duration::from_millis(13)));
+}
+
+#[test]
+fn span_timestamps_are_not_captured_by_default() {
+    let storage = SharedStorage::default();
+    let subscriber = Registry::default().with(CaptureLayer::new(&storage));
+    tracing::subscriber::with_default(subscriber, || {
+        tracing::info_span!("first").in_scope(|| {});
+        tracing::info_span!("second").in_scope(|| {});
+    });
+
+    let storage = storage.lock();
+    let first = storage.scan_spans().single(&name(eq("first")));
+    let second = storage.scan_spans().single(&name(eq("second")));
+    assert!(first.timestamps().is_none());
+    assert!(first.busy_durations().is_empty());
+    assert_eq!(first.closed_before(&second), None);
+}
+
+#[test]
+fn capturing_instrumented_future() {
+    let storage = SharedStorage::default();
+    let subscriber = Registry::default().with(CaptureLayer::new(&storage));
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap();
+    tracing::subscriber::with_default(subscriber, || {
+        let future = async {
+            for _ in 0..3 {
+                tokio::task::yield_now().await;
+            }
+        };
+        runtime.block_on(future.instrument(tracing::info_span!("async")));
+        tracing::info_span!("sync").in_scope(|| {});
+    });
+
+    let storage = storage.lock();
+    let async_span = storage.scan_spans().single(&name(eq("async")));
+    assert!(async_span.appears_async());
+    let stats = async_span.stats();
+    // The span is entered on each poll, and may be additionally entered when the future is dropped.
+    assert!(stats.poll_count() >= 4, "{stats:?}");
+    assert_eq!(stats.exited, stats.entered);
+    assert!(stats.is_closed);
+
+    let sync_span = storage.scan_spans().single(&name(eq("sync")));
+    assert!(!sync_span.appears_async());
+    assert_eq!(sync_span.stats().poll_count(), 1);
+}
+
+#[test]
+fn asserting_no_events_at_or_above_level() {
+    let storage = SharedStorage::default();
+    let subscriber = Registry::default().with(CaptureLayer::new(&storage));
+    tracing::subscriber::with_default(subscriber, || {
+        tracing::info_span!("test").in_scope(|| {
+            tracing::debug!("starting");
+            tracing::info!("finished");
+        });
+    });
+
+    let storage = storage.lock();
+    storage.assert_no_events_at_or_above(Level::WARN);
+    storage.assert_no_events_at_or_above(Level::ERROR);
+}
+
+#[test]
+#[should_panic(expected = "found 1 event(s) at or above level WARN")]
+fn asserting_no_events_at_or_above_level_failure() {
+    let storage = SharedStorage::default();
+    let subscriber = Registry::default().with(CaptureLayer::new(&storage));
+    tracing::subscriber::with_default(subscriber, || fib::fib(5));
+
+    let storage = storage.lock();
+    storage.assert_no_events_at_or_above(Level::ERROR);
+    storage.assert_no_events_at_or_above(Level::WARN);
+}
+
+#[test]
+fn capturing_allowlisted_spans() {
+    let storage = SharedStorage::default();
+    let layer = CaptureLayer::new(&storage).with_span_names(&["compute"]);
+    let subscriber = Registry::default().with(layer);
+    tracing::subscriber::with_default(subscriber, || fib::fib(5));
+
+    let storage = storage.lock();
+    let span_names: Vec<_> = storage
+        .all_spans()
+        .map(|span| span.metadata().name())
+        .collect();
+    assert_eq!(span_names, ["compute"]);
+
+    let span = storage.all_spans().next().unwrap();
+    assert_eq!(span.events().len(), storage.all_events().len());
+    storage
+        .scan_events()
+        .none(&message(eq("computed Fibonacci number")));
+    assert_eq!(
+        storage
+            .all_events()
+            .filter(|event| event.message() == Some("performing iteration"))
+            .count(),
+        5
+    );
+}
+
+#[derive(Debug, Default)]
+struct CountingLayer {
+    spans: Arc<AtomicUsize>,
+    events: Arc<AtomicUsize>,
+    closed_spans: Arc<AtomicUsize>,
+    disabled_level: Option<Level>,
+}
+
+impl<S: Subscriber> Layer<S> for CountingLayer {
+    fn enabled(&self, metadata: &Metadata<'_>, _ctx: Context<'_, S>) -> bool {
+        self.disabled_level != Some(*metadata.level())
+    }
+
+    fn on_new_span(&self, _attrs: &Attributes<'_>, _id: &Id, _ctx: Context<'_, S>) {
+        self.spans.fetch_add(1, Ordering::SeqCst);
+    }
+
+    fn on_event(&self, _event: &Event<'_>, _ctx: Context<'_, S>) {
+        self.events.fetch_add(1, Ordering::SeqCst);
+    }
+
+    fn on_close(&self, _id: Id, _ctx: Context<'_, S>) {
+        self.closed_spans.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+#[test]
+fn teeing_into_inner_layer() {
+    let inner = CountingLayer::default();
+    let (spans, events, closed_spans) = (
+        Arc::clone(&inner.spans),
+        Arc::clone(&inner.events),
+        Arc::clone(&inner.closed_spans),
+    );
+    let storage = SharedStorage::default();
+    let layer = CaptureLayer::new(&storage)
+        .with_max_level(Level::INFO)
+        .teeing(inner);
+    let subscriber = Registry::default().with(layer);
+    tracing::subscriber::with_default(subscriber, || fib::fib(5));
+
+    let storage = storage.lock();
+    assert_eq!(storage.all_spans().len(), 2);
+    assert_eq!(storage.all_events().len(), 3); // debug events are filtered out
+                                               // The inner layer receives all hook calls regardless of capture filtering.
+    assert_eq!(spans.load(Ordering::SeqCst), 2);
+    assert_eq!(closed_spans.load(Ordering::SeqCst), 2);
+    assert_eq!(events.load(Ordering::SeqCst), 8); // 5 iterations + `ret` + warn + info
+}
+
+#[test]
+fn teeing_into_filtering_inner_layer() {
+    let inner = CountingLayer {
+        disabled_level: Some(Level::INFO),
+        ..CountingLayer::default()
+    };
+    let (spans, events, closed_spans) = (
+        Arc::clone(&inner.spans),
+        Arc::clone(&inner.events),
+        Arc::clone(&inner.closed_spans),
+    );
+    let storage = SharedStorage::default();
+    let layer = CaptureLayer::new(&storage).teeing(inner);
+    let subscriber = Registry::default().with(layer);
+    tracing::subscriber::with_default(subscriber, || fib::fib(5));
+
+    // Capturing is not affected by the inner layer filtering out INFO spans and events.
+    let storage = storage.lock();
+    assert_eq!(storage.all_spans().len(), 2);
+    assert!(storage.all_spans().all(|span| span.stats().is_closed));
+    assert_eq!(storage.all_events().len(), 8);
+    let info_events = storage
+        .all_events()
+        .filter(|event| *event.metadata().level() == Level::INFO);
+    assert_eq!(info_events.count(), 2); // `ret` + info
+
+    // Hooks for the disabled spans and events are not forwarded to the inner layer.
+    assert_eq!(spans.load(Ordering::SeqCst), 0);
+    assert_eq!(closed_spans.load(Ordering::SeqCst), 0);
+    assert_eq!(events.load(Ordering::SeqCst), 6); // 5 iterations + warn
+}
+
+struct EmitOnDrop(&'static str);
+
+impl Drop for EmitOnDrop {
+    fn drop(&mut self) {
+        tracing::info!(name = self.0, "dropped");
+    }
+}
+
+fn emit_events_on_drop() {
+    let _before = EmitOnDrop("before");
+    let _entered = tracing::info_span!("compute").entered();
+    let _within = EmitOnDrop("within");
+    // Drop order: `_within` (within the span), `_entered` (exits and closes the span), `_before`
+}
+
+#[test]
+fn capturing_close_time_events_as_roots_by_default() {
+    let storage = SharedStorage::default();
+    let subscriber = Registry::default().with(CaptureLayer::new(&storage));
+    tracing::subscriber::with_default(subscriber, emit_events_on_drop);
+
+    let storage = storage.lock();
+    let span = storage.all_spans().next().unwrap();
+    assert!(span.stats().is_closed);
+    let span_events: Vec<_> = span.events().map(|event| event["name"].clone()).collect();
+    assert_eq!(span_events, ["within"]);
+    let root_events: Vec<_> = storage
+        .root_events()
+        .map(|event| event["name"].clone())
+        .collect();
+    assert_eq!(root_events, ["before"]);
+}
+
+#[test]
+fn capturing_close_time_events() {
+    let storage = SharedStorage::default();
+    let layer = CaptureLayer::new(&storage).with_close_time_events();
+    let subscriber = Registry::default().with(layer);
+    tracing::subscriber::with_default(subscriber, || {
+        emit_events_on_drop();
+        tracing::info!(name = "after", "after spans");
+        tracing::info_span!("other").in_scope(|| tracing::info!(name = "other", "in span"));
+    });
+
+    let storage = storage.lock();
+    let span = storage.all_spans().next().unwrap();
+    let span_events: Vec<_> = span.events().map(|event| event["name"].clone()).collect();
+    assert_eq!(span_events, ["within", "before"]);
+    let other_span = storage.all_spans().nth(1).unwrap();
+    let span_events: Vec<_> = other_span
+        .events()
+        .map(|event| event["name"].clone())
+        .collect();
+    assert_eq!(span_events, ["other"]);
+    // Only the first event after closing the span is tied to it.
+    let root_events: Vec<_> = storage
+        .root_events()
+        .map(|event| event["name"].clone())
+        .collect();
+    assert_eq!(root_events, ["after"]);
+}
+
+#[test]
+fn redacting_values() {
+    let storage = SharedStorage::default();
+    let layer = CaptureLayer::new(&storage).with_value_transform(|name, value| {
+        if name == "password" {
+            *value = TracedValue::from("***");
+        }
+    });
+    let subscriber = Registry::default().with(layer);
+    tracing::subscriber::with_default(subscriber, || {
+        let span = tracing::info_span!("login", user = "alice", password = "hunter2");
+        span.in_scope(|| tracing::info!(password = "hunter2", "checking password"));
+        span.record("password", "swordfish");
+    });
+
+    let storage = storage.lock();
+    let span = storage.all_spans().next().unwrap();
+    assert_eq!(span["user"], "alice");
+    assert_eq!(span["password"], "***");
+    let event = span.events().next().unwrap();
+    assert_eq!(event["password"], "***");
+    assert_eq!(event.message(), Some("checking password"));
+}
+
+#[test]
+fn diffing_captures() {
+    fn capture(extra_event: bool) -> SharedStorage {
+        let storage = SharedStorage::default();
+        let subscriber = Registry::default().with(CaptureLayer::new(&storage));
+        tracing::subscriber::with_default(subscriber, || {
+            fib::fib(5);
+            if extra_event {
+                tracing::info!(extra = true, "extra event");
+            }
+        });
+        storage
+    }
+
+    let (golden, fresh) = (capture(false), capture(true));
+    let (golden, fresh) = (golden.lock(), fresh.lock());
+    assert!(golden.diff(&golden).is_empty());
+
+    let diff = fresh.diff(&golden);
+    assert!(diff.added_spans.is_empty() && diff.removed_spans.is_empty());
+    assert!(diff.added_events.is_empty());
+    assert_eq!(diff.removed_events.len(), 1);
+    assert_eq!(diff.removed_events[0]["extra"], true);
+
+    let diff = golden.diff(&fresh);
+    assert!(!diff.is_empty());
+    assert!(diff.removed_events.is_empty());
+    assert_eq!(diff.added_events.len(), 1);
+    assert_eq!(diff.added_events[0].message(), Some("extra event"));
+}
+
+#[test]
+fn diffing_captures_with_different_recording_order() {
+    fn capture(reverse: bool, debug: bool) -> SharedStorage {
+        use tracing::field::Empty;
+
+        let storage = SharedStorage::default();
+        let subscriber = Registry::default().with(CaptureLayer::new(&storage));
+        tracing::subscriber::with_default(subscriber, || {
+            let span = if debug {
+                tracing::debug_span!("compute", a = Empty, b = Empty)
+            } else {
+                tracing::info_span!("compute", a = Empty, b = Empty)
+            };
+            if reverse {
+                span.record("b", 2_u64).record("a", 1_u64);
+            } else {
+                span.record("a", 1_u64).record("b", 2_u64);
+            }
+        });
+        storage
+    }
+
+    let golden = capture(false, false);
+    let reversed = capture(true, false);
+    let (golden, reversed) = (golden.lock(), reversed.lock());
+    assert!(golden.diff(&reversed).is_empty());
+
+    let debug = capture(false, true);
+    let debug = debug.lock();
+    let diff = golden.diff(&debug);
+    assert_eq!(diff.removed_spans.len(), 1);
+    assert_eq!(diff.added_spans.len(), 1);
+    assert_eq!(*diff.added_spans[0].metadata().level(), Level::DEBUG);
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn converting_events_to_json() {
+    use serde_json::json;
+
+    let storage = SharedStorage::default();
+    let subscriber = Registry::default().with(CaptureLayer::new(&storage));
+    tracing::subscriber::with_default(subscriber, || fib::fib(5));
+
+    let storage = storage.lock();
+    let event = storage
+        .scan_events()
+        .single(&message(eq("computed Fibonacci number")));
+    assert_eq!(
+        event.to_json(),
+        json!({
+            "level": "INFO",
+            "target": "integration::fib",
+            "message": "computed Fibonacci number",
+            "fields": { "result": { "u_int": 5 } },
+        })
+    );
+
+    let event = storage.scan_events().first(&field("i", 0_u64));
+    let json = event.to_json();
+    assert_eq!(json["level"], "DEBUG");
+    assert_eq!(json["target"], "fib");
+    assert_eq!(
+        json["fields"],
+        json!({ "i": { "u_int": 0 }, "current": { "u_int": 0 } })
+    );
+}
+
+#[cfg(feature = "json")]
+#[derive(Debug, Clone, Default)]
+struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+#[cfg(feature = "json")]
+impl SharedBuffer {
+    fn json_lines(&self) -> Vec<serde_json::Value> {
+        let buffer = self.0.lock().unwrap();
+        let lines = std::str::from_utf8(&buffer).unwrap().lines();
+        lines
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect()
+    }
+}
+
+#[cfg(feature = "json")]
+impl std::io::Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn exporting_json_lines() {
+    let buffer = SharedBuffer::default();
+    let storage = SharedStorage::default();
+    let layer = CaptureLayer::new(&storage).with_json_writer(buffer.clone());
+    let subscriber = Registry::default().with(layer);
+    tracing::subscriber::with_default(subscriber, || {
+        let span = tracing::info_span!("compute", count = 3, result = tracing::field::Empty);
+        span.in_scope(|| tracing::info!(i = 1, "iteration"));
+        span.record("result", 42);
+    });
+
+    let lines = buffer.json_lines();
+    let ops: Vec<_> = lines
+        .iter()
+        .map(|line| line["op"].as_str().unwrap())
+        .collect();
+    assert_eq!(
+        ops,
+        ["new_span", "enter", "event", "exit", "record", "close"]
+    );
+
+    assert_eq!(
+        lines[0],
+        serde_json::json!({
+            "op": "new_span",
+            "id": 0,
+            "parent": null,
+            "name": "compute",
+            "level": "INFO",
+            "target": "integration",
+            "fields": { "count": { "int": 3 } },
+        })
+    );
+    assert_eq!(lines[2]["parent"], 0);
+    assert_eq!(lines[2]["message"], "iteration");
+    assert_eq!(lines[2]["fields"], serde_json::json!({ "i": { "int": 1 } }));
+    assert_eq!(
+        lines[4]["fields"]["result"],
+        serde_json::json!({ "int": 42 })
+    );
+    assert_eq!(lines[5], serde_json::json!({ "op": "close", "id": 0 }));
+    assert_eq!(storage.lock().all_events().len(), 1);
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn json_writer_is_called_without_storage_lock() {
+    struct StorageReader {
+        storage: SharedStorage,
+        span_counts: Arc<Mutex<Vec<usize>>>,
+    }
+
+    impl std::io::Write for StorageReader {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            // This would deadlock if the storage were locked for write.
+            let span_count = self.storage.lock().all_spans().len();
+            self.span_counts.lock().unwrap().push(span_count);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    let storage = SharedStorage::default();
+    let span_counts = Arc::<Mutex<Vec<usize>>>::default();
+    let writer = StorageReader {
+        storage: storage.clone(),
+        span_counts: Arc::clone(&span_counts),
+    };
+    let layer = CaptureLayer::new(&storage).with_json_writer(writer);
+    let subscriber = Registry::default().with(layer);
+    tracing::subscriber::with_default(subscriber, || {
+        tracing::info_span!("compute").in_scope(|| tracing::info!("done"));
+    });
+
+    let span_counts = span_counts.lock().unwrap();
+    assert_eq!(*span_counts, [1; 5]); // new_span, enter, event, exit, close
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn exporting_chrome_trace() {
+    let storage = SharedStorage::default();
+    let layer = CaptureLayer::new(&storage).with_timing();
+    let subscriber = Registry::default().with(layer);
+    tracing::subscriber::with_default(subscriber, || {
+        fib::fib(5);
+        tracing::info_span!("other", arg = 3).in_scope(|| {
+            tracing::info_span!("nested").in_scope(|| tracing::info!("nested event"));
+        });
+    });
+
+    let storage = storage.lock();
+    let trace: serde_json::Value = serde_json::from_str(&storage.to_chrome_trace()).unwrap();
+    let trace_events = trace["traceEvents"].as_array().unwrap();
+    let begin_count = trace_events
+        .iter()
+        .filter(|event| event["ph"] == "B")
+        .count();
+    let end_count = trace_events
+        .iter()
+        .filter(|event| event["ph"] == "E")
+        .count();
+    assert_eq!(begin_count, storage.all_spans().len());
+    assert_eq!(end_count, begin_count);
+
+    // Check that begin / end markers are properly nested on each track.
+    let mut stacks = HashMap::<u64, Vec<&str>>::new();
+    let mut last_timestamp = 0.0;
+    for event in trace_events {
+        let timestamp = event["ts"].as_f64().unwrap();
+        assert!(timestamp >= last_timestamp, "{trace_events:#?}");
+        last_timestamp = timestamp;
+
+        let stack = stacks.entry(event["tid"].as_u64().unwrap()).or_default();
+        let name = event["name"].as_str().unwrap();
+        if event["ph"] == "B" {
+            stack.push(name);
+        } else {
+            assert_eq!(stack.pop(), Some(name), "{trace_events:#?}");
+        }
+    }
+    // Span trees are sequential, so they are placed on the same track.
+    assert_eq!(stacks.len(), 1);
+    assert!(stacks.values().all(Vec::is_empty));
+
+    let other_begin = trace_events
+        .iter()
+        .find(|event| event["name"] == "other")
+        .unwrap();
+    assert_eq!(
+        other_begin["args"],
+        serde_json::json!({ "arg": { "int": 3 } })
+    );
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn exporting_chrome_trace_with_child_outliving_parent() {
+    let storage = SharedStorage::default();
+    let layer = CaptureLayer::new(&storage).with_timing();
+    let subscriber = Registry::default().with(layer);
+    let _outer = tracing::subscriber::with_default(subscriber, || {
+        let outer = tracing::info_span!("outer");
+        let inner = outer.in_scope(|| tracing::info_span!("inner"));
+        thread::sleep(Duration::from_millis(1));
+        // `outer` is not closed, so it ends when it was last exited, i.e. before `inner` ends.
+        inner.in_scope(|| thread::sleep(Duration::from_millis(1)));
+        outer
+    });
+
+    let storage = storage.lock();
+    let trace: serde_json::Value = serde_json::from_str(&storage.to_chrome_trace()).unwrap();
+    let trace_events = trace["traceEvents"].as_array().unwrap();
+    let markers: Vec<_> = trace_events
+        .iter()
+        .map(|event| {
+            let ph = event["ph"].as_str().unwrap();
+            let name = event["name"].as_str().unwrap();
+            (ph, name, event["tid"].as_u64().unwrap())
+        })
+        .collect();
+    assert_eq!(
+        markers,
+        [
+            ("B", "outer", 1),
+            ("B", "inner", 2),
+            ("E", "outer", 1),
+            ("E", "inner", 2)
+        ]
+    );
+}
+
+#[test]
+fn asserting_span_tree_shape() {
+    let storage = SharedStorage::default();
+    let subscriber = Registry::default().with(CaptureLayer::new(&storage));
+    tracing::subscriber::with_default(subscriber, || fib::fib(5));
+
+    let storage = storage.lock();
+    let expected = span_tree! { "fib" => { "compute" => {} } };
+    assert_eq!(storage.span_tree(), expected);
+    storage.assert_span_tree_shape(&expected);
+
+    let wrong_tree = span_tree! {
+        "fib" => { "compute" => { "iteration" => {} } },
+        "other" => {},
+    };
+    let err = panic::catch_unwind(panic::AssertUnwindSafe(|| {
+        storage.assert_span_tree_shape(&wrong_tree);
+    }))
+    .unwrap_err();
+    let message = err.downcast_ref::<String>().unwrap();
+    assert!(message.starts_with("span tree shape mismatch"), "{message}");
+    assert!(message.contains("\n  fib\n    compute\n"), "{message}");
+    assert!(
+        message.contains("\n-     iteration\n- other\n"),
+        "{message}"
+    );
+}
+
+fn fmt_layer<S>() -> impl Layer<S> + Send + Sync
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    tracing_subscriber::fmt::layer().with_test_writer()
+}
+
+#[derive(Debug, Clone, Copy)]
+enum FmtComposition {
+    CaptureFirst,
+    FmtFirst,
+    Teeing,
+    FmtSubscriber,
+}
+
+impl FmtComposition {
+    const ALL: [Self; 4] = [
+        Self::CaptureFirst,
+        Self::FmtFirst,
+        Self::Teeing,
+        Self::FmtSubscriber,
+    ];
+
+    fn capture_fib(self) -> SharedStorage {
+        let storage = SharedStorage::default();
+        let subscriber: Box<dyn Subscriber + Send + Sync> = match self {
+            Self::CaptureFirst => {
+                let layer = CaptureLayer::new(&storage);
+                Box::new(Registry::default().with(layer).with(fmt_layer()))
+            }
+            Self::FmtFirst => {
+                let layer = CaptureLayer::new(&storage);
+                Box::new(Registry::default().with(fmt_layer()).with(layer))
+            }
+            Self::Teeing => {
+                let layer = CaptureLayer::new(&storage).teeing(fmt_layer());
+                Box::new(Registry::default().with(layer))
+            }
+            Self::FmtSubscriber => {
+                let subscriber = tracing_subscriber::FmtSubscriber::builder()
+                    .with_max_level(Level::TRACE)
+                    .with_test_writer()
+                    .finish();
+                Box::new(subscriber.with(CaptureLayer::new(&storage)))
+            }
+        };
+        tracing::subscriber::with_default(subscriber, || {
+            fib::fib(5);
+            tracing::info_span!("other", arg = ?Some(3)).in_scope(|| {
+                tracing::info!(answer = 42, "outside fib");
+            });
+        });
+        storage
+    }
+}
+
+#[test]
+fn capturing_is_independent_of_fmt_layer_order() {
+    let baseline = FmtComposition::CaptureFirst.capture_fib();
+    let baseline = baseline.lock();
+    assert_eq!(baseline.all_spans().len(), 3);
+    assert_eq!(baseline.all_events().len(), 9);
+    assert!(baseline.unbalanced_spans().next().is_none());
+
+    for composition in FmtComposition::ALL {
+        let storage = composition.capture_fib();
+        let storage = storage.lock();
+        let diff = baseline.diff(&storage);
+        assert!(diff.is_empty(), "{composition:?}: {diff:#?}");
+        assert_eq!(storage.span_tree(), baseline.span_tree(), "{composition:?}");
+
+        let stats: Vec<_> = storage.all_spans().map(|span| span.stats()).collect();
+        let baseline_stats: Vec<_> = baseline.all_spans().map(|span| span.stats()).collect();
+        assert_eq!(stats, baseline_stats, "{composition:?}");
+        let parents: Vec<_> = storage
+            .all_events()
+            .map(|event| event.parent().map(|span| span.metadata().name()))
+            .collect();
+        let baseline_parents: Vec<_> = baseline
+            .all_events()
+            .map(|event| event.parent().map(|span| span.metadata().name()))
+            .collect();
+        assert_eq!(parents, baseline_parents, "{composition:?}");
+    }
+}
+
+/// Subscriber wrapping a `Registry` that hides spans with the "hidden" name from span lookups,
+/// emulating disagreement about span existence between the subscriber and layers.
+#[derive(Debug, Default)]
+struct PartialRegistry(Registry);
+
+impl Subscriber for PartialRegistry {
+    fn register_callsite(&self, metadata: &'static Metadata<'static>) -> Interest {
+        self.0.register_callsite(metadata)
+    }
+
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        self.0.enabled(metadata)
+    }
+
+    fn max_level_hint(&self) -> Option<LevelFilter> {
+        self.0.max_level_hint()
+    }
+
+    fn new_span(&self, span: &Attributes<'_>) -> Id {
+        self.0.new_span(span)
+    }
+
+    fn record(&self, span: &Id, values: &Record<'_>) {
+        self.0.record(span, values);
+    }
+
+    fn record_follows_from(&self, span: &Id, follows: &Id) {
+        self.0.record_follows_from(span, follows);
+    }
+
+    fn event(&self, event: &Event<'_>) {
+        self.0.event(event);
+    }
+
+    fn enter(&self, span: &Id) {
+        self.0.enter(span);
+    }
+
+    fn exit(&self, span: &Id) {
+        self.0.exit(span);
+    }
+
+    fn clone_span(&self, id: &Id) -> Id {
+        self.0.clone_span(id)
+    }
+
+    fn try_close(&self, id: Id) -> bool {
+        self.0.try_close(id)
+    }
+
+    fn current_span(&self) -> Current {
+        self.0.current_span()
+    }
+}
+
+impl<'a> LookupSpan<'a> for PartialRegistry {
+    type Data = <Registry as LookupSpan<'a>>::Data;
+
+    fn span_data(&'a self, id: &Id) -> Option<Self::Data> {
+        let data = self.0.span_data(id)?;
+        (data.metadata().name() != "hidden").then_some(data)
+    }
+}
+
+/// Layer recording `value` in spans when they are closed.
+struct RecordingOnClose;
+
+impl<S: Subscriber + for<'a> LookupSpan<'a>> Layer<S> for RecordingOnClose {
+    fn on_close(&self, id: Id, ctx: Context<'_, S>) {
+        let metadata = ctx.metadata(&id).unwrap();
+        let Some(field) = metadata.fields().field("value") else {
+            return;
+        };
+        let values = [(&field, Some(&42_i64 as &dyn tracing::Value))];
+        let value_set = metadata.fields().value_set(&values);
+        tracing::dispatcher::get_default(|dispatch| {
+            dispatch.record(&id, &Record::new(&value_set));
+        });
+    }
+}
+
+#[test]
+fn values_recorded_after_span_is_closed_are_ignored() {
+    let storage = SharedStorage::default();
+    let subscriber = Registry::default()
+        .with(CaptureLayer::new(&storage))
+        .with(RecordingOnClose);
+    tracing::subscriber::with_default(subscriber, || {
+        let span = tracing::info_span!("test", value = tracing::field::Empty);
+        span.in_scope(|| tracing::info!("in span"));
+        let _closed_span = tracing::info_span!("closed", value = 1_i64).entered();
+    });
+
+    let storage = storage.lock();
+    assert_eq!(storage.all_spans().len(), 2);
+    for span in storage.all_spans() {
+        assert!(span.stats().is_closed);
+    }
+    let span = storage.scan_spans().single(&name(eq("test")));
+    assert_eq!(span.value("value"), None);
+    let span = storage.scan_spans().single(&name(eq("closed")));
+    assert_eq!(span["value"], 1_i64);
+}
+
+#[test]
+fn layer_does_not_panic_on_spans_unknown_to_subscriber() {
+    let storage = SharedStorage::default();
+    let subscriber = PartialRegistry::default().with(CaptureLayer::new(&storage));
+    tracing::subscriber::with_default(subscriber, || {
+        let outer = tracing::info_span!("outer");
+        let hidden = tracing::info_span!("hidden", value = tracing::field::Empty);
+        hidden.record("value", 42_i64);
+        hidden.follows_from(&outer);
+        outer.follows_from(&hidden);
+        hidden.in_scope(|| {
+            outer.in_scope(|| tracing::info!("in outer"));
+        });
+        drop(hidden);
+    });
+
+    let storage = storage.lock();
+    assert_eq!(storage.all_spans().len(), 1);
+    let outer = storage.all_spans().next().unwrap();
+    assert_eq!(outer.metadata().name(), "outer");
+    assert_eq!(outer.stats().entered, 1);
+    assert!(outer.stats().is_closed);
+    assert_eq!(outer.event_count(), 1);
+}
@@ -0,0 +1,265 @@
+//! Tests for storage snapshots.
+
+use assert_matches::assert_matches;
+use predicates::ord::eq;
+use tracing_core::{Level, LevelFilter};
+use tracing_subscriber::{layer::SubscriberExt, Registry};
+
+use std::{ptr, thread, time::Duration};
+
+use super::{assert_captured_spans, fib};
+use tracing_capture::{
+    predicates::{ancestor, field, level, message, name, ScanExt},
+    CaptureLayer, SharedStorage, SnapshotError, SpanStats, Storage, StorageSnapshot, TimelineItem,
+};
+
+fn round_trip(storage: &Storage) -> Storage {
+    let snapshot = storage.snapshot();
+    let json = serde_json::to_string_pretty(&snapshot).unwrap();
+    let snapshot: StorageSnapshot = serde_json::from_str(&json).unwrap();
+    snapshot.restore().unwrap()
+}
+
+#[test]
+fn restoring_storage_from_snapshot() {
+    let storage = SharedStorage::default();
+    let subscriber = Registry::default().with(CaptureLayer::new(&storage));
+    tracing::subscriber::with_default(subscriber, || fib::fib(5));
+
+    let restored = round_trip(&storage.lock());
+    assert_captured_spans(&restored);
+
+    let storage = storage.lock();
+    assert_eq!(restored.all_spans().len(), storage.all_spans().len());
+    assert_eq!(restored.all_events().len(), storage.all_events().len());
+    for (span, restored_span) in storage.all_spans().zip(restored.all_spans()) {
+        assert_eq!(span.metadata().name(), restored_span.metadata().name());
+        assert_eq!(span.stats().entered, restored_span.stats().entered);
+        assert_eq!(span.stats().exited, restored_span.stats().exited);
+        assert_eq!(span.stats().is_closed, restored_span.stats().is_closed);
+        assert_eq!(span.field_count(), restored_span.field_count());
+        assert_eq!(span.raw_id(), restored_span.raw_id());
+        assert_eq!(span.raw_parent_id(), restored_span.raw_parent_id());
+    }
+
+    // Check that the same predicate queries work on both storages.
+    let predicate = level(Level::DEBUG)
+        & message(eq("performing iteration"))
+        & field("i", 3_u64)
+        & ancestor(name(eq("fib")));
+    let event = storage.scan_events().single(&predicate);
+    let restored_event = restored.scan_events().single(&predicate);
+    assert_eq!(event["current"], restored_event["current"]);
+    assert_eq!(
+        restored_event
+            .ancestors()
+            .map(|span| span.metadata().name())
+            .collect::<Vec<_>>(),
+        ["compute", "fib"]
+    );
+}
+
+#[test]
+fn restoring_storage_with_relations() {
+    let storage = SharedStorage::default();
+    let layer = CaptureLayer::new(&storage).with_filter(LevelFilter::INFO);
+    let subscriber = Registry::default().with(layer);
+    let alive_span = tracing::subscriber::with_default(subscriber, || {
+        let main_span = tracing::info_span!("main");
+        let _entered = main_span.clone().entered();
+        let task_span = tracing::info_span!("task", i = 1);
+        task_span.follows_from(&main_span);
+        tracing::debug_span!("filtered").in_scope(|| {
+            tracing::info!(result = 42_i64, "done");
+        });
+        tracing::info!("orphan");
+        tracing::info_span!(parent: None, "alive")
+    });
+
+    let restored = round_trip(&storage.lock());
+    drop(alive_span);
+    let alive_span = restored.scan_spans().single(&name(eq("alive")));
+    assert_eq!(alive_span.stats().entered, 0);
+    assert!(!alive_span.stats().is_closed);
+    let main_span = restored.scan_spans().single(&name(eq("main")));
+    assert_eq!(main_span.stats().entered, 1);
+    assert_eq!(main_span.stats().exited, 1);
+    assert!(main_span.stats().is_closed);
+    let task_span = restored.scan_spans().single(&name(eq("task")));
+    assert_eq!(task_span.parent(), Some(main_span));
+    assert!(task_span.stats().is_closed);
+    assert_eq!(task_span.follows_from().collect::<Vec<_>>(), [main_span]);
+
+    let event = restored.scan_events().single(&field("result", 42_i64));
+    assert_eq!(event.parent(), Some(main_span));
+    assert_eq!(event.skipped_ancestors(), 1);
+    assert_eq!(event.message(), Some("done"));
+    let event = restored.scan_events().single(&message(eq("orphan")));
+    assert_eq!(event.parent(), Some(main_span));
+}
+
+#[test]
+fn restoring_timestamps_and_known_call_sites() {
+    let storage = SharedStorage::default();
+    let layer = CaptureLayer::new(&storage)
+        .with_filter(LevelFilter::INFO)
+        .with_timing()
+        .with_known_call_sites();
+    let subscriber = Registry::default().with(layer);
+    tracing::subscriber::with_default(subscriber, || {
+        let span = tracing::info_span!("sleep");
+        span.in_scope(|| thread::sleep(Duration::from_millis(10)));
+        tracing::debug!("filtered");
+    });
+
+    let restored = round_trip(&storage.lock());
+    let storage = storage.lock();
+    let span = storage.scan_spans().single(&name(eq("sleep")));
+    let restored_span = restored.scan_spans().single(&name(eq("sleep")));
+    let timestamps = span.timestamps().unwrap();
+    let restored_timestamps = restored_span.timestamps().unwrap();
+    let elapsed = timestamps.closed.unwrap() - timestamps.created;
+    let restored_elapsed = restored_timestamps.closed.unwrap() - restored_timestamps.created;
+    assert_eq!(elapsed, restored_elapsed);
+    assert!(restored_elapsed >= Duration::from_millis(10));
+    assert_eq!(span.busy_durations(), restored_span.busy_durations());
+
+    let call_site_names = |storage: &Storage| {
+        let names = storage.known_call_sites().iter();
+        names.map(|metadata| metadata.name()).collect::<Vec<_>>()
+    };
+    assert!(!restored.known_call_sites().is_empty());
+    assert_eq!(call_site_names(&restored), call_site_names(&storage));
+}
+
+#[test]
+fn restoring_timeline() {
+    fn timeline(storage: &Storage) -> Vec<String> {
+        let timeline = storage.timeline().map(|item| match item {
+            TimelineItem::Span(span) => format!("span {}", span.metadata().name()),
+            TimelineItem::Event(event) => event.message().unwrap().to_owned(),
+        });
+        timeline.collect()
+    }
+
+    let storage = SharedStorage::default();
+    let subscriber = Registry::default().with(CaptureLayer::new(&storage));
+    tracing::subscriber::with_default(subscriber, || {
+        tracing::info!("started");
+        tracing::info_span!("outer").in_scope(|| {
+            tracing::info!("outer work");
+            tracing::info_span!("inner").in_scope(|| tracing::info!("inner work"));
+        });
+        tracing::info!("finished");
+    });
+
+    let storage = storage.lock();
+    let restored = round_trip(&storage);
+    assert_eq!(timeline(&restored), timeline(&storage));
+    assert_eq!(
+        timeline(&restored),
+        [
+            "started",
+            "span outer",
+            "outer work",
+            "span inner",
+            "inner work",
+            "finished"
+        ]
+    );
+}
+
+#[test]
+fn restored_metadata_is_reused() {
+    let storage = SharedStorage::default();
+    let subscriber = Registry::default().with(CaptureLayer::new(&storage));
+    tracing::subscriber::with_default(subscriber, || fib::fib(3));
+
+    let snapshot = storage.lock().snapshot();
+    let restored = snapshot.restore().unwrap();
+    let restored_again = snapshot.restore().unwrap();
+    for (span, other_span) in restored.all_spans().zip(restored_again.all_spans()) {
+        assert!(ptr::eq(span.metadata(), other_span.metadata()));
+    }
+    for (event, other_event) in restored.all_events().zip(restored_again.all_events()) {
+        assert!(ptr::eq(event.metadata(), other_event.metadata()));
+    }
+}
+
+#[test]
+fn restoring_malformed_snapshot() {
+    let storage = SharedStorage::default();
+    let subscriber = Registry::default().with(CaptureLayer::new(&storage));
+    tracing::subscriber::with_default(subscriber, || {
+        tracing::info_span!("test", num = 42_i64).in_scope(|| tracing::info!("done"));
+    });
+    let snapshot = serde_json::to_value(storage.lock().snapshot()).unwrap();
+
+    let restore = |snapshot: serde_json::Value| {
+        let snapshot: StorageSnapshot = serde_json::from_value(snapshot).unwrap();
+        snapshot.restore().unwrap_err()
+    };
+
+    let mut malformed = snapshot.clone();
+    malformed["events"][0]["parent"] = 1.into();
+    assert_matches!(restore(malformed), SnapshotError::UnknownSpan(1));
+
+    let mut malformed = snapshot.clone();
+    // The event is placed before its parent span.
+    malformed["events"][0]["seq"] = 0.into();
+    malformed["spans"][0]["seq"] = 1.into();
+    assert_matches!(restore(malformed), SnapshotError::UnknownSpan(0));
+
+    let mut malformed = snapshot.clone();
+    malformed["spans"][0]["call_site"] = 5.into();
+    assert_matches!(restore(malformed), SnapshotError::UnknownCallSite(5));
+
+    let mut malformed = snapshot.clone();
+    let event_call_site = malformed["events"][0]["call_site"].clone();
+    malformed["spans"][0]["call_site"] = event_call_site;
+    assert_matches!(restore(malformed), SnapshotError::CallSiteKindMismatch(_));
+
+    let mut malformed = snapshot;
+    let value = malformed["spans"][0]["values"]["num"].clone();
+    malformed["spans"][0]["values"]["other"] = value;
+    let err = restore(malformed);
+    assert_matches!(
+        &err,
+        SnapshotError::UnknownField { call_site: "test", name } if name == "other"
+    );
+    assert_eq!(
+        err.to_string(),
+        "value `other` is not a field of call site `test`"
+    );
+}
+
+#[test]
+fn span_stats_serde_round_trip() {
+    let storage = SharedStorage::default();
+    let subscriber = Registry::default().with(CaptureLayer::new(&storage));
+    tracing::subscriber::with_default(subscriber, || {
+        let span = tracing::info_span!("test");
+        span.in_scope(|| { /* do nothing */ });
+        span.in_scope(|| { /* do nothing */ });
+    });
+
+    let storage = storage.lock();
+    let stats = storage.all_spans().next().unwrap().stats();
+    let json = serde_json::to_value(stats).unwrap();
+    assert_eq!(
+        json,
+        serde_json::json!({ "entered": 2, "exited": 2, "is_closed": true })
+    );
+    let restored: SpanStats = serde_json::from_value(json).unwrap();
+    assert_eq!(restored, stats);
+
+    // Unknown fields are ignored, and missing fields are set to defaults.
+    let json = serde_json::json!({ "entered": 1, "polled": 3 });
+    let restored: SpanStats = serde_json::from_value(json).unwrap();
+    assert_eq!(restored.entered, 1);
+    assert_eq!(restored.exited, 0);
+    assert!(!restored.is_closed);
+
+    let restored = round_trip(&storage);
+    assert_eq!(restored.all_spans().next().unwrap().stats(), stats);
+}
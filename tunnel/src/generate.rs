@@ -0,0 +1,340 @@
+//! Generation of random, but valid streams of [`TracingEvent`]s.
+//!
+//! This module is useful to property-test consumers of tracing events (e.g., custom
+//! [`TracingEventReceiver`](crate::TracingEventReceiver) wrappers) against a wide variety
+//! of event streams without instrumenting real code.
+
+use crate::{
+    alloc::{format, String, Vec},
+    CallSiteData, CallSiteKind, MetadataId, RawSpanId, TracedValue, TracedValues, TracingEvent,
+    TracingLevel,
+};
+
+/// Minimal `SplitMix64` pseudo-random number generator. We don't need cryptographic
+/// (or even statistical) quality, only reproducibility for a given seed.
+#[derive(Debug)]
+struct Rng(u64);
+
+impl Rng {
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Returns a number in `0..bound`. `bound` must be positive.
+    #[allow(clippy::cast_possible_truncation)] // the result is less than `bound`
+    fn below(&mut self, bound: usize) -> usize {
+        (self.next_u64() % bound as u64) as usize
+    }
+
+    fn chance(&mut self, numerator: usize, denominator: usize) -> bool {
+        self.below(denominator) < numerator
+    }
+
+    fn choose<'a, T>(&mut self, items: &'a [T]) -> Option<&'a T> {
+        if items.is_empty() {
+            None
+        } else {
+            Some(&items[self.below(items.len())])
+        }
+    }
+}
+
+#[derive(Debug)]
+struct CallSite {
+    id: MetadataId,
+    fields: Vec<String>,
+}
+
+#[derive(Debug)]
+struct AliveSpan {
+    id: RawSpanId,
+    metadata_id: MetadataId,
+    parent_id: Option<RawSpanId>,
+    child_count: usize,
+}
+
+/// Generator state tracking call sites and span lifecycle.
+#[derive(Debug)]
+struct StreamGenerator {
+    rng: Rng,
+    span_call_sites: Vec<CallSite>,
+    event_call_sites: Vec<CallSite>,
+    /// Alive spans in the order of their creation.
+    alive_spans: Vec<AliveSpan>,
+    entered_spans: Vec<RawSpanId>,
+    next_span_id: RawSpanId,
+    events: Vec<TracingEvent>,
+}
+
+impl StreamGenerator {
+    const LEVELS: [TracingLevel; 5] = [
+        TracingLevel::Error,
+        TracingLevel::Warn,
+        TracingLevel::Info,
+        TracingLevel::Debug,
+        TracingLevel::Trace,
+    ];
+
+    fn new(seed: u64) -> Self {
+        Self {
+            rng: Rng(seed),
+            span_call_sites: Vec::new(),
+            event_call_sites: Vec::new(),
+            alive_spans: Vec::new(),
+            entered_spans: Vec::new(),
+            next_span_id: 1,
+            events: Vec::new(),
+        }
+    }
+
+    fn generate_call_sites(&mut self) {
+        let span_count = 1 + self.rng.below(4);
+        let event_count = 1 + self.rng.below(4);
+        for i in 0..span_count + event_count {
+            let (kind, name) = if i < span_count {
+                (CallSiteKind::Span, format!("span{i}"))
+            } else {
+                (CallSiteKind::Event, format!("event{}", i - span_count))
+            };
+            let level = Self::LEVELS[self.rng.below(Self::LEVELS.len())];
+            let mut data = CallSiteData::new(kind, name, "tracing_tunnel::generate", level)
+                .with_module_path("tracing_tunnel::generate");
+            let mut fields = Vec::new();
+            if matches!(kind, CallSiteKind::Event) {
+                fields.push(String::from("message"));
+            }
+            fields.extend((0..self.rng.below(4)).map(|j| format!("field{j}")));
+            for field in &fields {
+                data = data.with_field(field.clone());
+            }
+
+            let id = i as MetadataId;
+            self.events.push(TracingEvent::new_call_site(id, data));
+            let call_site = CallSite { id, fields };
+            if matches!(kind, CallSiteKind::Span) {
+                self.span_call_sites.push(call_site);
+            } else {
+                self.event_call_sites.push(call_site);
+            }
+        }
+    }
+
+    fn generate_value(&mut self) -> TracedValue {
+        let raw = self.rng.next_u64();
+        match self.rng.below(5) {
+            0 => TracedValue::Bool(raw % 2 == 0),
+            #[allow(clippy::cast_possible_wrap)] // intentional
+            1 => TracedValue::Int(i128::from(raw as i64)),
+            2 => TracedValue::UInt(u128::from(raw)),
+            #[allow(clippy::cast_precision_loss)] // intentional
+            3 => TracedValue::Float((raw % 1_000_000) as f64 / 8.0),
+            _ => TracedValue::String(format!("value{}", raw % 100)),
+        }
+    }
+
+    /// Generates values for a random subset of `fields`.
+    fn generate_values(&mut self, fields: &[String]) -> TracedValues<String> {
+        let mut values = TracedValues::new();
+        for field in fields {
+            if self.rng.chance(2, 3) {
+                let value = self.generate_value();
+                values.insert(field.clone(), value);
+            }
+        }
+        values
+    }
+
+    fn random_parent(&mut self) -> Option<RawSpanId> {
+        match self.rng.below(3) {
+            0 => self.entered_spans.last().copied(),
+            1 => {
+                let alive_ids: Vec<_> = self.alive_spans.iter().map(|span| span.id).collect();
+                self.rng.choose(&alive_ids).copied()
+            }
+            _ => None,
+        }
+    }
+
+    fn new_span(&mut self) {
+        let call_site_idx = self.rng.below(self.span_call_sites.len());
+        let metadata_id = self.span_call_sites[call_site_idx].id;
+        let fields = self.span_call_sites[call_site_idx].fields.clone();
+        let values = self.generate_values(&fields);
+        let parent_id = self.random_parent();
+        if let Some(parent_id) = parent_id {
+            let parent = self
+                .alive_spans
+                .iter_mut()
+                .find(|span| span.id == parent_id);
+            parent.unwrap().child_count += 1;
+        }
+
+        let id = self.next_span_id;
+        self.next_span_id += 1;
+        self.alive_spans.push(AliveSpan {
+            id,
+            metadata_id,
+            parent_id,
+            child_count: 0,
+        });
+        let event = TracingEvent::new_span(id, parent_id, metadata_id, values);
+        self.events.push(event);
+    }
+
+    fn enter_span(&mut self) {
+        let candidates: Vec<_> = self
+            .alive_spans
+            .iter()
+            .map(|span| span.id)
+            .filter(|id| !self.entered_spans.contains(id))
+            .collect();
+        if let Some(&id) = self.rng.choose(&candidates) {
+            self.entered_spans.push(id);
+            self.events.push(TracingEvent::span_entered(id));
+        }
+    }
+
+    fn exit_span(&mut self) {
+        if let Some(id) = self.entered_spans.pop() {
+            self.events.push(TracingEvent::span_exited(id));
+        }
+    }
+
+    fn drop_span(&mut self) {
+        let candidates: Vec<_> = self
+            .alive_spans
+            .iter()
+            .enumerate()
+            .filter(|(_, span)| span.child_count == 0 && !self.entered_spans.contains(&span.id))
+            .map(|(idx, _)| idx)
+            .collect();
+        if let Some(&idx) = self.rng.choose(&candidates) {
+            self.remove_span(idx);
+        }
+    }
+
+    fn remove_span(&mut self, idx: usize) {
+        let span = self.alive_spans.remove(idx);
+        if let Some(parent_id) = span.parent_id {
+            let parent = self
+                .alive_spans
+                .iter_mut()
+                .find(|span| span.id == parent_id);
+            parent.unwrap().child_count -= 1;
+        }
+        self.events.push(TracingEvent::span_dropped(span.id));
+    }
+
+    fn record_values(&mut self) {
+        if self.alive_spans.is_empty() {
+            return;
+        }
+        let span = &self.alive_spans[self.rng.below(self.alive_spans.len())];
+        let id = span.id;
+        let call_site = self
+            .span_call_sites
+            .iter()
+            .find(|call_site| call_site.id == span.metadata_id);
+        let fields = call_site.unwrap().fields.clone();
+        let values = self.generate_values(&fields);
+        if !values.is_empty() {
+            self.events.push(TracingEvent::values_recorded(id, values));
+        }
+    }
+
+    fn follows_from(&mut self) {
+        let alive_ids: Vec<_> = self.alive_spans.iter().map(|span| span.id).collect();
+        let id = self.rng.choose(&alive_ids).copied();
+        let follows_from = self.rng.choose(&alive_ids).copied();
+        if let (Some(id), Some(follows_from)) = (id, follows_from) {
+            if id != follows_from {
+                self.events
+                    .push(TracingEvent::follows_from(id, follows_from));
+            }
+        }
+    }
+
+    fn new_event(&mut self) {
+        let call_site_idx = self.rng.below(self.event_call_sites.len());
+        let metadata_id = self.event_call_sites[call_site_idx].id;
+        let fields = self.event_call_sites[call_site_idx].fields.clone();
+        let mut values = self.generate_values(&fields[1..]);
+        let message = format!("message #{}", self.events.len());
+        values.insert(String::from("message"), TracedValue::String(message));
+        let parent = self.random_parent();
+        self.events
+            .push(TracingEvent::new_event(metadata_id, parent, values));
+    }
+
+    fn generate(mut self) -> Vec<TracingEvent> {
+        self.generate_call_sites();
+        let step_count = 16 + self.rng.below(112);
+        for _ in 0..step_count {
+            match self.rng.below(10) {
+                0 | 1 => self.new_span(),
+                2 | 3 => self.enter_span(),
+                4 => self.exit_span(),
+                5 => self.drop_span(),
+                6 => self.record_values(),
+                7 => self.follows_from(),
+                _ => self.new_event(),
+            }
+        }
+
+        // Finalize the stream so that all spans are exited and dropped.
+        while !self.entered_spans.is_empty() {
+            self.exit_span();
+        }
+        // Children are always created after their parents, so dropping spans in the reverse
+        // creation order never drops a parent before its children.
+        while !self.alive_spans.is_empty() {
+            self.remove_span(self.alive_spans.len() - 1);
+        }
+        self.events
+    }
+}
+
+/// Generates a random, but valid stream of [`TracingEvent`]s deterministically derived
+/// from the provided `seed`.
+///
+/// The stream satisfies the following invariants:
+///
+/// - All call sites are declared (via [`TracingEvent::NewCallSite`]) before they are referenced.
+///   Span and event values only reference fields declared by the corresponding call site.
+/// - Spans are only referenced while they are alive (i.e., after
+///   [`TracingEvent::NewSpan`] and before [`TracingEvent::SpanDropped`]).
+/// - Spans are entered and exited in the stack order, and an entered span is not re-entered
+///   before it is exited.
+/// - A span is not dropped while it is entered or while any of its children are alive.
+/// - At the end of the stream, all spans are exited and dropped.
+///
+/// Thus, the stream can be replayed by a [`TracingEventReceiver`] without errors, including
+/// with [strict field checks](crate::TracingEventReceiver::with_strict_fields()).
+///
+/// [`TracingEventReceiver`]: crate::TracingEventReceiver
+///
+/// # Examples
+///
+/// ```
+/// # use tracing_tunnel::{generate, TracingEvent};
+/// let events = generate::valid_stream(42);
+/// // Generation is deterministic.
+/// assert_eq!(format!("{events:?}"), format!("{:?}", generate::valid_stream(42)));
+///
+/// let span_count = events
+///     .iter()
+///     .filter(|event| matches!(event, TracingEvent::NewSpan { .. }))
+///     .count();
+/// let dropped_span_count = events
+///     .iter()
+///     .filter(|event| matches!(event, TracingEvent::SpanDropped { .. }))
+///     .count();
+/// assert_eq!(span_count, dropped_span_count);
+/// ```
+pub fn valid_stream(seed: u64) -> Vec<TracingEvent> {
+    StreamGenerator::new(seed).generate()
+}
@@ -0,0 +1,207 @@
+//! Conversions between `TracedValues` and plain JSON objects.
+
+use serde_json::{Map, Number, Value};
+
+use std::{error, fmt};
+
+use crate::{TracedValue, TracedValues};
+
+/// Error converting a JSON value into [`TracedValues`] returned by
+/// [`TracedValues::from_json_object()`].
+#[derive(Debug)]
+#[non_exhaustive]
+#[cfg_attr(docsrs, doc(cfg(feature = "json")))]
+pub enum FromJsonError {
+    /// The converted JSON value is not an object.
+    NotAnObject,
+    /// A field has a value that cannot be converted to a [`TracedValue`] (an array or an object).
+    UnsupportedValue {
+        /// Name of the field.
+        name: String,
+    },
+}
+
+impl fmt::Display for FromJsonError {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotAnObject => formatter.write_str("JSON value is not an object"),
+            Self::UnsupportedValue { name } => write!(
+                formatter,
+                "value of field `{name}` is an array or an object, which is not supported"
+            ),
+        }
+    }
+}
+
+impl error::Error for FromJsonError {}
+
+fn value_from_json(value: &Value) -> Option<TracedValue> {
+    Some(match value {
+        Value::Null => TracedValue::debug(&None::<()>),
+        Value::Bool(value) => TracedValue::Bool(*value),
+        Value::Number(number) => {
+            if let Some(value) = number.as_i64() {
+                TracedValue::Int(value.into())
+            } else if let Some(value) = number.as_u64() {
+                TracedValue::UInt(value.into())
+            } else {
+                TracedValue::Float(number.as_f64()?)
+            }
+        }
+        Value::String(value) => TracedValue::String(value.clone()),
+        Value::Array(_) | Value::Object(_) => return None,
+    })
+}
+
+fn value_to_json(value: &TracedValue) -> Value {
+    match value {
+        TracedValue::Bool(value) => Value::Bool(*value),
+        TracedValue::Int(value) => {
+            i64::try_from(*value).map_or_else(|_| Value::String(value.to_string()), Value::from)
+        }
+        TracedValue::UInt(value) => {
+            u64::try_from(*value).map_or_else(|_| Value::String(value.to_string()), Value::from)
+        }
+        TracedValue::Float(value) => Number::from_f64(*value).map_or(Value::Null, Value::Number),
+        TracedValue::String(value) => Value::String(value.clone()),
+        TracedValue::Object(_) if value.is_none_debug() => Value::Null,
+        TracedValue::Object(object) => Value::String(object.as_ref().to_owned()),
+        TracedValue::Error(err) => Value::String(err.to_string()),
+    }
+}
+
+impl TracedValues<String> {
+    /// Creates values from a plain JSON object. This is useful for data-driven tests,
+    /// e.g., to construct synthetic spans / events from JSON fixtures.
+    ///
+    /// JSON values are converted as follows:
+    ///
+    /// - Booleans and strings are converted to [`TracedValue::Bool`] and [`TracedValue::String`].
+    /// - Integers are converted to [`TracedValue::Int`] if they fit into `i64` (which is how
+    ///   `tracing` records most integers), and to [`TracedValue::UInt`] otherwise.
+    /// - Other numbers are converted to [`TracedValue::Float`].
+    /// - `null` is converted to the [`Debug`](fmt::Debug) presentation of `None`
+    ///   (see [`TracedValue::is_none_debug()`]).
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `value` is not an object, or if any of its fields is an array
+    /// or an object.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use serde_json::json;
+    /// # use tracing_tunnel::{TracedValue, TracedValues};
+    /// let json = json!({ "status": 200, "path": "/", "latency": 0.5, "user": null });
+    /// let values = TracedValues::from_json_object(&json)?;
+    /// assert_eq!(values["status"], 200_i64);
+    /// assert_eq!(values["path"], "/");
+    /// assert_eq!(values["latency"], 0.5);
+    /// assert!(values["user"].is_none_debug());
+    ///
+    /// assert_eq!(values.to_json_object(), json);
+    /// # Ok::<_, tracing_tunnel::FromJsonError>(())
+    /// ```
+    #[cfg_attr(docsrs, doc(cfg(feature = "json")))]
+    pub fn from_json_object(value: &Value) -> Result<Self, FromJsonError> {
+        let object = value.as_object().ok_or(FromJsonError::NotAnObject)?;
+        object
+            .iter()
+            .map(|(name, value)| {
+                let value = value_from_json(value)
+                    .ok_or_else(|| FromJsonError::UnsupportedValue { name: name.clone() })?;
+                Ok((name.clone(), value))
+            })
+            .collect()
+    }
+}
+
+impl<S: AsRef<str>> TracedValues<S> {
+    /// Converts these values into a plain JSON object. This is the inverse
+    /// of [`TracedValues::from_json_object()`]; JSON objects produced by this method
+    /// can be converted back without losses, except for the following cases:
+    ///
+    /// - [`TracedValue::Int`]s and [`TracedValue::UInt`]s are converted to JSON integers,
+    ///   so their variant is determined by the value when converting back. Integers not fitting
+    ///   into `i64` / `u64` are converted to strings.
+    /// - Non-finite [`TracedValue::Float`]s are converted to `null`.
+    /// - [`TracedValue::Object`]s are converted to their [`Debug`](fmt::Debug) presentation
+    ///   (or `null` for `None`), and [`TracedValue::Error`]s to their messages.
+    ///
+    /// Unlike the [`Serialize`](serde::Serialize) implementation, this method does not
+    /// preserve value types, but produces JSON that is easier to read and write manually.
+    #[cfg_attr(docsrs, doc(cfg(feature = "json")))]
+    pub fn to_json_object(&self) -> Value {
+        let object: Map<_, _> = self
+            .iter()
+            .map(|(name, value)| (name.to_owned(), value_to_json(value)))
+            .collect();
+        Value::Object(object)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use assert_matches::assert_matches;
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn values_round_trip() {
+        let json = json!({
+            "bool": true,
+            "int": -5,
+            "small_uint": 42,
+            "uint": u64::MAX,
+            "float": 1.5,
+            "str": "test",
+            "none": null,
+        });
+        let values = TracedValues::from_json_object(&json).unwrap();
+        assert_eq!(values.len(), 7);
+        assert_eq!(values["bool"], true);
+        assert_eq!(values["int"], -5_i64);
+        assert_eq!(values["small_uint"], 42_i64);
+        assert_eq!(values["uint"], u64::MAX);
+        assert_eq!(values["float"], 1.5);
+        assert_eq!(values["str"], "test");
+        assert!(values["none"].is_none_debug());
+        assert_eq!(values.to_json_object(), json);
+
+        let restored = TracedValues::from_json_object(&values.to_json_object()).unwrap();
+        assert_eq!(restored, values);
+    }
+
+    #[test]
+    fn lossy_conversions_to_json() {
+        let values = TracedValues::from_iter([
+            ("big", TracedValue::Int(i128::MIN)),
+            ("nan", TracedValue::Float(f64::NAN)),
+            ("object", TracedValue::debug(&Some(3))),
+        ]);
+        assert_eq!(
+            values.to_json_object(),
+            json!({
+                "big": i128::MIN.to_string(),
+                "nan": null,
+                "object": "Some(3)",
+            })
+        );
+    }
+
+    #[test]
+    fn errors_converting_from_json() {
+        let err = TracedValues::from_json_object(&json!([1, 2])).unwrap_err();
+        assert_matches!(err, FromJsonError::NotAnObject);
+
+        let err = TracedValues::from_json_object(&json!({ "ok": 1, "list": [1] })).unwrap_err();
+        assert_matches!(err, FromJsonError::UnsupportedValue { name } if name == "list");
+        let err = TracedValues::from_json_object(&json!({ "obj": {} })).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "value of field `obj` is an array or an object, which is not supported"
+        );
+    }
+}
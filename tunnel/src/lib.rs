@@ -79,6 +79,20 @@
 //!
 //! Provides [`TracingEventReceiver`] and related types.
 //!
+//! ## `json`
+//!
+//! *(Off by default; requires `std`)*
+//!
+//! Provides conversions between [`TracedValues`] and plain JSON objects,
+//! e.g., for data-driven tests.
+//!
+//! ## `test-util`
+//!
+//! *(Off by default)*
+//!
+//! Provides the [`generate`] module with a generator of random, but valid [`TracingEvent`]
+//! streams, e.g., for property testing of event consumers.
+//!
 //! [`tracing-core`]: https://docs.rs/tracing-core/0.1/tracing_core
 //!
 //! # Examples
@@ -151,9 +165,15 @@
 #![warn(clippy::all, clippy::pedantic)]
 #![allow(clippy::must_use_candidate, clippy::module_name_repetitions)]
 
+#[cfg(feature = "test-util")]
+#[cfg_attr(docsrs, doc(cfg(feature = "test-util")))]
+pub mod generate;
+#[cfg(feature = "json")]
+mod json;
 #[cfg(feature = "receiver")]
 #[cfg_attr(docsrs, doc(cfg(feature = "receiver")))]
 mod receiver;
+mod render;
 #[cfg(feature = "sender")]
 #[cfg_attr(docsrs, doc(cfg(feature = "sender")))]
 mod sender;
@@ -170,7 +190,7 @@ mod alloc {
 
     pub use alloc::{
         borrow::{Cow, ToOwned},
-        collections::BTreeMap,
+        collections::{BTreeMap, BTreeSet},
         // ^ `HashMap` would work better, but it's not present in `alloc`
         format,
         string::String,
@@ -178,18 +198,26 @@ mod alloc {
     };
 }
 
+#[cfg(feature = "json")]
+pub use crate::json::FromJsonError;
 #[cfg(feature = "receiver")]
 pub use crate::receiver::{
-    LocalSpans, PersistedMetadata, PersistedSpans, ReceiveError, TracingEventReceiver,
+    LocalSpans, PersistedMetadata, PersistedSpans, ReceiveError, ReceiveErrorAt, ReceiverStats,
+    TracingEventReceiver,
 };
+#[cfg(all(feature = "sender", feature = "std"))]
+pub use crate::sender::RingHandle;
 #[cfg(feature = "sender")]
 pub use crate::sender::TracingEventSender;
 #[cfg(feature = "std")]
 pub use crate::value::TracedError;
 pub use crate::{
-    types::{CallSiteData, CallSiteKind, MetadataId, RawSpanId, TracingEvent, TracingLevel},
+    types::{
+        CallSiteData, CallSiteKind, MetadataId, RawSpanId, TimedTracingEvent, TracingEvent,
+        TracingLevel,
+    },
     value::{DebugObject, FromTracedValue, TracedValue},
-    values::{TracedValues, TracedValuesIter},
+    values::{ConversionOptions, DebugValueConverter, TracedValues, TracedValuesIter},
 };
 
 #[cfg(doctest)]
@@ -9,8 +9,11 @@ use tracing_core::{
 };
 
 use std::{
+    borrow::Cow,
+    cell::Cell,
     collections::{HashMap, HashSet},
-    error, fmt, mem,
+    error, fmt, mem, thread,
+    time::{Duration, Instant},
 };
 
 mod arena;
@@ -18,7 +21,14 @@ mod arena;
 mod tests;
 
 use self::arena::ARENA;
-use crate::{CallSiteData, MetadataId, RawSpanId, TracedValue, TracedValues, TracingEvent};
+use crate::{
+    CallSiteData, MetadataId, RawSpanId, TimedTracingEvent, TracedValue, TracedValues, TracingEvent,
+};
+
+thread_local! {
+    /// Source span ID for the span being currently created by a receiver on this thread.
+    static SOURCE_SPAN_ID: Cell<Option<RawSpanId>> = const { Cell::new(None) };
+}
 
 enum CowValue<'a> {
     Borrowed(&'a dyn Value),
@@ -60,6 +70,24 @@ impl TracedValue {
     }
 }
 
+impl TracingEvent {
+    /// Returns the `snake_case` name of the event variant, as used in its serialized form.
+    pub(crate) fn kind_str(&self) -> &'static str {
+        match self {
+            Self::NewCallSite { .. } => "new_call_site",
+            Self::NewSpan { .. } => "new_span",
+            Self::FollowsFrom { .. } => "follows_from",
+            Self::SpanEntered { .. } => "span_entered",
+            Self::SpanExited { .. } => "span_exited",
+            Self::SpanCloned { .. } => "span_cloned",
+            Self::SpanDropped { .. } => "span_dropped",
+            Self::ValuesRecorded { .. } => "values_recorded",
+            Self::NewEvent { .. } => "new_event",
+            Self::Flush => "flush",
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 struct SpanData {
     metadata_id: MetadataId,
@@ -69,6 +97,24 @@ struct SpanData {
     values: TracedValues<String>,
 }
 
+/// Statistics about events processed by a [`TracingEventReceiver`], returned by
+/// [`TracingEventReceiver::stats()`].
+///
+/// Can be used to monitor throughput and error rates of event streams, e.g. to diagnose
+/// stuck or misbehaving [`TracingEventSender`]s.
+///
+/// [`TracingEventSender`]: crate::TracingEventSender
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ReceiverStats {
+    /// Total number of events passed to the receiver, including ones that resulted in an error.
+    pub events_received: usize,
+    /// Number of successfully processed [`NewSpan`](TracingEvent::NewSpan) events.
+    pub spans_created: usize,
+    /// Number of events that resulted in a [`ReceiveError`].
+    pub errors: usize,
+}
+
 /// Information about span / event [`Metadata`] that is [serializable] and thus
 /// can be persisted across multiple [`TracingEventReceiver`] lifetimes.
 ///
@@ -161,6 +207,20 @@ pub enum ReceiveError {
         /// Actual number of values.
         actual: usize,
     },
+    /// The event would create a span exceeding the limit on the number of alive spans.
+    /// See [`TracingEventReceiver::with_max_alive_spans()`].
+    TooManySpans {
+        /// Maximum supported number of alive spans.
+        max: usize,
+    },
+    /// The event contains a value for a field not declared in the call site metadata.
+    /// Only returned if the receiver is [strict about fields](TracingEventReceiver::with_strict_fields()).
+    UnknownField {
+        /// ID of the call site metadata.
+        metadata_id: MetadataId,
+        /// Name of the unknown field.
+        name: String,
+    },
 }
 
 impl fmt::Display for ReceiveError {
@@ -172,12 +232,52 @@ impl fmt::Display for ReceiveError {
                 formatter,
                 "too many values provided ({actual}), should be no more than {max}"
             ),
+            Self::TooManySpans { max } => {
+                write!(
+                    formatter,
+                    "too many alive spans, should be no more than {max}"
+                )
+            }
+            Self::UnknownField { metadata_id, name } => write!(
+                formatter,
+                "field `{name}` is not declared in metadata with ID {metadata_id}"
+            ),
         }
     }
 }
 
 impl error::Error for ReceiveError {}
 
+/// [`ReceiveError`] together with the information about the [`TracingEvent`] that has caused it.
+/// Returned by [`TracingEventReceiver::try_receive_at()`].
+#[derive(Debug)]
+#[non_exhaustive]
+pub struct ReceiveErrorAt {
+    /// Zero-based index of the event in the processed event stream, if known.
+    pub index: Option<usize>,
+    /// Kind of the event, such as `new_span`. Corresponds to the event tag
+    /// in the serialized presentation of [`TracingEvent`].
+    pub event_kind: &'static str,
+    /// Underlying error.
+    pub source: ReceiveError,
+}
+
+impl fmt::Display for ReceiveErrorAt {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(formatter, "failed processing `{}` event", self.event_kind)?;
+        if let Some(index) = self.index {
+            write!(formatter, " at index {index}")?;
+        }
+        write!(formatter, ": {}", self.source)
+    }
+}
+
+impl error::Error for ReceiveErrorAt {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        Some(&self.source)
+    }
+}
+
 macro_rules! create_value_set {
     ($fields:ident, $values:ident, [$($i:expr,)+]) => {
         match $values.len() {
@@ -251,12 +351,34 @@ impl CurrentExecution {
 /// [`TracingEventSender`]: crate::TracingEventSender
 /// [the Tardigrade runtime]: https://github.com/slowli/tardigrade
 /// [`tracing-core`]: https://docs.rs/tracing-core/
-#[derive(Debug, Default)]
+#[derive(Default)]
 pub struct TracingEventReceiver {
     metadata: HashMap<MetadataId, &'static Metadata<'static>>,
     spans: PersistedSpans,
     local_spans: LocalSpans,
     current_execution: CurrentExecution,
+    max_alive_spans: Option<usize>,
+    strict_fields: bool,
+    target_mapper: Option<Box<TargetMapperFn>>,
+    stats: ReceiverStats,
+}
+
+type TargetMapperFn = dyn Fn(&str) -> Cow<'_, str> + Send + Sync;
+
+impl fmt::Debug for TracingEventReceiver {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter
+            .debug_struct("TracingEventReceiver")
+            .field("metadata", &self.metadata)
+            .field("spans", &self.spans)
+            .field("local_spans", &self.local_spans)
+            .field("current_execution", &self.current_execution)
+            .field("max_alive_spans", &self.max_alive_spans)
+            .field("strict_fields", &self.strict_fields)
+            .field("target_mapper", &self.target_mapper.as_ref().map(|_| "Fn"))
+            .field("stats", &self.stats)
+            .finish()
+    }
 }
 
 impl TracingEventReceiver {
@@ -282,6 +404,10 @@ impl TracingEventReceiver {
             spans,
             local_spans,
             current_execution: CurrentExecution::default(),
+            max_alive_spans: None,
+            strict_fields: false,
+            target_mapper: None,
+            stats: ReceiverStats::default(),
         };
 
         for (id, data) in metadata.inner {
@@ -290,6 +416,84 @@ impl TracingEventReceiver {
         this
     }
 
+    /// Sets the maximum number of alive spans. If a [`TracingEvent::NewSpan`] event would create
+    /// a span exceeding this limit, it will be rejected with [`ReceiveError::TooManySpans`].
+    /// This protects against misbehaving [`TracingEventSender`]s creating an unbounded number
+    /// of spans. By default, the number of alive spans is not limited.
+    ///
+    /// The limit applies to all alive spans, including ones restored via [`Self::new()`].
+    ///
+    /// [`TracingEventSender`]: crate::TracingEventSender
+    #[must_use]
+    pub fn with_max_alive_spans(mut self, max: usize) -> Self {
+        self.max_alive_spans = Some(max);
+        self
+    }
+
+    /// Makes the receiver strict about span / event fields. By default, values for fields
+    /// not declared in the call site metadata are silently dropped, since they cannot be passed
+    /// to the tracing infrastructure. This can hide data loss, e.g., if the event stream
+    /// and the persisted metadata are produced by different versions of the instrumented code.
+    /// A strict receiver rejects such events with [`ReceiveError::UnknownField`] instead.
+    #[must_use]
+    pub fn with_strict_fields(mut self) -> Self {
+        self.strict_fields = true;
+        self
+    }
+
+    /// Sets a mapper for the targets of call sites received by this receiver. The mapper is applied
+    /// before call site metadata is allocated, so spans and events relayed to the tracing
+    /// infrastructure will have mapped targets. This can be used to distinguish replayed events
+    /// from the host ones, e.g., by prefixing targets with `wasm::`.
+    ///
+    /// The mapper only applies to call sites received after this call; in particular,
+    /// it does not apply to metadata restored via [`Self::new()`]. Correspondingly,
+    /// [persisted metadata](Self::persist_metadata()) contains mapped targets,
+    /// so it is not re-mapped when restored.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use std::borrow::Cow;
+    /// # use tracing_tunnel::TracingEventReceiver;
+    /// let receiver = TracingEventReceiver::default().with_target_mapper(|target| {
+    ///     Cow::Owned(format!("wasm::{target}"))
+    /// });
+    /// // Use the receiver...
+    /// ```
+    #[must_use]
+    pub fn with_target_mapper<F>(mut self, mapper: F) -> Self
+    where
+        F: Fn(&str) -> Cow<'_, str> + Send + Sync + 'static,
+    {
+        self.target_mapper = Some(Box::new(mapper));
+        self
+    }
+
+    /// Returns statistics about events processed by this receiver so far.
+    pub fn stats(&self) -> ReceiverStats {
+        self.stats
+    }
+
+    /// Resets the receiver to the empty state, so that it can be reused for an unrelated
+    /// execution without reconstruction. This discards all metadata and span mappings;
+    /// afterwards, the receiver behaves as if it was created with empty persisted data.
+    /// Receiver configuration (e.g., [the limit on alive spans](Self::with_max_alive_spans()))
+    /// and [statistics](Self::stats()) are retained.
+    ///
+    /// Spans entered by the current execution are exited, and spans created by it are closed,
+    /// same as when the receiver is dropped. Other spans are not closed
+    /// in the underlying [`Subscriber`], which may lead to span leakage; use [`Self::persist()`]
+    /// before resetting if the spans should be restored later.
+    ///
+    /// [`Subscriber`]: tracing_core::Subscriber
+    pub fn reset(&mut self) {
+        self.current_execution.finalize(&self.local_spans);
+        self.metadata.clear();
+        self.spans.inner.clear();
+        self.local_spans.inner.clear();
+    }
+
     fn dispatch<T>(dispatch_fn: impl FnOnce(&Dispatch) -> T) -> T {
         dispatch_fn(&dispatcher::get_default(Dispatch::clone))
     }
@@ -341,6 +545,34 @@ impl TracingEventReceiver {
         Ok(())
     }
 
+    fn ensure_fields_known(
+        &self,
+        metadata_id: MetadataId,
+        values: &TracedValues<String>,
+    ) -> Result<(), ReceiveError> {
+        if !self.strict_fields {
+            return Ok(());
+        }
+        let fields = self.metadata(metadata_id)?.fields();
+        let unknown_field = values.keys().find(|&name| fields.field(name).is_none());
+        if let Some(name) = unknown_field {
+            return Err(ReceiveError::UnknownField {
+                metadata_id,
+                name: name.to_owned(),
+            });
+        }
+        Ok(())
+    }
+
+    fn ensure_span_can_be_created(&self, id: RawSpanId) -> Result<(), ReceiveError> {
+        if let Some(max) = self.max_alive_spans {
+            if !self.spans.inner.contains_key(&id) && self.spans.inner.len() >= max {
+                return Err(ReceiveError::TooManySpans { max });
+            }
+        }
+        Ok(())
+    }
+
     fn generate_fields<'a>(
         metadata: &'static Metadata<'static>,
         values: &'a TracedValues<String>,
@@ -379,7 +611,13 @@ impl TracingEventReceiver {
         )
     }
 
-    fn on_new_call_site(&mut self, id: MetadataId, data: CallSiteData) {
+    fn on_new_call_site(&mut self, id: MetadataId, mut data: CallSiteData) {
+        if let Some(mapper) = &self.target_mapper {
+            let target = mapper(&data.target);
+            if *target != *data.target {
+                data.target = Cow::Owned(target.into_owned());
+            }
+        }
         let (metadata, is_new) = ARENA.alloc_metadata(data);
         self.metadata.insert(id, metadata);
         if is_new {
@@ -387,7 +625,30 @@ impl TracingEventReceiver {
         }
     }
 
-    fn create_local_span(&self, data: &SpanData) -> Result<Id, ReceiveError> {
+    fn on_new_event(
+        &self,
+        metadata_id: MetadataId,
+        parent: Option<RawSpanId>,
+        values: &TracedValues<String>,
+    ) -> Result<(), ReceiveError> {
+        Self::ensure_values_len(values)?;
+        self.ensure_fields_known(metadata_id, values)?;
+
+        let metadata = self.metadata(metadata_id)?;
+        let values = Self::generate_fields(metadata, values);
+        let values = Self::expand_fields(&values);
+        let values = Self::create_values(metadata.fields(), &values);
+        let parent = parent.map(|id| self.map_span_id(id)).transpose()?.flatten();
+        let event = if let Some(parent) = parent {
+            Event::new_child_of(parent.clone(), metadata, &values)
+        } else {
+            Event::new(metadata, &values)
+        };
+        Self::dispatch(|dispatch| dispatch.event(&event));
+        Ok(())
+    }
+
+    fn create_local_span(&self, id: RawSpanId, data: &SpanData) -> Result<Id, ReceiveError> {
         let metadata = self.metadata(data.metadata_id)?;
         let local_parent_id = data
             .parent_id
@@ -404,7 +665,34 @@ impl TracingEventReceiver {
             Attributes::new(metadata, &value_set)
         };
 
-        Ok(Self::dispatch(|dispatch| dispatch.new_span(&attributes)))
+        let prev_source_id = SOURCE_SPAN_ID.with(|cell| cell.replace(Some(id)));
+        let local_id = Self::dispatch(|dispatch| dispatch.new_span(&attributes));
+        SOURCE_SPAN_ID.with(|cell| cell.set(prev_source_id));
+        Ok(local_id)
+    }
+
+    /// Returns the local ID of the specified span, lazily creating the local span if necessary
+    /// (e.g., if the span was restored from the persisted state without local spans).
+    fn ensure_local_span(&mut self, id: RawSpanId) -> Result<Id, ReceiveError> {
+        if let Some(local_id) = self.map_span_id(id)? {
+            return Ok(local_id.clone());
+        }
+        let data = self.span(id)?;
+        let local_id = self.create_local_span(id, data)?;
+        self.local_spans.inner.insert(id, local_id.clone());
+        Ok(local_id)
+    }
+
+    /// Returns the ID of the span in the [`TracingEvent`] stream that is currently being created
+    /// by a receiver on this thread. The ID is only available while the underlying
+    /// [`Subscriber`] processes the [`new_span()`] call issued by the receiver, and is `None`
+    /// otherwise. Thus, this method can be used by subscribers / layers to correlate
+    /// local spans to the spans in the source event stream.
+    ///
+    /// [`Subscriber`]: tracing_core::Subscriber
+    /// [`new_span()`]: tracing_core::Subscriber::new_span()
+    pub fn source_span_id() -> Option<RawSpanId> {
+        SOURCE_SPAN_ID.with(Cell::get)
     }
 
     /// Tries to consume an event and relays it to the tracing infrastructure.
@@ -417,8 +705,20 @@ impl TracingEventReceiver {
     /// not a [`TracingEventSender`]).
     ///
     /// [`TracingEventSender`]: crate::TracingEventSender
-    #[allow(clippy::missing_panics_doc, clippy::map_entry)] // false positive
     pub fn try_receive(&mut self, event: TracingEvent) -> Result<(), ReceiveError> {
+        let is_new_span = matches!(event, TracingEvent::NewSpan { .. });
+        self.stats.events_received += 1;
+        let result = self.process_event(event);
+        if result.is_err() {
+            self.stats.errors += 1;
+        } else if is_new_span {
+            self.stats.spans_created += 1;
+        }
+        result
+    }
+
+    #[allow(clippy::map_entry)] // false positive
+    fn process_event(&mut self, event: TracingEvent) -> Result<(), ReceiveError> {
         match event {
             TracingEvent::NewCallSite { id, data } => {
                 self.on_new_call_site(id, data);
@@ -431,6 +731,8 @@ impl TracingEventReceiver {
                 values,
             } => {
                 Self::ensure_values_len(&values)?;
+                self.ensure_fields_known(metadata_id, &values)?;
+                self.ensure_span_can_be_created(id)?;
 
                 let data = SpanData {
                     metadata_id,
@@ -439,7 +741,7 @@ impl TracingEventReceiver {
                     values,
                 };
                 if !self.local_spans.inner.contains_key(&id) {
-                    let local_id = self.create_local_span(&data)?;
+                    let local_id = self.create_local_span(id, &data)?;
                     self.local_spans.inner.insert(id, local_id);
                 }
                 self.spans.inner.insert(id, data);
@@ -447,26 +749,20 @@ impl TracingEventReceiver {
             }
 
             TracingEvent::FollowsFrom { id, follows_from } => {
-                let local_id = self.map_span_id(id)?;
-                let local_follows_from = self.map_span_id(follows_from)?;
-
-                // TODO: properly handle remaining cases
-                if let (Some(id), Some(follows_from)) = (local_id, local_follows_from) {
-                    Self::dispatch(|dispatch| {
-                        dispatch.record_follows_from(id, follows_from);
-                    });
-                }
+                // Check both spans before creating any local spans, so that a bogus event
+                // doesn't change the receiver state.
+                self.map_span_id(id)?;
+                self.map_span_id(follows_from)?;
+
+                let local_id = self.ensure_local_span(id)?;
+                let local_follows_from = self.ensure_local_span(follows_from)?;
+                Self::dispatch(|dispatch| {
+                    dispatch.record_follows_from(&local_id, &local_follows_from);
+                });
             }
 
             TracingEvent::SpanEntered { id } => {
-                let local_id = if let Some(id) = self.map_span_id(id)? {
-                    id.clone()
-                } else {
-                    let data = self.span(id)?;
-                    let local_id = self.create_local_span(data)?;
-                    self.local_spans.inner.insert(id, local_id.clone());
-                    local_id
-                };
+                let local_id = self.ensure_local_span(id)?;
                 self.current_execution.entered_span_ids.insert(id);
                 Self::dispatch(|dispatch| dispatch.enter(&local_id));
             }
@@ -496,6 +792,7 @@ impl TracingEventReceiver {
 
             TracingEvent::ValuesRecorded { id, values } => {
                 Self::ensure_values_len(&values)?;
+                self.ensure_fields_known(self.span(id)?.metadata_id, &values)?;
 
                 if let Some(local_id) = self.map_span_id(id)? {
                     let metadata = self.metadata(self.spans.inner[&id].metadata_id)?;
@@ -514,24 +811,33 @@ impl TracingEventReceiver {
                 parent,
                 values,
             } => {
-                Self::ensure_values_len(&values)?;
-
-                let metadata = self.metadata(metadata_id)?;
-                let values = Self::generate_fields(metadata, &values);
-                let values = Self::expand_fields(&values);
-                let values = Self::create_values(metadata.fields(), &values);
-                let parent = parent.map(|id| self.map_span_id(id)).transpose()?.flatten();
-                let event = if let Some(parent) = parent {
-                    Event::new_child_of(parent.clone(), metadata, &values)
-                } else {
-                    Event::new(metadata, &values)
-                };
-                Self::dispatch(|dispatch| dispatch.event(&event));
+                self.on_new_event(metadata_id, parent, &values)?;
             }
+
+            TracingEvent::Flush => { /* Markers do not influence replayed tracing */ }
         }
         Ok(())
     }
 
+    /// Same as [`Self::try_receive()`], but provides the context of the failing event
+    /// in the returned error: the event kind and, optionally, its `index` in the event stream.
+    ///
+    /// # Errors
+    ///
+    /// Fails in the same cases as [`Self::try_receive()`].
+    pub fn try_receive_at(
+        &mut self,
+        index: Option<usize>,
+        event: TracingEvent,
+    ) -> Result<(), ReceiveErrorAt> {
+        let event_kind = event.kind_str();
+        self.try_receive(event).map_err(|source| ReceiveErrorAt {
+            index,
+            event_kind,
+            source,
+        })
+    }
+
     /// Consumes an event and relays it to the tracing infrastructure.
     ///
     /// # Panics
@@ -542,6 +848,69 @@ impl TracingEventReceiver {
             .expect("received bogus tracing event");
     }
 
+    /// Replays [timed events](TimedTracingEvent), sleeping between events to reproduce
+    /// the original inter-event timing scaled by `time_scale`. E.g., `time_scale == 2.0`
+    /// replays events twice as slow as they were emitted, and `time_scale == 0.0` replays
+    /// events without sleeping. This can be used to load-test a subscriber with a realistic
+    /// event cadence.
+    ///
+    /// The timing is measured relative to the first event, so delays introduced by processing
+    /// events do not accumulate.
+    ///
+    /// # Errors
+    ///
+    /// Fails in the same cases as [`Self::try_receive()`]. The error contains the index
+    /// of the failing event in `events`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `time_scale` is negative or not finite.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use std::sync::mpsc;
+    /// # use tracing_tunnel::{TracingEventReceiver, TracingEventSender};
+    /// let (events_sx, events_rx) = mpsc::channel();
+    /// let sender = TracingEventSender::new_timed(move |event| {
+    ///     events_sx.send(event).ok();
+    /// });
+    /// tracing::subscriber::with_default(sender, || {
+    ///     tracing::info_span!("test").in_scope(|| tracing::info!("Hello"));
+    /// });
+    ///
+    /// let mut receiver = TracingEventReceiver::default();
+    /// // Replay events 10 times faster than they were emitted.
+    /// receiver.replay_timed(events_rx.try_iter(), 0.1)?;
+    /// # Ok::<_, tracing_tunnel::ReceiveErrorAt>(())
+    /// ```
+    pub fn replay_timed(
+        &mut self,
+        events: impl IntoIterator<Item = TimedTracingEvent>,
+        time_scale: f64,
+    ) -> Result<(), ReceiveErrorAt> {
+        assert!(
+            time_scale.is_finite() && time_scale >= 0.0,
+            "time scale must be a non-negative finite number"
+        );
+
+        let mut start = None;
+        for (index, timed_event) in events.into_iter().enumerate() {
+            let (start_instant, start_timestamp) =
+                *start.get_or_insert_with(|| (Instant::now(), timed_event.timestamp));
+            if time_scale > 0.0 {
+                let elapsed_nanos = timed_event.timestamp.saturating_sub(start_timestamp);
+                let delay = Duration::from_nanos(elapsed_nanos).mul_f64(time_scale);
+                let now = Instant::now();
+                if let Some(sleep_duration) = (start_instant + delay).checked_duration_since(now) {
+                    thread::sleep(sleep_duration);
+                }
+            }
+            self.try_receive_at(Some(index), timed_event.event)?;
+        }
+        Ok(())
+    }
+
     /// Persists [`Metadata`] produced by the previously consumed events. The returned
     /// metadata should be merged into the metadata provided to [`Self::new()`].
     pub fn persist_metadata(&self) -> PersistedMetadata {
@@ -554,6 +923,13 @@ impl TracingEventReceiver {
     }
 
     /// Returns persisted and local spans.
+    ///
+    /// Spans entered by the current execution (i.e., ones without a matching
+    /// [`SpanExited`](TracingEvent::SpanExited) event) are exited in the underlying
+    /// [`Subscriber`], so that its span stack remains balanced after this call. Unlike with
+    /// dropping the receiver, spans created by the execution are not closed.
+    ///
+    /// [`Subscriber`]: tracing_core::Subscriber
     pub fn persist(mut self) -> (PersistedSpans, LocalSpans) {
         self.current_execution.uncommitted_span_ids.clear();
         let spans = mem::take(&mut self.spans);
@@ -563,6 +939,19 @@ impl TracingEventReceiver {
     }
 }
 
+/// Consumes events one by one, similar to [`TracingEventReceiver::receive()`].
+///
+/// # Panics
+///
+/// Panics in the same cases when [`TracingEventReceiver::try_receive()`] returns an error.
+impl Extend<TracingEvent> for TracingEventReceiver {
+    fn extend<I: IntoIterator<Item = TracingEvent>>(&mut self, iter: I) {
+        for event in iter {
+            self.receive(event);
+        }
+    }
+}
+
 impl Drop for TracingEventReceiver {
     fn drop(&mut self) {
         self.current_execution.finalize(&self.local_spans);
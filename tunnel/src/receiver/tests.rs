@@ -166,6 +166,49 @@ fn receiver_does_not_panic_on_bogus_field() {
     }
 }
 
+#[test]
+fn strict_receiver_rejects_unknown_fields() {
+    let call_site = create_call_site(vec![Cow::Borrowed("i")]);
+    let mut receiver = TracingEventReceiver::default().with_strict_fields();
+    receiver.receive(TracingEvent::NewCallSite {
+        id: 0,
+        data: call_site,
+    });
+    receiver.receive(TracingEvent::NewSpan {
+        id: 0,
+        parent_id: None,
+        metadata_id: 0,
+        values: TracedValues::from_iter([("i".to_owned(), TracedValue::from(42_i64))]),
+    });
+
+    let bogus_events = [
+        TracingEvent::NewSpan {
+            id: 1,
+            parent_id: None,
+            metadata_id: 0,
+            values: TracedValues::from_iter([("j".to_owned(), TracedValue::from(42_i64))]),
+        },
+        TracingEvent::ValuesRecorded {
+            id: 0,
+            values: TracedValues::from_iter([("j".to_owned(), TracedValue::from(23_i64))]),
+        },
+    ];
+    for bogus_event in bogus_events {
+        let err = receiver.try_receive(bogus_event).unwrap_err();
+        assert_matches!(
+            &err,
+            ReceiveError::UnknownField { metadata_id: 0, name } if name == "j"
+        );
+        assert_eq!(
+            err.to_string(),
+            "field `j` is not declared in metadata with ID 0"
+        );
+    }
+    // Rejected events must not change the receiver state.
+    assert_eq!(receiver.spans.len(), 1);
+    assert!(!receiver.spans.inner[&0].values.contains_key("j"));
+}
+
 #[test]
 fn restoring_spans() {
     let metadata = PersistedMetadata {
@@ -227,3 +270,250 @@ fn restoring_span_after_recording_values() {
 
     visit_and_drop_span(&mut receiver);
 }
+
+#[test]
+fn follows_from_creates_missing_local_spans() {
+    let metadata = PersistedMetadata {
+        inner: HashMap::from_iter([(0, CALL_SITE_DATA)]),
+    };
+    let span_data = SpanData {
+        metadata_id: 0,
+        parent_id: None,
+        ref_count: 1,
+        values: TracedValues::new(),
+    };
+    let spans = PersistedSpans {
+        inner: HashMap::from_iter([(1, span_data.clone()), (2, span_data)]),
+    };
+    let local_spans = LocalSpans::default();
+
+    let mut receiver = TracingEventReceiver::new(metadata, spans, local_spans);
+    let err = receiver
+        .try_receive(TracingEvent::FollowsFrom {
+            id: 2,
+            follows_from: 3,
+        })
+        .unwrap_err();
+    assert_matches!(err, ReceiveError::UnknownSpanId(3));
+    assert!(receiver.local_spans.inner.is_empty());
+
+    receiver.receive(TracingEvent::FollowsFrom {
+        id: 2,
+        follows_from: 1,
+    });
+    assert!(receiver.local_spans.inner.contains_key(&1));
+    assert!(receiver.local_spans.inner.contains_key(&2));
+    visit_and_drop_span(&mut receiver);
+}
+
+#[test]
+fn error_context_includes_event_kind() {
+    let mut receiver = TracingEventReceiver::default();
+    let event = TracingEvent::NewSpan {
+        id: 0,
+        parent_id: None,
+        metadata_id: 0,
+        values: TracedValues::new(),
+    };
+    let err = receiver.try_receive_at(Some(42), event).unwrap_err();
+    assert_eq!(err.index, Some(42));
+    assert_eq!(err.event_kind, "new_span");
+    assert_matches!(err.source, ReceiveError::UnknownMetadataId(0));
+    let err = err.to_string();
+    assert!(err.contains("`new_span` event at index 42"), "{err}");
+    assert!(err.contains("unknown metadata ID"), "{err}");
+
+    let err = receiver
+        .try_receive_at(None, TracingEvent::SpanEntered { id: 1 })
+        .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "failed processing `span_entered` event: unknown span ID: 1"
+    );
+}
+
+#[test]
+fn too_many_spans_error() {
+    let mut receiver = TracingEventReceiver::default().with_max_alive_spans(2);
+    receiver.receive(TracingEvent::NewCallSite {
+        id: 0,
+        data: CALL_SITE_DATA,
+    });
+
+    let new_span = |id| TracingEvent::NewSpan {
+        id,
+        parent_id: None,
+        metadata_id: 0,
+        values: TracedValues::new(),
+    };
+    receiver.receive(new_span(1));
+    receiver.receive(new_span(2));
+    let err = receiver.try_receive(new_span(3)).unwrap_err();
+    assert_matches!(err, ReceiveError::TooManySpans { max: 2 });
+    assert!(!receiver.spans.inner.contains_key(&3));
+
+    // Dropping a span should free up space for a new one.
+    receiver.receive(TracingEvent::SpanDropped { id: 1 });
+    receiver.receive(new_span(3));
+    assert_eq!(receiver.spans.len(), 2);
+}
+
+#[test]
+fn extending_receiver_with_events() {
+    let events = [
+        TracingEvent::NewCallSite {
+            id: 0,
+            data: CALL_SITE_DATA,
+        },
+        TracingEvent::NewSpan {
+            id: 1,
+            parent_id: None,
+            metadata_id: 0,
+            values: TracedValues::new(),
+        },
+        TracingEvent::NewSpan {
+            id: 2,
+            parent_id: Some(1),
+            metadata_id: 0,
+            values: TracedValues::new(),
+        },
+        TracingEvent::SpanEntered { id: 2 },
+        TracingEvent::SpanExited { id: 2 },
+        TracingEvent::SpanDropped { id: 2 },
+    ];
+
+    let mut looping_receiver = TracingEventReceiver::default();
+    for event in events.clone() {
+        looping_receiver.receive(event);
+    }
+    let mut extended_receiver = TracingEventReceiver::default();
+    extended_receiver.extend(events);
+
+    for receiver in [&looping_receiver, &extended_receiver] {
+        assert_eq!(receiver.metadata.len(), 1);
+        assert_eq!(receiver.spans.len(), 1);
+        assert!(receiver.spans.inner.contains_key(&1));
+        assert_eq!(receiver.local_spans.inner.len(), 1);
+    }
+}
+
+#[test]
+#[should_panic(expected = "received bogus tracing event")]
+fn extending_receiver_with_bogus_events() {
+    let mut receiver = TracingEventReceiver::default();
+    receiver.extend([TracingEvent::SpanEntered { id: 1 }]);
+}
+
+#[test]
+fn receiver_stats() {
+    let events = [
+        TracingEvent::NewCallSite {
+            id: 0,
+            data: CALL_SITE_DATA,
+        },
+        TracingEvent::NewSpan {
+            id: 1,
+            parent_id: None,
+            metadata_id: 0,
+            values: TracedValues::new(),
+        },
+        TracingEvent::SpanEntered { id: 1 },
+        TracingEvent::SpanEntered { id: 2 }, // bogus
+        TracingEvent::NewSpan {
+            id: 3,
+            parent_id: None,
+            metadata_id: 1, // bogus
+            values: TracedValues::new(),
+        },
+        TracingEvent::SpanExited { id: 1 },
+    ];
+
+    let mut receiver = TracingEventReceiver::default();
+    assert_eq!(receiver.stats(), ReceiverStats::default());
+    for event in events {
+        receiver.try_receive(event).ok();
+    }
+
+    let stats = receiver.stats();
+    assert_eq!(stats.events_received, 6);
+    assert_eq!(stats.spans_created, 1);
+    assert_eq!(stats.errors, 2);
+}
+
+#[test]
+fn resetting_receiver() {
+    let mut receiver = TracingEventReceiver::default();
+    receiver.extend([
+        TracingEvent::NewCallSite {
+            id: 0,
+            data: CALL_SITE_DATA,
+        },
+        TracingEvent::NewSpan {
+            id: 1,
+            parent_id: None,
+            metadata_id: 0,
+            values: TracedValues::new(),
+        },
+        TracingEvent::SpanEntered { id: 1 },
+    ]);
+    assert_eq!(receiver.persist_metadata().len(), 1);
+
+    receiver.reset();
+    assert!(receiver.persist_metadata().is_empty());
+    // Metadata and spans from the previous stream must not be accessible.
+    let err = receiver
+        .try_receive(TracingEvent::SpanExited { id: 1 })
+        .unwrap_err();
+    assert_matches!(err, ReceiveError::UnknownSpanId(1));
+    let err = receiver
+        .try_receive(TracingEvent::NewSpan {
+            id: 1,
+            parent_id: None,
+            metadata_id: 0,
+            values: TracedValues::new(),
+        })
+        .unwrap_err();
+    assert_matches!(err, ReceiveError::UnknownMetadataId(0));
+
+    // The receiver can process a new stream reusing the same IDs.
+    receiver.extend([
+        TracingEvent::NewCallSite {
+            id: 0,
+            data: CALL_SITE_DATA,
+        },
+        TracingEvent::NewSpan {
+            id: 1,
+            parent_id: None,
+            metadata_id: 0,
+            values: TracedValues::new(),
+        },
+    ]);
+    let (spans, local_spans) = receiver.persist();
+    assert_eq!(spans.len(), 1);
+    assert_eq!(local_spans.inner.len(), 1);
+}
+
+#[test]
+fn mapping_call_site_targets() {
+    let mut receiver = TracingEventReceiver::default().with_target_mapper(|target| {
+        target
+            .strip_prefix("internal::")
+            .map_or_else(|| Cow::Owned(format!("wasm::{target}")), Cow::Borrowed)
+    });
+    let mut call_site = create_call_site(vec![]);
+    call_site.target = Cow::Borrowed("internal::test");
+    receiver.receive(TracingEvent::NewCallSite {
+        id: 0,
+        data: call_site.clone(),
+    });
+    call_site.target = Cow::Borrowed("app");
+    receiver.receive(TracingEvent::NewCallSite {
+        id: 1,
+        data: call_site,
+    });
+
+    assert_eq!(receiver.metadata[&0].target(), "test");
+    assert_eq!(receiver.metadata[&1].target(), "wasm::app");
+    let metadata = receiver.persist_metadata();
+    assert_eq!(metadata.inner[&1].target, "wasm::app");
+}
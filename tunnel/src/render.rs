@@ -0,0 +1,269 @@
+//! Human-readable rendering of `TracingEvent`s.
+
+use core::fmt::{self, Write as _};
+
+use crate::{
+    alloc::{BTreeMap, String},
+    CallSiteData, MetadataId, TracedValue, TracedValues, TracingEvent, TracingLevel,
+};
+
+type CallSites<'a> = BTreeMap<MetadataId, &'a CallSiteData>;
+
+impl TracingLevel {
+    fn as_upper_str(self) -> &'static str {
+        match self {
+            Self::Error => "ERROR",
+            Self::Warn => "WARN",
+            Self::Info => "INFO",
+            Self::Debug => "DEBUG",
+            Self::Trace => "TRACE",
+        }
+    }
+}
+
+impl TracingEvent {
+    /// Renders this event as a concise single-line string, e.g. `+span#3 site#1 {count=5}`
+    /// or `enter#3`. This is useful to eyeball event streams, e.g., in test failures.
+    ///
+    /// Since a standalone event does not contain information about call sites, spans and events
+    /// refer to call sites by their IDs. Use [`Self::render_stream()`] to render
+    /// call site names and levels instead.
+    pub fn render(&self) -> String {
+        let mut buffer = String::new();
+        // Writing to a `String` cannot fail.
+        self.render_to(&mut buffer, &CallSites::new()).ok();
+        buffer
+    }
+
+    /// Renders a stream of events, one event per line, in the same format as [`Self::render()`].
+    /// Unlike with `render()`, spans and events in the stream are rendered with the name / level
+    /// of their call site if the call site is defined earlier in the stream.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use std::sync::mpsc;
+    /// # use tracing_tunnel::{TracingEvent, TracingEventSender};
+    /// let (events_sx, events_rx) = mpsc::channel();
+    /// let sender = TracingEventSender::new(move |event| {
+    ///     events_sx.send(event).ok();
+    /// });
+    /// tracing::subscriber::with_default(sender, || {
+    ///     tracing::info_span!("compute", count = 5).in_scope(|| tracing::info!("done"));
+    /// });
+    /// let events: Vec<_> = events_rx.try_iter().collect();
+    /// let rendered = TracingEvent::render_stream(&events);
+    /// assert!(rendered.contains("+span#1 \"compute\" {count=5}"), "{rendered}");
+    /// assert!(rendered.contains("event INFO \"done\""), "{rendered}");
+    /// ```
+    pub fn render_stream(events: &[Self]) -> String {
+        let mut call_sites = CallSites::new();
+        let mut buffer = String::new();
+        for event in events {
+            if let Self::NewCallSite { id, data } = event {
+                call_sites.insert(*id, data);
+            }
+            event.render_to(&mut buffer, &call_sites).ok();
+            buffer.push('\n');
+        }
+        buffer
+    }
+
+    fn render_to(&self, buffer: &mut String, call_sites: &CallSites<'_>) -> fmt::Result {
+        match self {
+            Self::NewCallSite { id, data } => {
+                let level = data.level.as_upper_str();
+                write!(buffer, "site#{id} {} {level} {:?}", data.kind, data.name)?;
+                write!(buffer, " @ {}", data.target)
+            }
+            Self::NewSpan {
+                id,
+                parent_id,
+                metadata_id,
+                values,
+            } => {
+                write!(buffer, "+span#{id} ")?;
+                Self::render_call_site(buffer, *metadata_id, call_sites)?;
+                if let Some(parent_id) = parent_id {
+                    write!(buffer, " ^#{parent_id}")?;
+                }
+                Self::render_values(buffer, values, None)
+            }
+            Self::FollowsFrom { id, follows_from } => {
+                write!(buffer, "follows#{id} <- #{follows_from}")
+            }
+            Self::SpanEntered { id } => write!(buffer, "enter#{id}"),
+            Self::SpanExited { id } => write!(buffer, "exit#{id}"),
+            Self::SpanCloned { id } => write!(buffer, "clone#{id}"),
+            Self::SpanDropped { id } => write!(buffer, "-span#{id}"),
+            Self::ValuesRecorded { id, values } => {
+                write!(buffer, "record#{id}")?;
+                Self::render_values(buffer, values, None)
+            }
+            Self::NewEvent {
+                metadata_id,
+                parent,
+                values,
+            } => {
+                buffer.push_str("event ");
+                if let Some(data) = call_sites.get(metadata_id) {
+                    buffer.push_str(data.level.as_upper_str());
+                } else {
+                    write!(buffer, "site#{metadata_id}")?;
+                }
+                if let Some(parent) = parent {
+                    write!(buffer, " ^#{parent}")?;
+                }
+                let message = values.get("message");
+                let message = message.and_then(|msg| msg.as_debug_str().or_else(|| msg.as_str()));
+                if let Some(message) = message {
+                    write!(buffer, " {message:?}")?;
+                }
+                let skipped_field = message.map(|_| "message");
+                Self::render_values(buffer, values, skipped_field)
+            }
+            Self::Flush => buffer.write_str("flush"),
+        }
+    }
+
+    fn render_call_site(
+        buffer: &mut String,
+        id: MetadataId,
+        call_sites: &CallSites<'_>,
+    ) -> fmt::Result {
+        if let Some(data) = call_sites.get(&id) {
+            write!(buffer, "{:?}", data.name)
+        } else {
+            write!(buffer, "site#{id}")
+        }
+    }
+
+    fn render_values(
+        buffer: &mut String,
+        values: &TracedValues<String>,
+        skipped_field: Option<&str>,
+    ) -> fmt::Result {
+        let mut values = values
+            .iter()
+            .filter(|(name, _)| Some(*name) != skipped_field)
+            .peekable();
+        if values.peek().is_none() {
+            return Ok(());
+        }
+
+        buffer.push_str(" {");
+        for (i, (name, value)) in values.enumerate() {
+            if i > 0 {
+                buffer.push_str(", ");
+            }
+            write!(buffer, "{name}=")?;
+            Self::render_value(buffer, value)?;
+        }
+        buffer.push('}');
+        Ok(())
+    }
+
+    fn render_value(buffer: &mut String, value: &TracedValue) -> fmt::Result {
+        match value {
+            TracedValue::Bool(value) => write!(buffer, "{value}"),
+            TracedValue::Int(value) => write!(buffer, "{value}"),
+            TracedValue::UInt(value) => write!(buffer, "{value}"),
+            TracedValue::Float(value) => write!(buffer, "{value}"),
+            TracedValue::String(value) => write!(buffer, "{value:?}"),
+            TracedValue::Object(value) => write!(buffer, "{value:?}"),
+            #[cfg(feature = "std")]
+            TracedValue::Error(err) => write!(buffer, "{err}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        alloc::{Cow, Vec},
+        CallSiteKind,
+    };
+
+    fn call_site(kind: CallSiteKind, name: &'static str, level: TracingLevel) -> CallSiteData {
+        CallSiteData {
+            kind,
+            name: Cow::Borrowed(name),
+            target: Cow::Borrowed("render"),
+            level,
+            module_path: None,
+            file: None,
+            line: None,
+            fields: Vec::new(),
+        }
+    }
+
+    fn stream() -> [TracingEvent; 8] {
+        [
+            TracingEvent::NewCallSite {
+                id: 0,
+                data: call_site(CallSiteKind::Span, "compute", TracingLevel::Info),
+            },
+            TracingEvent::NewSpan {
+                id: 3,
+                parent_id: None,
+                metadata_id: 0,
+                values: TracedValues::from_iter([("count".into(), 5_i64.into())]),
+            },
+            TracingEvent::SpanEntered { id: 3 },
+            TracingEvent::NewCallSite {
+                id: 1,
+                data: call_site(CallSiteKind::Event, "event", TracingLevel::Warn),
+            },
+            TracingEvent::NewEvent {
+                metadata_id: 1,
+                parent: None,
+                values: TracedValues::from_iter([
+                    ("message".into(), TracedValue::debug(&format_args!("done"))),
+                    ("name".into(), "test".into()),
+                    ("ok".into(), true.into()),
+                ]),
+            },
+            TracingEvent::ValuesRecorded {
+                id: 3,
+                values: TracedValues::from_iter([("approx".into(), 1.5.into())]),
+            },
+            TracingEvent::SpanExited { id: 3 },
+            TracingEvent::SpanDropped { id: 3 },
+        ]
+    }
+
+    #[test]
+    fn rendering_events() {
+        let events = stream();
+        let rendered: Vec<_> = events.iter().map(TracingEvent::render).collect();
+        assert_eq!(
+            rendered,
+            [
+                "site#0 span INFO \"compute\" @ render",
+                "+span#3 site#0 {count=5}",
+                "enter#3",
+                "site#1 event WARN \"event\" @ render",
+                "event site#1 \"done\" {name=\"test\", ok=true}",
+                "record#3 {approx=1.5}",
+                "exit#3",
+                "-span#3",
+            ]
+        );
+    }
+
+    #[test]
+    fn rendering_stream() {
+        let rendered = TracingEvent::render_stream(&stream());
+        let expected = "\
+            site#0 span INFO \"compute\" @ render\n\
+            +span#3 \"compute\" {count=5}\n\
+            enter#3\n\
+            site#1 event WARN \"event\" @ render\n\
+            event WARN \"done\" {name=\"test\", ok=true}\n\
+            record#3 {approx=1.5}\n\
+            exit#3\n\
+            -span#3\n";
+        assert_eq!(rendered, expected);
+    }
+}
@@ -5,32 +5,45 @@ use tracing_core::{
     Event, Interest, Metadata, Subscriber,
 };
 
-use core::sync::atomic::{AtomicU32, Ordering};
+use core::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
 
-use crate::{CallSiteData, MetadataId, RawSpanId, TracedValues, TracingEvent};
+#[cfg(feature = "std")]
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex, PoisonError},
+};
+
+#[cfg(feature = "std")]
+use crate::TimedTracingEvent;
+use crate::{CallSiteData, ConversionOptions, MetadataId, RawSpanId, TracedValues, TracingEvent};
 
 impl TracingEvent {
-    fn new_span(span: &Attributes<'_>, metadata_id: MetadataId, id: RawSpanId) -> Self {
+    fn from_attributes(
+        span: &Attributes<'_>,
+        metadata_id: MetadataId,
+        id: RawSpanId,
+        options: &ConversionOptions,
+    ) -> Self {
         Self::NewSpan {
             id,
             parent_id: span.parent().map(Id::into_u64),
             metadata_id,
-            values: TracedValues::from_values(span.values()),
+            values: TracedValues::from_values_with_options(span.values(), options),
         }
     }
 
-    fn values_recorded(id: RawSpanId, values: &Record<'_>) -> Self {
+    fn from_record(id: RawSpanId, values: &Record<'_>, options: &ConversionOptions) -> Self {
         Self::ValuesRecorded {
             id,
-            values: TracedValues::from_record(values),
+            values: TracedValues::from_record_with_options(values, options),
         }
     }
 
-    fn new_event(event: &Event<'_>, metadata_id: MetadataId) -> Self {
+    fn from_event(event: &Event<'_>, metadata_id: MetadataId, options: &ConversionOptions) -> Self {
         Self::NewEvent {
             metadata_id,
             parent: event.parent().map(Id::into_u64),
-            values: TracedValues::from_event(event),
+            values: TracedValues::from_event_with_options(event, options),
         }
     }
 }
@@ -50,18 +63,298 @@ impl TracingEvent {
 #[derive(Debug)]
 pub struct TracingEventSender<F = fn(TracingEvent)> {
     next_span_id: AtomicU32,
+    depth: AtomicUsize,
+    max_depth: Option<usize>,
+    conversion_options: ConversionOptions<'static>,
     on_event: F,
 }
 
+#[cfg(feature = "std")]
+impl TracingEventSender {
+    /// Capacity of the channel used by [`Self::spawn()`].
+    const BACKGROUND_CHANNEL_CAPACITY: usize = 256;
+
+    /// Creates a subscriber that delivers events to the specified `hook` on a dedicated
+    /// background thread, so that the latency of the hook (e.g., serialization or IO)
+    /// does not slow down the traced code.
+    ///
+    /// Events are passed to the background thread via a bounded channel. If the channel
+    /// is full, the traced code blocks until the hook catches up. When the subscriber is dropped,
+    /// all pending events are delivered to the hook before the drop completes.
+    ///
+    /// The `hook` must not emit tracing events processed by the returned subscriber;
+    /// otherwise, it may deadlock if the channel is full.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the background thread cannot be spawned.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use std::sync::mpsc;
+    /// # use tracing_tunnel::{TracingEvent, TracingEventSender};
+    /// let (events_sx, events_rx) = mpsc::channel();
+    /// let sender = TracingEventSender::spawn(move |event| {
+    ///     events_sx.send(event).ok();
+    /// });
+    /// tracing::subscriber::with_default(sender, || {
+    ///     tracing::info_span!("test").in_scope(|| tracing::info!("Hello"));
+    /// });
+    /// // All events are delivered once the subscriber is dropped.
+    /// let events: Vec<TracingEvent> = events_rx.try_iter().collect();
+    /// assert!(events.len() >= 5);
+    /// ```
+    pub fn spawn<H>(hook: H) -> TracingEventSender<impl Fn(TracingEvent) + Send + Sync + 'static>
+    where
+        H: FnMut(TracingEvent) + Send + 'static,
+    {
+        let delivery = BackgroundDelivery::spawn(Self::BACKGROUND_CHANNEL_CAPACITY, hook);
+        TracingEventSender::new(move |event| delivery.send(event))
+    }
+
+    /// Creates a subscriber that attaches a monotonic timestamp to each emitted event
+    /// and passes the resulting [`TimedTracingEvent`] to the specified `hook`. Timestamps
+    /// are measured in nanoseconds since the subscriber creation.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use std::sync::mpsc;
+    /// # use tracing_tunnel::{TimedTracingEvent, TracingEventSender};
+    /// let (events_sx, events_rx) = mpsc::channel();
+    /// let sender = TracingEventSender::new_timed(move |event| {
+    ///     events_sx.send(event).ok();
+    /// });
+    /// tracing::subscriber::with_default(sender, || {
+    ///     tracing::info_span!("test").in_scope(|| tracing::info!("Hello"));
+    /// });
+    /// let events: Vec<TimedTracingEvent> = events_rx.try_iter().collect();
+    /// assert!(events
+    ///     .windows(2)
+    ///     .all(|window| window[0].timestamp <= window[1].timestamp));
+    /// ```
+    pub fn new_timed<H>(hook: H) -> TracingEventSender<impl Fn(TracingEvent) + 'static>
+    where
+        H: Fn(TimedTracingEvent) + 'static,
+    {
+        let start = std::time::Instant::now();
+        TracingEventSender::new(move |event| {
+            let timestamp = u64::try_from(start.elapsed().as_nanos()).unwrap_or(u64::MAX);
+            hook(TimedTracingEvent { timestamp, event });
+        })
+    }
+
+    /// Creates a subscriber that retains the last `capacity` emitted events in a ring buffer,
+    /// overwriting the oldest events once the buffer is full. This can be used as a "flight
+    /// recorder" for always-on tracing when only the most recent events matter (e.g., to dump
+    /// them on a crash). The retained events can be accessed via the returned [`RingHandle`].
+    ///
+    /// Keep in mind that retained events may refer to call sites and spans defined
+    /// in overwritten events, so the dumped events generally cannot be consumed
+    /// by a [`TracingEventReceiver`](crate::TracingEventReceiver).
+    ///
+    /// # Panics
+    ///
+    /// Panics if `capacity` is zero.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use tracing_tunnel::{TracingEvent, TracingEventSender};
+    /// let (sender, ring) = TracingEventSender::ring_buffer(2);
+    /// tracing::subscriber::with_default(sender, || {
+    ///     tracing::info_span!("test").in_scope(|| tracing::info!("Hello"));
+    /// });
+    /// let events = ring.dump();
+    /// assert_eq!(events.len(), 2);
+    /// assert!(matches!(events[1], TracingEvent::SpanDropped { .. }));
+    /// ```
+    pub fn ring_buffer(
+        capacity: usize,
+    ) -> (
+        TracingEventSender<impl Fn(TracingEvent) + Send + Sync + 'static>,
+        RingHandle,
+    ) {
+        assert!(capacity > 0, "ring buffer capacity must be positive");
+        let handle = RingHandle {
+            capacity,
+            events: Arc::new(Mutex::new(VecDeque::with_capacity(capacity))),
+        };
+        let events = Arc::clone(&handle.events);
+        let sender = TracingEventSender::new(move |event| {
+            let mut events = events.lock().unwrap_or_else(PoisonError::into_inner);
+            if events.len() == capacity {
+                events.pop_front();
+            }
+            events.push_back(event);
+        });
+        (sender, handle)
+    }
+}
+
+/// Handle to the ring buffer of events returned from [`TracingEventSender::ring_buffer()`].
+#[cfg(feature = "std")]
+#[derive(Debug, Clone)]
+pub struct RingHandle {
+    capacity: usize,
+    events: Arc<Mutex<VecDeque<TracingEvent>>>,
+}
+
+#[cfg(feature = "std")]
+impl RingHandle {
+    /// Returns the maximum number of retained events.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Returns a copy of currently retained events, from the oldest to the newest one.
+    pub fn dump(&self) -> Vec<TracingEvent> {
+        let events = self.events.lock().unwrap_or_else(PoisonError::into_inner);
+        events.iter().cloned().collect()
+    }
+}
+
+/// Background thread delivering events for [`TracingEventSender::spawn()`].
+#[cfg(feature = "std")]
+#[derive(Debug)]
+struct BackgroundDelivery {
+    events_sx: Option<std::sync::mpsc::SyncSender<TracingEvent>>,
+    handle: Option<std::thread::JoinHandle<()>>,
+}
+
+#[cfg(feature = "std")]
+impl BackgroundDelivery {
+    fn spawn<H>(capacity: usize, mut hook: H) -> Self
+    where
+        H: FnMut(TracingEvent) + Send + 'static,
+    {
+        let (events_sx, receiver) = std::sync::mpsc::sync_channel(capacity);
+        let handle = std::thread::Builder::new()
+            .name("tracing-event-sender".to_owned())
+            .spawn(move || {
+                for event in receiver {
+                    hook(event);
+                }
+            })
+            .expect("failed spawning tracing event delivery thread");
+        Self {
+            events_sx: Some(events_sx),
+            handle: Some(handle),
+        }
+    }
+
+    fn send(&self, event: TracingEvent) {
+        if let Some(events_sx) = &self.events_sx {
+            // An error means that the delivery thread has panicked; we cannot do anything
+            // meaningful in this case.
+            events_sx.send(event).ok();
+        }
+    }
+}
+
+/// Flushes pending events on drop.
+#[cfg(feature = "std")]
+impl Drop for BackgroundDelivery {
+    fn drop(&mut self) {
+        drop(self.events_sx.take()); // closes the channel, which will terminate the thread
+        if let Some(handle) = self.handle.take() {
+            handle.join().ok();
+        }
+    }
+}
+
 impl<F: Fn(TracingEvent) + 'static> TracingEventSender<F> {
     /// Creates a subscriber with the specified "on event" hook.
     pub fn new(on_event: F) -> Self {
         Self {
             next_span_id: AtomicU32::new(1), // 0 is invalid span ID
+            depth: AtomicUsize::new(0),
+            max_depth: None,
+            conversion_options: ConversionOptions::new(),
             on_event,
         }
     }
 
+    /// Limits the span nesting depth. [`SpanEntered`] events beyond the limit, together with
+    /// the matching [`SpanExited`] events, will not be sent. This can be used to protect
+    /// against event flooding because of unbounded recursion.
+    ///
+    /// The depth is tracked for the subscriber as a whole rather than per thread, so the limit
+    /// works as expected only if the subscriber is used from a single thread
+    /// (e.g., in WASM modules).
+    ///
+    /// [`SpanEntered`]: TracingEvent::SpanEntered
+    /// [`SpanExited`]: TracingEvent::SpanExited
+    #[must_use]
+    pub fn with_max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = Some(max_depth);
+        self
+    }
+
+    /// Limits the length of [`Debug`](core::fmt::Debug) output (in bytes) for values recorded
+    /// in spans and events. See [`ConversionOptions::with_debug_limit()`] for details.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use std::sync::mpsc;
+    /// # use tracing_tunnel::{TracedValue, TracingEvent, TracingEventSender};
+    /// let (events_sx, events_rx) = mpsc::channel();
+    /// let sender = TracingEventSender::new(move |event| {
+    ///     events_sx.send(event).ok();
+    /// });
+    /// let sender = sender.with_debug_limit(16);
+    /// tracing::subscriber::with_default(sender, || {
+    ///     tracing::info!(data = ?vec![0_u8; 100], "huge");
+    /// });
+    ///
+    /// let values = events_rx.try_iter().find_map(|event| match event {
+    ///     TracingEvent::NewEvent { values, .. } => Some(values),
+    ///     _ => None,
+    /// });
+    /// let TracedValue::Object(data) = &values.unwrap()["data"] else { unreachable!() };
+    /// assert!(data.is_truncated());
+    /// assert_eq!(data.as_ref(), "[0, 0, 0, 0, 0, …");
+    /// ```
+    #[must_use]
+    pub fn with_debug_limit(mut self, limit: usize) -> Self {
+        self.conversion_options = self.conversion_options.with_debug_limit(limit);
+        self
+    }
+
+    /// Emits a [`Flush`] marker signaling a logical boundary in the event stream
+    /// (e.g., the end of a workflow step). The marker is ignored by
+    /// a [`TracingEventReceiver`](crate::TracingEventReceiver), but can be used
+    /// by the consuming code to segment events.
+    ///
+    /// [`Flush`]: TracingEvent::Flush
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use std::sync::{mpsc, Arc};
+    /// # use tracing_tunnel::{TracingEvent, TracingEventSender};
+    /// let (events_sx, events_rx) = mpsc::channel();
+    /// let sender = Arc::new(TracingEventSender::new(move |event| {
+    ///     events_sx.send(event).ok();
+    /// }));
+    /// tracing::subscriber::with_default(Arc::clone(&sender), || {
+    ///     tracing::info!("step #1");
+    ///     sender.flush_marker();
+    ///     tracing::info!("step #2");
+    /// });
+    /// let events: Vec<_> = events_rx.try_iter().collect();
+    /// assert!(events.iter().any(|event| matches!(event, TracingEvent::Flush)));
+    /// ```
+    pub fn flush_marker(&self) {
+        self.send(TracingEvent::Flush);
+    }
+
+    fn is_within_depth(&self, depth: usize) -> bool {
+        self.max_depth.map_or(true, |max_depth| depth < max_depth)
+    }
+
     fn metadata_id(metadata: &'static Metadata<'static>) -> MetadataId {
         metadata as *const _ as MetadataId
     }
@@ -88,12 +381,21 @@ impl<F: Fn(TracingEvent) + 'static> Subscriber for TracingEventSender<F> {
     fn new_span(&self, span: &Attributes<'_>) -> Id {
         let metadata_id = Self::metadata_id(span.metadata());
         let span_id = u64::from(self.next_span_id.fetch_add(1, Ordering::SeqCst));
-        self.send(TracingEvent::new_span(span, metadata_id, span_id));
+        self.send(TracingEvent::from_attributes(
+            span,
+            metadata_id,
+            span_id,
+            &self.conversion_options,
+        ));
         Id::from_u64(span_id)
     }
 
     fn record(&self, span: &Id, values: &Record<'_>) {
-        self.send(TracingEvent::values_recorded(span.into_u64(), values));
+        self.send(TracingEvent::from_record(
+            span.into_u64(),
+            values,
+            &self.conversion_options,
+        ));
     }
 
     fn record_follows_from(&self, span: &Id, follows: &Id) {
@@ -105,16 +407,35 @@ impl<F: Fn(TracingEvent) + 'static> Subscriber for TracingEventSender<F> {
 
     fn event(&self, event: &Event<'_>) {
         let metadata_id = Self::metadata_id(event.metadata());
-        self.send(TracingEvent::new_event(event, metadata_id));
+        self.send(TracingEvent::from_event(
+            event,
+            metadata_id,
+            &self.conversion_options,
+        ));
     }
 
     fn enter(&self, span: &Id) {
+        let depth = self.depth.fetch_add(1, Ordering::SeqCst);
+        if !self.is_within_depth(depth) {
+            return;
+        }
         self.send(TracingEvent::SpanEntered {
             id: span.into_u64(),
         });
     }
 
     fn exit(&self, span: &Id) {
+        // An exit may have no matching enter (e.g., if the span was entered before the sender
+        // was installed), so we saturate the depth at 0 instead of underflowing it.
+        let prev_depth = self
+            .depth
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |depth| {
+                Some(depth.saturating_sub(1))
+            })
+            .unwrap(); // the update closure never returns `None`
+        if !self.is_within_depth(prev_depth.saturating_sub(1)) {
+            return;
+        }
         self.send(TracingEvent::SpanExited {
             id: span.into_u64(),
         });
@@ -3,12 +3,12 @@
 use serde::{Deserialize, Serialize};
 use tracing_core::{Level, Metadata};
 
-use core::hash::Hash;
+use core::{fmt, hash::Hash};
 #[cfg(feature = "std")]
 use std::path;
 
 use crate::{
-    alloc::{BTreeMap, Cow, String, Vec},
+    alloc::{BTreeMap, BTreeSet, Cow, String, Vec},
     TracedValues,
 };
 
@@ -57,6 +57,23 @@ pub enum CallSiteKind {
     Event,
 }
 
+impl CallSiteKind {
+    /// Returns the string presentation of this kind: `"span"` or `"event"`. This presentation
+    /// is consistent with the serialized form of the kind.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Span => "span",
+            Self::Event => "event",
+        }
+    }
+}
+
+impl fmt::Display for CallSiteKind {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter.write_str(self.as_str())
+    }
+}
+
 /// Data for a single tracing call site: either a span definition, or an event definition.
 ///
 /// This corresponds to [`Metadata`] from the `tracing-core` library, but is (de)serializable.
@@ -83,6 +100,71 @@ pub struct CallSiteData {
     pub fields: Vec<Cow<'static, str>>,
 }
 
+impl CallSiteData {
+    /// Creates call site data with the specified required properties. The remaining properties
+    /// (fields, module path, file and line) are empty and can be set via `with_*` methods.
+    /// This is useful to hand-craft event streams, e.g., for testing.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use tracing_tunnel::{CallSiteData, CallSiteKind, TracingLevel};
+    /// let data = CallSiteData::new(CallSiteKind::Span, "compute", "my_app", TracingLevel::Info)
+    ///     .with_field("count")
+    ///     .with_field("result")
+    ///     .with_module_path("my_app::compute")
+    ///     .with_file("src/compute.rs")
+    ///     .with_line(42);
+    /// assert_eq!(data.fields, ["count", "result"]);
+    /// assert_eq!(data.line, Some(42));
+    /// ```
+    pub fn new(
+        kind: CallSiteKind,
+        name: impl Into<Cow<'static, str>>,
+        target: impl Into<Cow<'static, str>>,
+        level: TracingLevel,
+    ) -> Self {
+        Self {
+            kind,
+            name: name.into(),
+            target: target.into(),
+            level,
+            module_path: None,
+            file: None,
+            line: None,
+            fields: Vec::new(),
+        }
+    }
+
+    /// Adds a field to this call site.
+    #[must_use]
+    pub fn with_field(mut self, name: impl Into<Cow<'static, str>>) -> Self {
+        self.fields.push(name.into());
+        self
+    }
+
+    /// Sets the module path of this call site.
+    #[must_use]
+    pub fn with_module_path(mut self, module_path: impl Into<Cow<'static, str>>) -> Self {
+        self.module_path = Some(module_path.into());
+        self
+    }
+
+    /// Sets the file path of this call site.
+    #[must_use]
+    pub fn with_file(mut self, file: impl Into<Cow<'static, str>>) -> Self {
+        self.file = Some(file.into());
+        self
+    }
+
+    /// Sets the line number of this call site.
+    #[must_use]
+    pub fn with_line(mut self, line: u32) -> Self {
+        self.line = Some(line);
+        self
+    }
+}
+
 impl From<&Metadata<'static>> for CallSiteData {
     fn from(metadata: &Metadata<'static>) -> Self {
         let kind = if metadata.is_span() {
@@ -186,6 +268,118 @@ pub enum TracingEvent {
         /// Values associated with the event.
         values: TracedValues<String>,
     },
+    /// Marker of a logical boundary in the event stream (e.g., the end of a workflow step)
+    /// emitted via [`TracingEventSender::flush_marker()`]. The marker can be used
+    /// by the consuming code to segment events; it is ignored by [`TracingEventReceiver`].
+    ///
+    /// [`TracingEventSender::flush_marker()`]: crate::TracingEventSender::flush_marker()
+    /// [`TracingEventReceiver`]: crate::TracingEventReceiver
+    Flush,
+}
+
+/// Constructors for the event variants. These are useful to hand-craft event streams
+/// (e.g., for testing) since the enum is non-exhaustive and thus cannot be constructed
+/// with struct literals outside this crate.
+///
+/// # Examples
+///
+/// ```
+/// # use tracing_tunnel::{
+/// #     CallSiteData, CallSiteKind, TracedValue, TracedValues, TracingEvent, TracingEventReceiver,
+/// #     TracingLevel,
+/// # };
+/// let span_site = CallSiteData::new(CallSiteKind::Span, "compute", "app", TracingLevel::Info)
+///     .with_field("count");
+/// let event_site = CallSiteData::new(CallSiteKind::Event, "event", "app", TracingLevel::Info)
+///     .with_field("message");
+/// let events = [
+///     TracingEvent::new_call_site(0, span_site),
+///     TracingEvent::new_span(1, None, 0, TracedValues::from_iter([
+///         ("count".to_owned(), TracedValue::from(5_i64)),
+///     ])),
+///     TracingEvent::span_entered(1),
+///     TracingEvent::new_call_site(1, event_site),
+///     TracingEvent::new_event(1, Some(1), TracedValues::from_iter([
+///         ("message".to_owned(), TracedValue::from("done")),
+///     ])),
+///     TracingEvent::span_exited(1),
+///     TracingEvent::span_dropped(1),
+/// ];
+///
+/// let mut receiver = TracingEventReceiver::default();
+/// for event in events {
+///     receiver.try_receive(event)?;
+/// }
+/// # Ok::<_, tracing_tunnel::ReceiveError>(())
+/// ```
+impl TracingEvent {
+    /// Creates a [`Self::NewCallSite`] event.
+    pub fn new_call_site(id: MetadataId, data: CallSiteData) -> Self {
+        Self::NewCallSite { id, data }
+    }
+
+    /// Creates a [`Self::NewSpan`] event.
+    pub fn new_span(
+        id: RawSpanId,
+        parent_id: Option<RawSpanId>,
+        metadata_id: MetadataId,
+        values: TracedValues<String>,
+    ) -> Self {
+        Self::NewSpan {
+            id,
+            parent_id,
+            metadata_id,
+            values,
+        }
+    }
+
+    /// Creates a [`Self::FollowsFrom`] event.
+    pub fn follows_from(id: RawSpanId, follows_from: RawSpanId) -> Self {
+        Self::FollowsFrom { id, follows_from }
+    }
+
+    /// Creates a [`Self::SpanEntered`] event.
+    pub fn span_entered(id: RawSpanId) -> Self {
+        Self::SpanEntered { id }
+    }
+
+    /// Creates a [`Self::SpanExited`] event.
+    pub fn span_exited(id: RawSpanId) -> Self {
+        Self::SpanExited { id }
+    }
+
+    /// Creates a [`Self::SpanCloned`] event.
+    pub fn span_cloned(id: RawSpanId) -> Self {
+        Self::SpanCloned { id }
+    }
+
+    /// Creates a [`Self::SpanDropped`] event.
+    pub fn span_dropped(id: RawSpanId) -> Self {
+        Self::SpanDropped { id }
+    }
+
+    /// Creates a [`Self::ValuesRecorded`] event.
+    pub fn values_recorded(id: RawSpanId, values: TracedValues<String>) -> Self {
+        Self::ValuesRecorded { id, values }
+    }
+
+    /// Creates a [`Self::NewEvent`] event.
+    pub fn new_event(
+        metadata_id: MetadataId,
+        parent: Option<RawSpanId>,
+        values: TracedValues<String>,
+    ) -> Self {
+        Self::NewEvent {
+            metadata_id,
+            parent,
+            values,
+        }
+    }
+
+    /// Creates a [`Self::Flush`] event.
+    pub fn flush() -> Self {
+        Self::Flush
+    }
 }
 
 impl TracingEvent {
@@ -228,4 +422,321 @@ impl TracingEvent {
             }
         }
     }
+
+    /// Compacts a sequence of events, removing events that do not influence how
+    /// the sequence is replayed by a [`TracingEventReceiver`]. This can be used
+    /// to reduce the size of archived event streams. The following transformations
+    /// are performed:
+    ///
+    /// - [`NewCallSite`](Self::NewCallSite) events not used by any span or event before
+    ///   the call site is redefined (or the stream ends) are removed. This includes
+    ///   unused call sites and duplicate call site definitions.
+    /// - [`SpanCloned`](Self::SpanCloned) immediately followed by [`SpanDropped`](Self::SpanDropped)
+    ///   for the same span is removed since it does not change the span reference count.
+    /// - [`SpanEntered`](Self::SpanEntered) immediately followed by [`SpanExited`](Self::SpanExited)
+    ///   for the same span is removed. Since nothing happens while the span is entered,
+    ///   this does not influence the span hierarchy or event placement. Note however
+    ///   that span statistics and timing collected by the receiving subscriber will differ.
+    ///
+    /// Transformations are applied repeatedly; e.g., entering a span, cloning and dropping
+    /// the span reference, and exiting the span will be removed completely.
+    ///
+    /// Metadata [persisted](crate::TracingEventReceiver::persist_metadata()) after replaying
+    /// a compacted stream may lack unused call sites compared to the original stream.
+    ///
+    /// [`TracingEventReceiver`]: crate::TracingEventReceiver
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use tracing_tunnel::{TracedValues, TracingEvent};
+    /// let events = vec![
+    ///     TracingEvent::span_entered(1),
+    ///     TracingEvent::span_cloned(1),
+    ///     TracingEvent::span_dropped(1),
+    ///     TracingEvent::span_exited(1),
+    ///     TracingEvent::span_dropped(1),
+    /// ];
+    /// let compacted = TracingEvent::compact(events);
+    /// assert_eq!(compacted.len(), 1);
+    /// assert!(matches!(compacted[0], TracingEvent::SpanDropped { id: 1 }));
+    /// ```
+    pub fn compact(events: impl IntoIterator<Item = Self>) -> Vec<Self> {
+        let events = Self::retain_used_call_sites(events.into_iter().collect());
+
+        // Remove no-op sequences.
+        let mut compacted: Vec<Self> = Vec::with_capacity(events.len());
+        for event in events {
+            let is_noop = match (compacted.last(), &event) {
+                (Some(Self::SpanEntered { id: prev_id }), Self::SpanExited { id })
+                | (Some(Self::SpanCloned { id: prev_id }), Self::SpanDropped { id }) => {
+                    prev_id == id
+                }
+                _ => false,
+            };
+            if is_noop {
+                compacted.pop();
+            } else {
+                compacted.push(event);
+            }
+        }
+        compacted
+    }
+
+    /// Removes call sites not used before their redefinition (or the stream end).
+    fn retain_used_call_sites(events: Vec<Self>) -> Vec<Self> {
+        // Traverse events in reverse so that call site usage is known when the call site
+        // definition is encountered.
+        let mut used_metadata_ids = BTreeSet::new();
+        let mut reversed_events = Vec::with_capacity(events.len());
+        for event in events.into_iter().rev() {
+            match &event {
+                Self::NewCallSite { id, .. } if !used_metadata_ids.remove(id) => continue,
+                Self::NewSpan { metadata_id, .. } | Self::NewEvent { metadata_id, .. } => {
+                    used_metadata_ids.insert(*metadata_id);
+                }
+                _ => { /* Do nothing */ }
+            }
+            reversed_events.push(event);
+        }
+        reversed_events.reverse();
+        reversed_events
+    }
+
+    /// Extracts events related to the subtree rooted at the span with the specified ID
+    /// from a sequence of events. The extracted events can be replayed by
+    /// a [`TracingEventReceiver`] independently of the remaining events; this can be used
+    /// to selectively replay a part of a recorded stream (e.g., a single workflow from
+    /// a merged log).
+    ///
+    /// The following events are retained:
+    ///
+    /// - Events for the root span and its descendants (including span creation, entering
+    ///   and exiting, recording values etc.). The root span is created without a parent.
+    /// - [`FollowsFrom`](Self::FollowsFrom) relations between spans in the subtree.
+    /// - [`NewEvent`](Self::NewEvent)s having a span in the subtree as the explicit
+    ///   or contextual parent.
+    /// - [`NewCallSite`](Self::NewCallSite) events for the call sites used by the retained
+    ///   spans and events.
+    /// - [`Flush`](Self::Flush) markers.
+    ///
+    /// Contextual parents are resolved by tracking entered spans within the stream, so the stream
+    /// is assumed to be produced by a single thread (e.g., by a WASM module).
+    ///
+    /// [`TracingEventReceiver`]: crate::TracingEventReceiver
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use std::sync::mpsc;
+    /// # use tracing_tunnel::{TracingEvent, TracingEventReceiver, TracingEventSender};
+    /// let (events_sx, events_rx) = mpsc::channel();
+    /// let sender = TracingEventSender::new(move |event| {
+    ///     events_sx.send(event).ok();
+    /// });
+    /// tracing::subscriber::with_default(sender, || {
+    ///     tracing::info_span!("first").in_scope(|| tracing::info!("started"));
+    ///     tracing::info_span!("second").in_scope(|| tracing::info!("finished"));
+    /// });
+    /// let events: Vec<_> = events_rx.try_iter().collect();
+    ///
+    /// // The second span has ID 2.
+    /// let subtree = TracingEvent::filter_subtree(events, 2);
+    /// let span_count = subtree
+    ///     .iter()
+    ///     .filter(|event| matches!(event, TracingEvent::NewSpan { .. }))
+    ///     .count();
+    /// assert_eq!(span_count, 1);
+    /// let mut receiver = TracingEventReceiver::default();
+    /// for event in subtree {
+    ///     receiver.try_receive(event)?;
+    /// }
+    /// # Ok::<_, tracing_tunnel::ReceiveError>(())
+    /// ```
+    pub fn filter_subtree(
+        events: impl IntoIterator<Item = Self>,
+        root_span_id: RawSpanId,
+    ) -> Vec<Self> {
+        let mut subtree_span_ids = BTreeSet::new();
+        let mut entered_span_ids = Vec::new();
+        let mut filtered = Vec::new();
+        for mut event in events {
+            let is_retained = match &mut event {
+                Self::NewCallSite { .. } | Self::Flush => true,
+                Self::NewSpan { id, parent_id, .. } => {
+                    let parent = parent_id.or_else(|| entered_span_ids.last().copied());
+                    let is_retained = if *id == root_span_id {
+                        *parent_id = None;
+                        true
+                    } else {
+                        parent.is_some_and(|parent| subtree_span_ids.contains(&parent))
+                    };
+                    if is_retained {
+                        subtree_span_ids.insert(*id);
+                    } else {
+                        subtree_span_ids.remove(id); // in case the span ID is reused
+                    }
+                    is_retained
+                }
+                Self::FollowsFrom { id, follows_from } => {
+                    subtree_span_ids.contains(id) && subtree_span_ids.contains(follows_from)
+                }
+                Self::SpanEntered { id } => {
+                    entered_span_ids.push(*id);
+                    subtree_span_ids.contains(id)
+                }
+                Self::SpanExited { id } => {
+                    if let Some(pos) = entered_span_ids.iter().rposition(|entered| entered == id) {
+                        entered_span_ids.remove(pos);
+                    }
+                    subtree_span_ids.contains(id)
+                }
+                Self::SpanCloned { id }
+                | Self::SpanDropped { id }
+                | Self::ValuesRecorded { id, .. } => subtree_span_ids.contains(id),
+                Self::NewEvent { parent, .. } => {
+                    let parent = parent.or_else(|| entered_span_ids.last().copied());
+                    parent.is_some_and(|parent| subtree_span_ids.contains(&parent))
+                }
+            };
+            if is_retained {
+                filtered.push(event);
+            }
+        }
+        Self::retain_used_call_sites(filtered)
+    }
+}
+
+/// [`TracingEvent`] together with the timestamp of its emission.
+///
+/// Timed events are produced by a [`TracingEventSender`] created with
+/// [`TracingEventSender::new_timed()`]. They can be used to faithfully reproduce trace timing,
+/// e.g., to estimate span latencies on the receiving side. A [`TracingEventReceiver`] consumes
+/// the wrapped [`event`](Self::event); the timestamp can be used or ignored by the receiving code.
+///
+/// [`TracingEventSender`]: crate::TracingEventSender
+/// [`TracingEventSender::new_timed()`]: crate::TracingEventSender::new_timed()
+/// [`TracingEventReceiver`]: crate::TracingEventReceiver
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TimedTracingEvent {
+    /// Monotonic timestamp of the event emission in nanoseconds. The timestamp is measured
+    /// relative to an unspecified starting point (e.g., the sender creation), so only
+    /// differences among timestamps in the same event stream are meaningful.
+    pub timestamp: u64,
+    /// Wrapped event.
+    pub event: TracingEvent,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::alloc::format;
+
+    #[test]
+    fn call_site_kind_presentation() {
+        assert_eq!(CallSiteKind::Span.as_str(), "span");
+        assert_eq!(CallSiteKind::Event.as_str(), "event");
+        for kind in [CallSiteKind::Span, CallSiteKind::Event] {
+            assert_eq!(format!("{kind}"), kind.as_str());
+            let serialized = serde_json::to_string(&kind).unwrap();
+            assert_eq!(serialized, format!("\"{kind}\""));
+        }
+    }
+
+    #[test]
+    fn compacting_events() {
+        let span_site =
+            CallSiteData::new(CallSiteKind::Span, "compute", "test", TracingLevel::Info);
+        let event_site =
+            CallSiteData::new(CallSiteKind::Event, "event", "test", TracingLevel::Warn);
+        let unused_site =
+            CallSiteData::new(CallSiteKind::Event, "unused", "test", TracingLevel::Info);
+        let events = [
+            TracingEvent::new_call_site(0, span_site.clone()),
+            TracingEvent::new_call_site(0, span_site), // duplicate definition
+            TracingEvent::new_call_site(1, event_site.clone()),
+            TracingEvent::new_call_site(2, unused_site),
+            TracingEvent::new_span(1, None, 0, TracedValues::new()),
+            TracingEvent::span_entered(1),
+            TracingEvent::span_exited(1),
+            TracingEvent::span_entered(1),
+            TracingEvent::span_cloned(1),
+            TracingEvent::span_dropped(1),
+            TracingEvent::new_event(1, None, TracedValues::new()),
+            TracingEvent::span_exited(1),
+            TracingEvent::span_dropped(1),
+            // Redefinition after use must be retained.
+            TracingEvent::new_call_site(1, event_site),
+            TracingEvent::new_event(1, None, TracedValues::new()),
+        ];
+
+        let compacted = TracingEvent::compact(events);
+        let rendered: Vec<_> = compacted.iter().map(TracingEvent::render).collect();
+        assert_eq!(
+            rendered,
+            [
+                "site#0 span INFO \"compute\" @ test",
+                "site#1 event WARN \"event\" @ test",
+                "+span#1 site#0",
+                "enter#1",
+                "event site#1",
+                "exit#1",
+                "-span#1",
+                "site#1 event WARN \"event\" @ test",
+                "event site#1",
+            ]
+        );
+    }
+
+    #[test]
+    fn filtering_subtree() {
+        let span_site =
+            CallSiteData::new(CallSiteKind::Span, "compute", "test", TracingLevel::Info);
+        let event_site =
+            CallSiteData::new(CallSiteKind::Event, "event", "test", TracingLevel::Warn);
+        let other_site =
+            CallSiteData::new(CallSiteKind::Event, "other", "test", TracingLevel::Info);
+        let events = [
+            TracingEvent::new_call_site(0, span_site),
+            TracingEvent::new_call_site(1, event_site),
+            TracingEvent::new_call_site(2, other_site),
+            TracingEvent::new_span(1, None, 0, TracedValues::new()),
+            TracingEvent::span_entered(1),
+            TracingEvent::new_event(2, None, TracedValues::new()), // outside the subtree
+            TracingEvent::new_span(2, None, 0, TracedValues::new()), // subtree root
+            TracingEvent::new_span(3, Some(2), 0, TracedValues::new()), // explicit parent
+            TracingEvent::span_entered(2),
+            TracingEvent::new_span(4, None, 0, TracedValues::new()), // contextual parent
+            TracingEvent::new_event(1, None, TracedValues::new()),
+            TracingEvent::follows_from(4, 1), // references a span outside the subtree
+            TracingEvent::span_exited(2),
+            TracingEvent::new_event(1, Some(3), TracedValues::new()),
+            TracingEvent::new_event(1, None, TracedValues::new()), // outside the subtree
+            TracingEvent::span_dropped(4),
+            TracingEvent::span_dropped(3),
+            TracingEvent::span_dropped(2),
+            TracingEvent::span_exited(1),
+            TracingEvent::span_dropped(1),
+        ];
+
+        let filtered = TracingEvent::filter_subtree(events, 2);
+        let rendered: Vec<_> = filtered.iter().map(TracingEvent::render).collect();
+        assert_eq!(
+            rendered,
+            [
+                "site#0 span INFO \"compute\" @ test",
+                "site#1 event WARN \"event\" @ test",
+                "+span#2 site#0",
+                "+span#3 site#0 ^#2",
+                "enter#2",
+                "+span#4 site#0",
+                "event site#1",
+                "exit#2",
+                "event site#1 ^#3",
+                "-span#4",
+                "-span#3",
+                "-span#2",
+            ]
+        );
+    }
 }
@@ -2,9 +2,14 @@
 
 use serde::{Deserialize, Serialize};
 
-use core::{borrow::Borrow, fmt};
+use core::{
+    borrow::Borrow,
+    fmt,
+    hash::{Hash, Hasher},
+    mem,
+};
 
-use crate::alloc::{format, String, ToOwned};
+use crate::alloc::{format, String, ToOwned, Vec};
 
 #[cfg(feature = "std")]
 mod error {
@@ -13,7 +18,7 @@ mod error {
     use std::{error, fmt};
 
     /// (De)serializable presentation for an error recorded as a value in a tracing span or event.
-    #[derive(Debug, Clone, Serialize, Deserialize)]
+    #[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
     #[non_exhaustive]
     #[cfg_attr(docsrs, doc(cfg(feature = "std")))]
     pub struct TracedError {
@@ -52,24 +57,149 @@ pub use self::error::TracedError;
 
 /// Opaque wrapper for a [`Debug`](fmt::Debug)gable object recorded as a value
 /// in a tracing span or event.
-#[derive(Clone, Serialize, Deserialize)]
-#[serde(transparent)]
-pub struct DebugObject(String);
+///
+/// # Serialization
+///
+/// An object is serialized as a string with its `Debug` output. A [truncated](Self::is_truncated())
+/// object is serialized as an object with a single `truncated` field containing the output
+/// so that the truncation flag is preserved.
+#[derive(Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(from = "DebugObjectRepr", into = "DebugObjectRepr")]
+pub struct DebugObject {
+    output: String,
+    truncated: bool,
+}
 
 impl fmt::Debug for DebugObject {
     fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
-        formatter.write_str(&self.0)
+        formatter.write_str(&self.output)
+    }
+}
+
+impl DebugObject {
+    /// Marker appended to the [`Debug`](fmt::Debug) output truncated because of
+    /// the [debug output limit](crate::ConversionOptions::with_debug_limit()).
+    pub const TRUNCATION_MARKER: &'static str = "…";
+
+    fn new(object: &dyn fmt::Debug, limit: Option<usize>) -> Self {
+        let mut writer = LimitedWriter {
+            buffer: String::new(),
+            limit: limit.unwrap_or(usize::MAX),
+            is_truncated: false,
+        };
+        // An error can be caused either by truncation (which we handle below),
+        // or by a faulty `Debug` implementation, in which case we keep the partial output.
+        fmt::write(&mut writer, format_args!("{object:?}")).ok();
+        if writer.is_truncated {
+            writer.buffer.push_str(Self::TRUNCATION_MARKER);
+        }
+        Self {
+            output: writer.buffer,
+            truncated: writer.is_truncated,
+        }
+    }
+
+    #[cfg(test)]
+    pub(crate) fn from_output(output: impl Into<String>) -> Self {
+        Self {
+            output: output.into(),
+            truncated: false,
+        }
+    }
+
+    /// Checks whether the [`Debug`](fmt::Debug) output was truncated because it exceeded
+    /// the [debug output limit](crate::ConversionOptions::with_debug_limit()). Truncated output
+    /// ends with [`Self::TRUNCATION_MARKER`].
+    pub fn is_truncated(&self) -> bool {
+        self.truncated
+    }
+}
+
+/// Serialized presentation of a [`DebugObject`].
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum DebugObjectRepr {
+    Full(String),
+    Truncated { truncated: String },
+}
+
+impl From<DebugObjectRepr> for DebugObject {
+    fn from(repr: DebugObjectRepr) -> Self {
+        match repr {
+            DebugObjectRepr::Full(output) => Self {
+                output,
+                truncated: false,
+            },
+            DebugObjectRepr::Truncated { truncated } => Self {
+                output: truncated,
+                truncated: true,
+            },
+        }
+    }
+}
+
+impl From<DebugObject> for DebugObjectRepr {
+    fn from(object: DebugObject) -> Self {
+        if object.truncated {
+            Self::Truncated {
+                truncated: object.output,
+            }
+        } else {
+            Self::Full(object.output)
+        }
+    }
+}
+
+/// Writer bounding the length of the written string. Once the limit is exceeded,
+/// it returns an error, so that formatting the remaining output is skipped. If a `Debug`
+/// implementation ignores the error, subsequent writes are ignored as well.
+#[derive(Debug)]
+struct LimitedWriter {
+    buffer: String,
+    limit: usize,
+    is_truncated: bool,
+}
+
+impl fmt::Write for LimitedWriter {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        if self.is_truncated {
+            return Err(fmt::Error);
+        }
+        let remaining = self.limit - self.buffer.len();
+        if s.len() <= remaining {
+            self.buffer.push_str(s);
+            return Ok(());
+        }
+
+        let mut end = remaining;
+        while !s.is_char_boundary(end) {
+            end -= 1;
+        }
+        self.buffer.push_str(&s[..end]);
+        self.is_truncated = true;
+        Err(fmt::Error)
     }
 }
 
 /// Returns the [`Debug`](fmt::Debug) representation of the object.
 impl AsRef<str> for DebugObject {
     fn as_ref(&self) -> &str {
-        &self.0
+        &self.output
     }
 }
 
 /// Value recorded in a tracing span or event.
+///
+/// # Equality and hashing
+///
+/// Values are compared structurally; values of different variants (e.g., [`Self::Int`]
+/// and [`Self::UInt`]) are never equal. To allow using values as keys in hash maps / sets,
+/// `TracedValue` implements [`Eq`] and [`Hash`], which requires special handling
+/// of [`Self::Float`] values:
+///
+/// - `0.0` and `-0.0` are equal and have the same hash, same as with `f64` comparison.
+/// - All NaN values are equal to each other and have the same hash (unlike with `f64`
+///   comparison, where NaN is not equal to itself).
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
 #[non_exhaustive]
@@ -95,7 +225,11 @@ pub enum TracedValue {
 impl TracedValue {
     #[doc(hidden)] // public for testing purposes
     pub fn debug(object: &dyn fmt::Debug) -> Self {
-        Self::Object(DebugObject(format!("{object:?}")))
+        Self::debug_with_limit(object, None)
+    }
+
+    pub(crate) fn debug_with_limit(object: &dyn fmt::Debug, limit: Option<usize>) -> Self {
+        Self::Object(DebugObject::new(object, limit))
     }
 
     /// Returns value as a Boolean, or `None` if it's not a Boolean value.
@@ -122,6 +256,32 @@ impl TracedValue {
         f64::from_value(self)
     }
 
+    /// Checks whether this value is a number ([`Self::Float`], [`Self::Int`] or [`Self::UInt`])
+    /// within `epsilon` of the `other` value. Integer values are converted to `f64`
+    /// for comparison, which may lose precision for large values. Values of other types
+    /// never match.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use tracing_tunnel::TracedValue;
+    /// let value = TracedValue::from(0.3333);
+    /// assert!(value.approx_eq(0.333, 1e-3));
+    /// assert!(!value.approx_eq(0.333, 1e-5));
+    /// assert!(TracedValue::from(2_u64).approx_eq(2.0, 0.0));
+    /// assert!(!TracedValue::from("2").approx_eq(2.0, 1.0));
+    /// ```
+    #[allow(clippy::cast_precision_loss)] // documented
+    pub fn approx_eq(&self, other: f64, epsilon: f64) -> bool {
+        let value = match self {
+            Self::Float(value) => *value,
+            Self::Int(value) => *value as f64,
+            Self::UInt(value) => *value as f64,
+            _ => return false,
+        };
+        (value - other).abs() <= epsilon
+    }
+
     /// Returns value as a string, or `None` if it's not one.
     #[inline]
     pub fn as_str(&self) -> Option<&str> {
@@ -132,7 +292,7 @@ impl TracedValue {
     /// output as the provided `object`.
     pub fn is_debug(&self, object: &dyn fmt::Debug) -> bool {
         match self {
-            Self::Object(value) => value.0 == format!("{object:?}"),
+            Self::Object(value) => value.output == format!("{object:?}"),
             _ => false,
         }
     }
@@ -141,17 +301,198 @@ impl TracedValue {
     /// is not [`Self::Object`].
     pub fn as_debug_str(&self) -> Option<&str> {
         match self {
-            Self::Object(value) => Some(&value.0),
+            Self::Object(value) => Some(&value.output),
             _ => None,
         }
     }
 
+    /// Checks whether this value is a [`Debug`](fmt::Debug) presentation of [`None`].
+    ///
+    /// `tracing` has no native null value, so optional fields are usually recorded
+    /// using their `Debug` implementation (e.g., `tracing::info!(value = ?maybe_value)`).
+    /// For `Option::None`, this produces a [`Self::Object`] with the `None` output.
+    /// Unlike a comparison with `"None"`, this method does not match [`Self::String`]s,
+    /// so a recorded `None` can be distinguished from a string with the same contents.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use tracing_tunnel::TracedValue;
+    /// let value = TracedValue::debug(&None::<u32>);
+    /// assert!(value.is_none_debug());
+    /// let value = TracedValue::from("None");
+    /// assert!(!value.is_none_debug());
+    /// let value = TracedValue::debug(&Some(42));
+    /// assert!(!value.is_none_debug());
+    /// ```
+    pub fn is_none_debug(&self) -> bool {
+        self.as_debug_str() == Some("None")
+    }
+
+    /// Parses this value as a [`Debug`](fmt::Debug) output of a struct (i.e., a string
+    /// with the `Name { field: value, .. }` shape) and returns the string presentation
+    /// of the specified field. Both the standard and alternate (`{:#?}`) `Debug` formats
+    /// are supported. Returns `None` if this value is not [`Self::Object`], does not have
+    /// the expected shape, or does not contain the field.
+    ///
+    /// Parsing is best-effort: it tracks nesting of brackets and skips over string literals,
+    /// but may fail for unusual `Debug` implementations.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use tracing_tunnel::TracedValue;
+    /// #[derive(Debug)]
+    /// struct Labels { method: &'static str, codes: Vec<u16> }
+    ///
+    /// let labels = Labels { method: "GET", codes: vec![200, 404] };
+    /// let value = TracedValue::debug(&labels);
+    /// assert_eq!(value.debug_field("method"), Some("\"GET\""));
+    /// assert_eq!(value.debug_field("codes"), Some("[200, 404]"));
+    /// assert_eq!(value.debug_field("missing"), None);
+    /// ```
+    pub fn debug_field(&self, name: &str) -> Option<&str> {
+        let (_, body) = split_debug_container(self.as_debug_str()?, '{', '}')?;
+        split_debug_items(body)?.into_iter().find_map(|item| {
+            let (field_name, value) = item.split_once(':')?;
+            (field_name.trim_end() == name).then(|| value.trim_start())
+        })
+    }
+
+    /// Parses this value as a [`Debug`](fmt::Debug) output of a tuple or a tuple struct
+    /// (i.e., a string with the `Name(value, ..)` or `(value, ..)` shape) and returns
+    /// the string presentation of its elements. Returns `None` if this value
+    /// is not [`Self::Object`] or does not have the expected shape.
+    ///
+    /// Parsing is best-effort; see [`Self::debug_field()`] for details.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use tracing_tunnel::TracedValue;
+    /// let value = TracedValue::debug(&(1, "test", Some(2)));
+    /// assert_eq!(value.as_debug_tuple().unwrap(), ["1", "\"test\"", "Some(2)"]);
+    /// let value = TracedValue::debug(&Some([1, 2]));
+    /// assert_eq!(value.as_debug_tuple().unwrap(), ["[1, 2]"]);
+    /// ```
+    pub fn as_debug_tuple(&self) -> Option<Vec<&str>> {
+        let (_, body) = split_debug_container(self.as_debug_str()?, '(', ')')?;
+        split_debug_items(body)
+    }
+
     #[cfg(feature = "std")]
     pub(crate) fn error(err: &(dyn std::error::Error + 'static)) -> Self {
         Self::Error(TracedError::new(err))
     }
 }
 
+/// Splits `Debug` output with the `Name{open}..{close}` shape into the name and the contents
+/// between the brackets.
+fn split_debug_container(debug_str: &str, open: char, close: char) -> Option<(&str, &str)> {
+    let debug_str = debug_str.trim();
+    let (name, rest) = debug_str.split_once(open)?;
+    let name = name.trim_end();
+    let is_valid_name = name
+        .chars()
+        .all(|ch| ch.is_alphanumeric() || matches!(ch, '_' | ':' | '<' | '>'));
+    if !is_valid_name {
+        return None;
+    }
+    let body = rest.strip_suffix(close)?;
+    Some((name, body))
+}
+
+/// Splits comma-separated `Debug` items, taking nested brackets and string literals
+/// into account. Returns `None` if brackets are unbalanced.
+fn split_debug_items(body: &str) -> Option<Vec<&str>> {
+    let mut items = Vec::new();
+    let mut depth = 0_usize;
+    let mut in_string = false;
+    let mut is_escaped = false;
+    let mut item_start = 0;
+    for (pos, ch) in body.char_indices() {
+        if in_string {
+            match ch {
+                _ if is_escaped => is_escaped = false,
+                '\\' => is_escaped = true,
+                '"' => in_string = false,
+                _ => { /* do nothing */ }
+            }
+            continue;
+        }
+
+        match ch {
+            '"' => in_string = true,
+            '(' | '[' | '{' => depth += 1,
+            ')' | ']' | '}' => depth = depth.checked_sub(1)?,
+            ',' if depth == 0 => {
+                items.push(body[item_start..pos].trim());
+                item_start = pos + 1;
+            }
+            _ => { /* do nothing */ }
+        }
+    }
+    if depth > 0 || in_string {
+        return None;
+    }
+
+    let last_item = body[item_start..].trim();
+    if !last_item.is_empty() {
+        // The last item may be empty because of a trailing comma (e.g., in the alternate format).
+        items.push(last_item);
+    }
+    Some(items)
+}
+
+impl TracedValue {
+    /// Normalizes a floating-point value for comparison and hashing.
+    fn float_bits(value: f64) -> u64 {
+        if value.is_nan() {
+            f64::NAN.to_bits()
+        } else if value == 0.0 {
+            0 // normalizes `-0.0`
+        } else {
+            value.to_bits()
+        }
+    }
+}
+
+impl PartialEq for TracedValue {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::Bool(this), Self::Bool(other)) => this == other,
+            (Self::Int(this), Self::Int(other)) => this == other,
+            (Self::UInt(this), Self::UInt(other)) => this == other,
+            (Self::Float(this), Self::Float(other)) => {
+                Self::float_bits(*this) == Self::float_bits(*other)
+            }
+            (Self::String(this), Self::String(other)) => this == other,
+            (Self::Object(this), Self::Object(other)) => this == other,
+            #[cfg(feature = "std")]
+            (Self::Error(this), Self::Error(other)) => this == other,
+            _ => false,
+        }
+    }
+}
+
+impl Eq for TracedValue {}
+
+impl Hash for TracedValue {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        mem::discriminant(self).hash(state);
+        match self {
+            Self::Bool(value) => value.hash(state),
+            Self::Int(value) => value.hash(state),
+            Self::UInt(value) => value.hash(state),
+            Self::Float(value) => Self::float_bits(*value).hash(state),
+            Self::String(value) => value.hash(state),
+            Self::Object(value) => value.hash(state),
+            #[cfg(feature = "std")]
+            Self::Error(value) => value.hash(state),
+        }
+    }
+}
+
 /// Fallible conversion from a [`TracedValue`] reference.
 pub trait FromTracedValue<'a> {
     /// Output of the conversion.
@@ -269,6 +610,21 @@ impl From<&str> for TracedValue {
     }
 }
 
+impl From<String> for TracedValue {
+    fn from(value: String) -> Self {
+        Self::String(value)
+    }
+}
+
+/// Converts a `char` into a [`String`](TracedValue::String) value. Note that `tracing`
+/// records `char` fields as [debug objects](TracedValue::Object), so this conversion
+/// is mostly useful to construct synthetic values.
+impl From<char> for TracedValue {
+    fn from(value: char) -> Self {
+        Self::String(value.into())
+    }
+}
+
 impl PartialEq<&str> for TracedValue {
     fn eq(&self, other: &&str) -> bool {
         match self {
@@ -283,3 +639,144 @@ impl PartialEq<TracedValue> for &str {
         other == self
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::{hash_map::DefaultHasher, HashSet},
+        hash::{Hash, Hasher},
+    };
+
+    use super::*;
+
+    fn hash(value: &TracedValue) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        value.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    #[test]
+    fn equal_values_have_equal_hashes() {
+        let pairs = [
+            (TracedValue::from(true), TracedValue::from(true)),
+            (TracedValue::from(42_i64), TracedValue::Int(42)),
+            (TracedValue::from(42_u64), TracedValue::UInt(42)),
+            (TracedValue::from(1.5), TracedValue::Float(1.5)),
+            (
+                TracedValue::from("test"),
+                TracedValue::String("test".into()),
+            ),
+            (TracedValue::debug(&Some(1)), TracedValue::debug(&Some(1))),
+        ];
+        for (first, second) in &pairs {
+            assert_eq!(first, second);
+            assert_eq!(hash(first), hash(second));
+        }
+    }
+
+    #[test]
+    fn values_of_different_variants_are_not_equal() {
+        assert_ne!(TracedValue::Int(42), TracedValue::UInt(42));
+        assert_ne!(TracedValue::from("42"), TracedValue::debug(&"42"));
+        assert_ne!(TracedValue::from(42_i64), TracedValue::from(42.0));
+    }
+
+    #[test]
+    fn float_values_are_normalized() {
+        let zero = TracedValue::from(0.0);
+        let negative_zero = TracedValue::from(-0.0);
+        assert_eq!(zero, -0.0); // consistency with `f64` comparison
+        assert_eq!(zero, negative_zero);
+        assert_eq!(hash(&zero), hash(&negative_zero));
+
+        let nan = TracedValue::from(f64::NAN);
+        let other_nan = TracedValue::from(-f64::NAN);
+        assert_eq!(nan, nan);
+        assert_eq!(nan, other_nan);
+        assert_eq!(hash(&nan), hash(&other_nan));
+
+        let values: HashSet<_> = [zero, negative_zero, nan, other_nan, TracedValue::from(1.0)]
+            .into_iter()
+            .collect();
+        assert_eq!(values.len(), 3);
+    }
+
+    #[test]
+    fn parsing_debug_structs() {
+        #[derive(Debug)]
+        #[allow(dead_code)] // fields are only used via `Debug`
+        struct Inner {
+            name: &'static str,
+            items: Vec<u32>,
+        }
+
+        #[derive(Debug)]
+        #[allow(dead_code)] // fields are only used via `Debug`
+        struct Outer {
+            id: u64,
+            inner: Inner,
+            tag: Option<char>,
+        }
+
+        let object = Outer {
+            id: 42,
+            inner: Inner {
+                name: "test, with {brackets\" and: quotes",
+                items: vec![1, 2],
+            },
+            tag: Some('x'),
+        };
+        for value in [
+            TracedValue::debug(&object),
+            TracedValue::Object(DebugObject::from_output(format!("{object:#?}"))),
+        ] {
+            assert_eq!(value.debug_field("id"), Some("42"));
+            assert_eq!(value.debug_field("missing"), None);
+
+            let reparse = |name| {
+                TracedValue::Object(DebugObject::from_output(value.debug_field(name).unwrap()))
+            };
+            assert_eq!(reparse("tag").as_debug_tuple().unwrap(), ["'x'"]);
+            let inner = reparse("inner");
+            assert_eq!(
+                inner.debug_field("name"),
+                Some(r#""test, with {brackets\" and: quotes""#)
+            );
+            assert!(inner.debug_field("items").unwrap().contains('2'));
+        }
+
+        assert_eq!(
+            TracedValue::from("Outer { id: 42 }").debug_field("id"),
+            None
+        );
+        assert_eq!(TracedValue::debug(&[1, 2]).debug_field("id"), None);
+        let unbalanced = TracedValue::Object(DebugObject::from_output("Outer { id: (42 }"));
+        assert_eq!(unbalanced.debug_field("id"), None);
+    }
+
+    #[test]
+    fn parsing_debug_tuples() {
+        let value = TracedValue::debug(&(1, "a, b", [2, 3]));
+        assert_eq!(
+            value.as_debug_tuple().unwrap(),
+            ["1", r#""a, b""#, "[2, 3]"]
+        );
+        let value = TracedValue::debug(&Some(5));
+        assert_eq!(value.as_debug_tuple().unwrap(), ["5"]);
+        let value = TracedValue::debug(&());
+        assert!(value.as_debug_tuple().unwrap().is_empty());
+        let value = TracedValue::debug(&None::<u32>);
+        assert_eq!(value.as_debug_tuple(), None);
+        let value = TracedValue::debug(&"(1, 2)");
+        assert_eq!(value.as_debug_tuple(), None);
+    }
+
+    #[test]
+    fn converting_chars_and_strings() {
+        let value = TracedValue::from('x');
+        assert_eq!(value, "x");
+        assert_eq!(value.as_str(), Some("x"));
+        assert_eq!(TracedValue::from('ё'), TracedValue::from(String::from("ё")));
+        assert_ne!(TracedValue::from('1'), TracedValue::from(1_i64));
+    }
+}
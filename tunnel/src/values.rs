@@ -23,6 +23,22 @@ use crate::{
 /// Functionally this collection is similar to a `HashMap<S, TracedValue>`,
 /// with the key difference being that the order of [iteration](Self::iter()) is the insertion order.
 /// If a value is updated, including via [`Extend`] etc., it preserves its old placement.
+///
+/// Equality comparison is order-independent: two collections are equal if they contain
+/// the same names and the values for each name are equal, regardless of the insertion order.
+/// Collections with different name types (e.g., `&'static str` and `String`) can be compared.
+///
+/// # Examples
+///
+/// ```
+/// # use tracing_tunnel::{TracedValue, TracedValues};
+/// let values = TracedValues::from_iter([("a", TracedValue::from(1_i64)), ("b", "test".into())]);
+/// let reordered = TracedValues::from_iter([
+///     ("b".to_owned(), TracedValue::from("test")),
+///     ("a".to_owned(), 1_i64.into()),
+/// ]);
+/// assert_eq!(values, reordered);
+/// ```
 #[derive(Clone)]
 pub struct TracedValues<S> {
     // Using `Vec` for entries is inefficient for random access, but seems acceptable given that
@@ -47,33 +63,165 @@ impl<S: AsRef<str>> fmt::Debug for TracedValues<S> {
     }
 }
 
+impl<S: AsRef<str>, T: AsRef<str>> PartialEq<TracedValues<T>> for TracedValues<S> {
+    fn eq(&self, other: &TracedValues<T>) -> bool {
+        // Names are unique within a collection, so it's sufficient to check lengths
+        // and inclusion.
+        self.len() == other.len()
+            && self
+                .iter()
+                .all(|(name, value)| other.get(name) == Some(value))
+    }
+}
+
+/// Options for converting values recorded in tracing spans and events into [`TracedValues`],
+/// used in [`TracedValues::from_values_with_options()`] and similar methods.
+///
+/// # Examples
+///
+/// ```
+/// # use tracing_core::Event;
+/// # use tracing_tunnel::{ConversionOptions, TracedValues};
+/// const OPTIONS: ConversionOptions<'static> = ConversionOptions::new().with_debug_limit(1_024);
+///
+/// fn record(event: &Event<'_>) -> TracedValues<&'static str> {
+///     TracedValues::from_event_with_options(event, &OPTIONS)
+/// }
+/// ```
+#[derive(Clone, Copy, Default)]
+pub struct ConversionOptions<'a> {
+    debug_limit: Option<usize>,
+    debug_converters: &'a [&'a DebugValueConverter],
+}
+
+impl fmt::Debug for ConversionOptions<'_> {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter
+            .debug_struct("ConversionOptions")
+            .field("debug_limit", &self.debug_limit)
+            .field("debug_converters", &self.debug_converters.len())
+            .finish()
+    }
+}
+
+impl<'a> ConversionOptions<'a> {
+    /// Creates default options. By default, [`Debug`](fmt::Debug) output is not limited,
+    /// and no [converters](DebugValueConverter) are used.
+    pub const fn new() -> Self {
+        Self {
+            debug_limit: None,
+            debug_converters: &[],
+        }
+    }
+
+    /// Sets the limit on the length of [`Debug`](fmt::Debug) output (in bytes) for
+    /// [objects](TracedValue::Object). Oversized output is truncated and marked with
+    /// [`DebugObject::TRUNCATION_MARKER`]; the truncated objects can be detected using
+    /// [`DebugObject::is_truncated()`]. This bounds memory consumption for pathological fields
+    /// (e.g., huge collections).
+    ///
+    /// [`DebugObject::TRUNCATION_MARKER`]: crate::DebugObject::TRUNCATION_MARKER
+    /// [`DebugObject::is_truncated()`]: crate::DebugObject::is_truncated()
+    #[must_use]
+    pub const fn with_debug_limit(mut self, limit: usize) -> Self {
+        self.debug_limit = Some(limit);
+        self
+    }
+
+    /// Returns the limit on the length of [`Debug`](fmt::Debug) output, if set.
+    pub const fn debug_limit(&self) -> Option<usize> {
+        self.debug_limit
+    }
+
+    /// Sets converters for values that are only recorded via their [`Debug`](fmt::Debug)
+    /// implementation (e.g., custom types recorded as `?value`). The first converter returning
+    /// `Some(_)` determines the value; if all converters return `None`, the value is recorded
+    /// as [`TracedValue::Object`]. The [debug output limit](Self::with_debug_limit()) only applies
+    /// to values not handled by converters.
+    #[must_use]
+    pub const fn with_debug_converters(
+        mut self,
+        converters: &'a [&'a DebugValueConverter],
+    ) -> Self {
+        self.debug_converters = converters;
+        self
+    }
+
+    /// Returns converters for values recorded via their [`Debug`](fmt::Debug) implementation.
+    pub const fn debug_converters(&self) -> &'a [&'a DebugValueConverter] {
+        self.debug_converters
+    }
+}
+
 impl<S: From<&'static str> + AsRef<str>> TracedValues<S> {
     /// Creates traced values from the specified value set.
     pub fn from_values(values: &ValueSet<'_>) -> Self {
-        let mut visitor = TracedValueVisitor {
-            values: Self::default(),
-        };
+        Self::from_values_with_options(values, &ConversionOptions::new())
+    }
+
+    /// Creates traced values from the specified value set using the specified conversion options.
+    pub fn from_values_with_options(values: &ValueSet<'_>, options: &ConversionOptions) -> Self {
+        let mut visitor = TracedValueVisitor::new(values.len(), options);
         values.record(&mut visitor);
         visitor.values
     }
 
     /// Creates traced values from the specified record.
     pub fn from_record(values: &Record<'_>) -> Self {
-        let mut visitor = TracedValueVisitor {
-            values: Self::default(),
-        };
+        Self::from_record_with_options(values, &ConversionOptions::new())
+    }
+
+    /// Creates traced values from the specified record using the specified conversion options.
+    pub fn from_record_with_options(values: &Record<'_>, options: &ConversionOptions) -> Self {
+        let mut visitor = TracedValueVisitor::new(values.len(), options);
         values.record(&mut visitor);
         visitor.values
     }
 
     /// Creates traced values from the values in the specified event.
     pub fn from_event(event: &Event<'_>) -> Self {
-        let mut visitor = TracedValueVisitor {
-            values: Self::default(),
-        };
+        Self::from_event_with_options(event, &ConversionOptions::new())
+    }
+
+    /// Creates traced values from the values in the specified event using the specified
+    /// conversion options.
+    pub fn from_event_with_options(event: &Event<'_>, options: &ConversionOptions) -> Self {
+        let capacity = event.metadata().fields().len();
+        let mut visitor = TracedValueVisitor::new(capacity, options);
         event.record(&mut visitor);
         visitor.values
     }
+
+    /// Creates traced values from the values in the specified event, using the provided
+    /// `converters` for values that are only recorded via their [`Debug`](fmt::Debug)
+    /// implementation (e.g., custom types recorded as `?value`). This is a shortcut for
+    /// [`Self::from_event_with_options()`] with [`ConversionOptions::with_debug_converters()`];
+    /// use the options directly to apply converters to span values, or to combine converters
+    /// with other options.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use tracing_core::{field::Field, Event};
+    /// # use core::fmt;
+    /// # use tracing_tunnel::{TracedValue, TracedValues};
+    /// fn convert_id(field: &Field, value: &dyn fmt::Debug) -> Option<TracedValue> {
+    ///     if field.name() != "id" {
+    ///         return None;
+    ///     }
+    ///     let value = format!("{value:?}");
+    ///     let value = value.strip_prefix("Id(")?.strip_suffix(')')?;
+    ///     value.parse::<u64>().ok().map(TracedValue::from)
+    /// }
+    ///
+    /// fn record(event: &Event<'_>) -> TracedValues<&'static str> {
+    ///     TracedValues::from_event_with(event, &[&convert_id])
+    /// }
+    /// ```
+    pub fn from_event_with(event: &Event<'_>, converters: &[&DebugValueConverter]) -> Self {
+        let options = ConversionOptions::new().with_debug_converters(converters);
+        Self::from_event_with_options(event, &options)
+    }
 }
 
 impl<S: AsRef<str>> TracedValues<S> {
@@ -82,11 +230,24 @@ impl<S: AsRef<str>> TracedValues<S> {
         Self::default()
     }
 
+    /// Creates new empty values with capacity sufficient to hold the specified number of values
+    /// without reallocation.
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            inner: Vec::with_capacity(capacity),
+        }
+    }
+
     /// Returns the number of stored values.
     pub fn len(&self) -> usize {
         self.inner.len()
     }
 
+    /// Returns the number of values this collection can hold without reallocation.
+    pub fn capacity(&self) -> usize {
+        self.inner.capacity()
+    }
+
     /// Checks whether this collection of values is empty.
     pub fn is_empty(&self) -> bool {
         self.inner.is_empty()
@@ -103,6 +264,16 @@ impl<S: AsRef<str>> TracedValues<S> {
         })
     }
 
+    /// Checks whether a value with the specified name is present.
+    pub fn contains_key(&self, name: &str) -> bool {
+        self.get(name).is_some()
+    }
+
+    /// Iterates over the names of the contained values.
+    pub fn keys(&self) -> impl Iterator<Item = &str> + '_ {
+        self.inner.iter().map(|(name, _)| name.as_ref())
+    }
+
     /// Iterates over the contained name-value pairs.
     pub fn iter(&self) -> TracedValuesIter<'_, S> {
         TracedValuesIter {
@@ -247,20 +418,36 @@ impl<'de> Deserialize<'de> for TracedValues<String> {
     }
 }
 
-struct TracedValueVisitor<S> {
+/// Converter for values recorded via their [`Debug`](fmt::Debug) implementation, used in
+/// [`TracedValues::from_event_with()`] and [`ConversionOptions::with_debug_converters()`].
+/// Returns `None` if the converter is not applicable to the value.
+pub type DebugValueConverter = dyn Fn(&Field, &dyn fmt::Debug) -> Option<TracedValue> + Send + Sync;
+
+struct TracedValueVisitor<'c, S> {
     values: TracedValues<S>,
+    options: ConversionOptions<'c>,
 }
 
-impl<S: AsRef<str>> fmt::Debug for TracedValueVisitor<S> {
+impl<'c, S: AsRef<str>> TracedValueVisitor<'c, S> {
+    fn new(capacity: usize, options: &ConversionOptions<'c>) -> Self {
+        Self {
+            values: TracedValues::with_capacity(capacity),
+            options: *options,
+        }
+    }
+}
+
+impl<S: AsRef<str>> fmt::Debug for TracedValueVisitor<'_, S> {
     fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
         formatter
             .debug_struct("ValueVisitor")
             .field("values", &self.values)
+            .field("options", &self.options)
             .finish()
     }
 }
 
-impl<S: From<&'static str> + AsRef<str>> Visit for TracedValueVisitor<S> {
+impl<S: From<&'static str> + AsRef<str>> Visit for TracedValueVisitor<'_, S> {
     fn record_f64(&mut self, field: &Field, value: f64) {
         self.values.insert(field.name().into(), value.into());
     }
@@ -296,7 +483,298 @@ impl<S: From<&'static str> + AsRef<str>> Visit for TracedValueVisitor<S> {
     }
 
     fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
-        self.values
-            .insert(field.name().into(), TracedValue::debug(value));
+        let converted = self
+            .options
+            .debug_converters
+            .iter()
+            .find_map(|converter| converter(field, value));
+        let value = converted
+            .unwrap_or_else(|| TracedValue::debug_with_limit(value, self.options.debug_limit));
+        self.values.insert(field.name().into(), value);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tracing_core::{
+        span::{Attributes, Id},
+        Metadata, Subscriber,
+    };
+
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+    use crate::DebugObject;
+
+    #[derive(Default)]
+    struct ValuesSubscriber {
+        values: Arc<Mutex<Vec<TracedValues<&'static str>>>>,
+        options: ConversionOptions<'static>,
+    }
+
+    impl Subscriber for ValuesSubscriber {
+        fn enabled(&self, _metadata: &Metadata<'_>) -> bool {
+            true
+        }
+
+        fn new_span(&self, span: &Attributes<'_>) -> Id {
+            let values = TracedValues::from_values_with_options(span.values(), &self.options);
+            self.values.lock().unwrap().push(values);
+            Id::from_u64(1)
+        }
+
+        fn record(&self, _span: &Id, values: &Record<'_>) {
+            let values = TracedValues::from_record_with_options(values, &self.options);
+            self.values.lock().unwrap().push(values);
+        }
+
+        fn record_follows_from(&self, _span: &Id, _follows: &Id) {}
+
+        fn event(&self, event: &Event<'_>) {
+            let values = TracedValues::from_event_with_options(event, &self.options);
+            self.values.lock().unwrap().push(values);
+        }
+
+        fn enter(&self, _span: &Id) {}
+
+        fn exit(&self, _span: &Id) {}
+    }
+
+    #[test]
+    fn values_are_preallocated() {
+        let mut values = TracedValues::with_capacity(3);
+        let initial_capacity = values.capacity();
+        assert!(initial_capacity >= 3);
+        for (name, value) in [("a", 1_i64), ("b", 2), ("c", 3)] {
+            values.insert(name, value.into());
+        }
+        assert_eq!(values.len(), 3);
+        assert_eq!(values.capacity(), initial_capacity);
+
+        let subscriber = ValuesSubscriber::default();
+        let recorded_values = Arc::clone(&subscriber.values);
+        tracing::subscriber::with_default(subscriber, || {
+            let span = tracing::info_span!("test", a = 1, b = "2", c = tracing::field::Empty);
+            span.record("c", 3.0);
+            tracing::info!(a = 1, b = true, c = "test", d = 1.0, "event");
+        });
+
+        let recorded_values = recorded_values.lock().unwrap();
+        let lengths: Vec<_> = recorded_values.iter().map(TracedValues::len).collect();
+        assert_eq!(lengths, [2, 1, 5]);
+        // Capacity is sufficient for the number of fields including empty ones.
+        let field_counts = [3, 1, 5];
+        for (values, field_count) in recorded_values.iter().zip(field_counts) {
+            assert!(values.capacity() >= field_count, "{values:?}");
+        }
+    }
+
+    #[test]
+    fn comparing_values() {
+        let values = TracedValues::from_iter([("a", 1_i64.into()), ("b", "test".into())]);
+        let reordered = TracedValues::from_iter([("b", "test".into()), ("a", 1_i64.into())]);
+        assert_eq!(values, reordered);
+        assert_eq!(reordered, values);
+        let owned: TracedValues<String> = values
+            .iter()
+            .map(|(name, value)| (name.to_owned(), value.clone()))
+            .collect();
+        assert_eq!(values, owned);
+
+        let mut updated = reordered.clone();
+        updated.insert("a", 2_i64.into());
+        assert_ne!(values, updated);
+        let mut extended = reordered.clone();
+        extended.insert("c", true.into());
+        assert_ne!(values, extended);
+        assert_ne!(extended, values);
+        let renamed = TracedValues::from_iter([("a", 1_i64.into()), ("c", "test".into())]);
+        assert_ne!(values, renamed);
+        assert_eq!(TracedValues::<String>::new(), TracedValues::<&str>::new());
+    }
+
+    #[test]
+    fn checking_value_names() {
+        let values = TracedValues::from_iter([("a", 1_i64.into()), ("b", "test".into())]);
+        assert!(values.contains_key("a"));
+        assert!(values.contains_key("b"));
+        assert!(!values.contains_key("c"));
+        assert_eq!(values.keys().collect::<Vec<_>>(), ["a", "b"]);
+
+        let empty = TracedValues::<String>::new();
+        assert!(!empty.contains_key("a"));
+        assert_eq!(empty.keys().count(), 0);
+    }
+
+    #[test]
+    fn recorded_none_is_distinguished_from_string() {
+        let subscriber = ValuesSubscriber::default();
+        let recorded_values = Arc::clone(&subscriber.values);
+        tracing::subscriber::with_default(subscriber, || {
+            let maybe_value: Option<u32> = None;
+            let some_value = Some(42_u32);
+            tracing::info!(none = ?maybe_value, some = ?some_value, string = "None", "event");
+        });
+
+        let recorded_values = recorded_values.lock().unwrap();
+        let values = &recorded_values[0];
+        assert!(values["none"].is_none_debug());
+        assert!(!values["some"].is_none_debug());
+        assert!(values["some"].is_debug(&Some(42_u32)));
+        assert!(!values["string"].is_none_debug());
+        assert_eq!(values["string"], "None");
+    }
+
+    #[test]
+    fn truncating_large_debug_values() {
+        const LIMIT: usize = 4_096;
+
+        let subscriber = ValuesSubscriber {
+            options: ConversionOptions::new().with_debug_limit(LIMIT),
+            ..ValuesSubscriber::default()
+        };
+        let recorded_values = Arc::clone(&subscriber.values);
+        tracing::subscriber::with_default(subscriber, || {
+            let huge_vec = vec![42_u64; 1_000_000];
+            let small_vec = vec![42_u64; 10];
+            tracing::info!(huge = ?huge_vec, small = ?small_vec, "event");
+        });
+
+        let recorded_values = recorded_values.lock().unwrap();
+        let values = &recorded_values[0];
+        let TracedValue::Object(huge) = &values["huge"] else {
+            panic!("unexpected value: {:?}", values["huge"]);
+        };
+        assert!(huge.is_truncated());
+        let huge = huge.as_ref();
+        assert_eq!(huge.len(), LIMIT + DebugObject::TRUNCATION_MARKER.len());
+        assert!(huge.starts_with("[42, 42, "));
+
+        let TracedValue::Object(small) = &values["small"] else {
+            panic!("unexpected value: {:?}", values["small"]);
+        };
+        assert!(!small.is_truncated());
+        assert!(values["small"].is_debug(&[42_u64; 10]));
+    }
+
+    /// Object with `Debug` output ending with the truncation marker.
+    struct Ellipsis;
+
+    impl fmt::Debug for Ellipsis {
+        fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+            formatter.write_str("wait…")
+        }
+    }
+
+    /// `Debug` implementation that ignores formatting errors.
+    struct Stubborn;
+
+    impl fmt::Debug for Stubborn {
+        fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+            formatter.write_str("Stubborn").ok();
+            formatter.write_str(" and long").ok();
+            formatter.write_str("!")
+        }
+    }
+
+    #[test]
+    fn truncation_flag_is_explicit() {
+        let options = ConversionOptions::new().with_debug_limit(12);
+        let subscriber = ValuesSubscriber {
+            options,
+            ..ValuesSubscriber::default()
+        };
+        let recorded_values = Arc::clone(&subscriber.values);
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info!(ellipsis = ?Ellipsis, stubborn = ?Stubborn, "event");
+        });
+
+        let recorded_values = recorded_values.lock().unwrap();
+        let values = &recorded_values[0];
+        let TracedValue::Object(ellipsis) = &values["ellipsis"] else {
+            panic!("unexpected value: {:?}", values["ellipsis"]);
+        };
+        assert!(ellipsis.as_ref().ends_with(DebugObject::TRUNCATION_MARKER));
+        assert!(!ellipsis.is_truncated());
+
+        let TracedValue::Object(stubborn) = &values["stubborn"] else {
+            panic!("unexpected value: {:?}", values["stubborn"]);
+        };
+        assert!(stubborn.is_truncated());
+        // Writes after truncation must be ignored.
+        assert_eq!(stubborn.as_ref(), "Stubborn and…");
+
+        // The flag is preserved during serialization.
+        let json = serde_json::to_value(&values["stubborn"]).unwrap();
+        let restored: TracedValue = serde_json::from_value(json).unwrap();
+        assert_eq!(restored, values["stubborn"]);
+        let json = serde_json::to_value(&values["ellipsis"]).unwrap();
+        assert_eq!(json, serde_json::json!({ "object": "wait…" }));
+        let restored: TracedValue = serde_json::from_value(json).unwrap();
+        assert_eq!(restored, values["ellipsis"]);
+    }
+
+    struct RequestId(u64);
+
+    impl fmt::Debug for RequestId {
+        fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(formatter, "RequestId({})", self.0)
+        }
+    }
+
+    fn convert_request_id(field: &Field, value: &dyn fmt::Debug) -> Option<TracedValue> {
+        if field.name() != "request_id" {
+            return None;
+        }
+        let value = format!("{value:?}");
+        let value = value.strip_prefix("RequestId(")?.strip_suffix(')')?;
+        value.parse::<u64>().ok().map(TracedValue::from)
+    }
+
+    #[test]
+    fn converting_custom_debug_values() {
+        let subscriber = ValuesSubscriber {
+            options: ConversionOptions::new().with_debug_converters(&[&convert_request_id]),
+            ..ValuesSubscriber::default()
+        };
+        let recorded_values = Arc::clone(&subscriber.values);
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info!(request_id = ?RequestId(42), other_id = ?RequestId(23), "event");
+        });
+
+        let recorded_values = recorded_values.lock().unwrap();
+        let values = &recorded_values[0];
+        assert_eq!(values["request_id"], 42_u64);
+        // Converter is not applied to other fields.
+        assert!(values["other_id"].is_debug(&RequestId(23)));
+        // ...or to the message, which is also recorded as `Debug`.
+        assert_eq!(values["message"].as_debug_str(), Some("event"));
+    }
+
+    #[test]
+    fn converting_custom_debug_values_in_spans() {
+        let options = ConversionOptions::new()
+            .with_debug_limit(8)
+            .with_debug_converters(&[&convert_request_id]);
+        let subscriber = ValuesSubscriber {
+            options,
+            ..ValuesSubscriber::default()
+        };
+        let recorded_values = Arc::clone(&subscriber.values);
+        tracing::subscriber::with_default(subscriber, || {
+            let span = tracing::info_span!("test", request_id = ?RequestId(42), other_id = 1);
+            span.record("other_id", tracing::field::debug(RequestId(23)));
+        });
+
+        let recorded_values = recorded_values.lock().unwrap();
+        let [span_values, record_values] = recorded_values.as_slice() else {
+            panic!("unexpected values: {recorded_values:?}");
+        };
+        assert_eq!(span_values["request_id"], 42_u64);
+        // The debug limit applies to values not handled by converters.
+        let TracedValue::Object(other_id) = &record_values["other_id"] else {
+            panic!("unexpected value: {record_values:?}");
+        };
+        assert!(other_id.is_truncated());
     }
 }
@@ -2,20 +2,23 @@
 
 use assert_matches::assert_matches;
 use once_cell::sync::Lazy;
-use tracing_core::{Level, Subscriber};
+use tracing_core::{span::Id, Level, Subscriber};
 use tracing_subscriber::{registry::LookupSpan, FmtSubscriber};
 
 use std::{
     borrow::Cow,
     collections::{HashMap, HashSet},
-    iter, thread,
+    iter,
+    sync::{Arc, Mutex},
+    thread,
+    time::{Duration, Instant},
 };
 
 mod fib;
 
 use tracing_tunnel::{
-    CallSiteKind, LocalSpans, PersistedMetadata, PersistedSpans, TracedValue, TracingEvent,
-    TracingEventReceiver, TracingLevel,
+    CallSiteKind, LocalSpans, PersistedMetadata, PersistedSpans, TimedTracingEvent, TracedValue,
+    TracingEvent, TracingEventReceiver, TracingEventSender, TracingLevel,
 };
 
 #[derive(Debug)]
@@ -76,6 +79,31 @@ fn assert_span_management(events: &[TracingEvent]) {
     assert!(open_spans.is_empty());
 }
 
+#[cfg(feature = "test-util")]
+#[test]
+fn replaying_generated_streams() {
+    for seed in 0..200 {
+        let events = tracing_tunnel::generate::valid_stream(seed);
+        assert_span_management(&events);
+        let span_count = events
+            .iter()
+            .filter(|event| matches!(event, TracingEvent::NewSpan { .. }))
+            .count();
+
+        let mut receiver = TracingEventReceiver::default().with_strict_fields();
+        tracing::subscriber::with_default(tracing_subscriber::registry(), || {
+            for (idx, event) in events.into_iter().enumerate() {
+                if let Err(err) = receiver.try_receive(event) {
+                    panic!("failed receiving event #{idx} for seed {seed}: {err}");
+                }
+            }
+        });
+        let stats = receiver.stats();
+        assert_eq!(stats.spans_created, span_count, "seed {seed}");
+        assert_eq!(stats.errors, 0, "seed {seed}");
+    }
+}
+
 #[test]
 fn call_sites_for_tracing_events() {
     let events = &EVENTS.long;
@@ -323,3 +351,209 @@ fn assert_valid_refs(events: &[TracingEvent]) {
         }
     }
 }
+
+#[tracing::instrument]
+fn recurse(depth: usize) {
+    if depth > 0 {
+        recurse(depth - 1);
+    }
+}
+
+#[test]
+fn limiting_span_depth_in_sender() {
+    const MAX_DEPTH: usize = 10;
+
+    let events = Arc::new(Mutex::new(vec![]));
+    let events_ = Arc::clone(&events);
+    let sender = TracingEventSender::new(move |event| events_.lock().unwrap().push(event))
+        .with_max_depth(MAX_DEPTH);
+    tracing::subscriber::with_default(sender, || recurse(100));
+
+    let events = events.lock().unwrap();
+    assert_valid_refs(&events);
+    assert_span_management(&events);
+
+    let entered_count = events
+        .iter()
+        .filter(|event| matches!(event, TracingEvent::SpanEntered { .. }))
+        .count();
+    assert_eq!(entered_count, MAX_DEPTH);
+    let exited_count = events
+        .iter()
+        .filter(|event| matches!(event, TracingEvent::SpanExited { .. }))
+        .count();
+    assert_eq!(exited_count, MAX_DEPTH);
+}
+
+#[test]
+fn unbalanced_exit_does_not_break_span_depth_limit() {
+    const MAX_DEPTH: usize = 10;
+
+    let events = Arc::new(Mutex::new(vec![]));
+    let events_ = Arc::clone(&events);
+    let sender = TracingEventSender::new(move |event| events_.lock().unwrap().push(event))
+        .with_max_depth(MAX_DEPTH);
+    // Emulate exiting a span entered before the sender was installed.
+    sender.exit(&Id::from_u64(1));
+    events.lock().unwrap().clear();
+    tracing::subscriber::with_default(sender, || recurse(100));
+
+    let events = events.lock().unwrap();
+    assert_span_management(&events);
+    let entered_count = events
+        .iter()
+        .filter(|event| matches!(event, TracingEvent::SpanEntered { .. }))
+        .count();
+    assert_eq!(entered_count, MAX_DEPTH);
+}
+
+#[test]
+fn delivering_events_in_background_thread() {
+    Lazy::force(&EVENTS);
+
+    let events = Arc::new(Mutex::new(vec![]));
+    let events_ = Arc::clone(&events);
+    let sender = TracingEventSender::spawn(move |event| {
+        thread::sleep(std::time::Duration::from_micros(100)); // emulate slow delivery
+        events_.lock().unwrap().push(event);
+    });
+    tracing::subscriber::with_default(sender, || fib::fib(5));
+    // The subscriber is dropped at this point, so all events should be delivered.
+
+    let events = events.lock().unwrap();
+    assert_valid_refs(&events);
+    assert_span_management(&events);
+    let last_message = events.iter().rev().find_map(|event| {
+        if let TracingEvent::NewEvent { values, .. } = event {
+            return values.get("message").and_then(TracedValue::as_debug_str);
+        }
+        None
+    });
+    assert_eq!(last_message, Some("computed Fibonacci number"));
+}
+
+#[test]
+fn timestamps_in_timed_events_are_monotonic() {
+    Lazy::force(&EVENTS);
+
+    let events = Arc::new(Mutex::new(vec![]));
+    let events_ = Arc::clone(&events);
+    let sender = TracingEventSender::new_timed(move |event| events_.lock().unwrap().push(event));
+    tracing::subscriber::with_default(sender, || fib::fib(5));
+
+    let events = events.lock().unwrap();
+    let timestamps: Vec<_> = events.iter().map(|event| event.timestamp).collect();
+    assert!(
+        timestamps.windows(2).all(|window| window[0] <= window[1]),
+        "{timestamps:?}"
+    );
+    assert!(timestamps.last() > timestamps.first());
+
+    let events: Vec<_> = events.iter().map(|timed| timed.event.clone()).collect();
+    assert_valid_refs(&events);
+    assert_span_management(&events);
+}
+
+#[test]
+fn ring_buffer_retains_last_events() {
+    const CAPACITY: usize = 3;
+
+    let (sender, ring) = TracingEventSender::ring_buffer(CAPACITY);
+    assert_eq!(ring.capacity(), CAPACITY);
+    tracing::subscriber::with_default(sender, || {
+        for i in 0_i64..10 {
+            tracing::info!(i, "iteration");
+        }
+    });
+
+    let events = ring.dump();
+    assert_eq!(events.len(), CAPACITY);
+    let indices: Vec<_> = events
+        .iter()
+        .map(|event| match event {
+            TracingEvent::NewEvent { values, .. } => values["i"].as_int().unwrap(),
+            _ => panic!("unexpected event: {event:?}"),
+        })
+        .collect();
+    assert_eq!(indices, [7, 8, 9]);
+}
+
+#[test]
+fn flush_markers_are_emitted_and_ignored_by_receiver() {
+    let events = Arc::new(Mutex::new(vec![]));
+    let events_ = Arc::clone(&events);
+    let sender = Arc::new(TracingEventSender::new(move |event| {
+        events_.lock().unwrap().push(event);
+    }));
+    tracing::subscriber::with_default(Arc::clone(&sender), || {
+        tracing::info_span!("workflow").in_scope(|| {
+            tracing::info!("step #1");
+            sender.flush_marker();
+            tracing::info!("step #2");
+        });
+    });
+
+    let events = events.lock().unwrap().clone();
+    let flush_positions: Vec<_> = events
+        .iter()
+        .enumerate()
+        .filter_map(|(i, event)| matches!(event, TracingEvent::Flush).then_some(i))
+        .collect();
+    assert_eq!(flush_positions.len(), 1);
+    let (before, after) = events.split_at(flush_positions[0]);
+    assert!(before
+        .iter()
+        .any(|event| matches!(event, TracingEvent::NewEvent { .. })));
+    assert!(after
+        .iter()
+        .any(|event| matches!(event, TracingEvent::NewEvent { .. })));
+    assert_eq!(TracingEvent::flush().render(), "flush");
+
+    let json = serde_json::to_string(&TracingEvent::Flush).unwrap();
+    let restored: TracingEvent = serde_json::from_str(&json).unwrap();
+    assert_matches!(restored, TracingEvent::Flush);
+
+    let mut receiver = TracingEventReceiver::default();
+    for event in events {
+        receiver.try_receive(event).unwrap();
+    }
+    assert_eq!(receiver.stats().errors, 0);
+}
+
+#[test]
+fn replaying_timed_events_without_delays() {
+    Lazy::force(&EVENTS);
+
+    let events = Arc::new(Mutex::new(vec![]));
+    let events_ = Arc::clone(&events);
+    let sender = TracingEventSender::new_timed(move |event| events_.lock().unwrap().push(event));
+    tracing::subscriber::with_default(sender, || fib::fib(5));
+    let events = events.lock().unwrap().clone();
+    let event_count = events.len();
+
+    let mut receiver = TracingEventReceiver::default();
+    tracing::subscriber::with_default(create_fmt_subscriber(), || {
+        receiver.replay_timed(events, 0.0).unwrap();
+    });
+    let stats = receiver.stats();
+    assert_eq!(stats.events_received, event_count);
+    assert_eq!(stats.errors, 0);
+}
+
+#[test]
+fn replaying_timed_events_with_scaled_delays() {
+    const DELAY: Duration = Duration::from_millis(20);
+
+    let delay_nanos = u64::try_from(DELAY.as_nanos()).unwrap();
+    let events = (0..3).map(|i| TimedTracingEvent {
+        timestamp: 1_000 + i * delay_nanos,
+        event: TracingEvent::flush(),
+    });
+
+    let mut receiver = TracingEventReceiver::default();
+    let start = Instant::now();
+    receiver.replay_timed(events, 0.5).unwrap();
+    // The last event should be replayed after `2 * DELAY * 0.5`.
+    assert!(start.elapsed() >= DELAY);
+    assert_eq!(receiver.stats().events_received, 3);
+}